@@ -0,0 +1,249 @@
+extern crate game_engine;
+
+use game_engine::action_map::ActionMap;
+use game_engine::app::{App, Engine};
+use game_engine::assets;
+use game_engine::ecs::{Light, Transform};
+use game_engine::gameobject::GameObject;
+use game_engine::material::Material;
+use game_engine::model::Model;
+use game_engine::physics_engine::PhysicsShape;
+use game_engine::quaternion::Quaternion;
+use game_engine::recording::InputRecording;
+use game_engine::resource_manager::Resource;
+use game_engine::runner::{self, HeadlessConfig, RunnerConfig};
+use game_engine::shader;
+use game_engine::texture;
+use game_engine::vector::Vector3;
+
+struct DemoGame {
+    default_ui: Resource,
+    menu_open: bool,
+}
+
+impl App for DemoGame {
+    fn init(&mut self, engine: &mut Engine) {
+        let display = engine.display.as_ref();
+        let res = &mut engine.resources;
+        let physics = &mut engine.physics;
+        let entities = &mut engine.entities;
+        let prefabs = &mut engine.prefabs;
+
+        engine.render.clear_color = [0.0, 0.3, 0.7];
+        engine.input.actions = ActionMap::load("./res/input.bindings");
+
+        let plane_model = res.alloc_model(Model::load(
+            display,
+            &assets::get_asset("./res/plane.obj"),
+            &assets::get_asset("./res/plane.mtl"),
+        ));
+
+        let bricks = res.alloc_tex(texture::load(
+            display,
+            &assets::get_asset("./res/nicebrick.jpg"),
+        ));
+        let bricksnrm = res.alloc_tex(texture::load(
+            display,
+            &assets::get_asset("./res/nicebrick_nrm.jpg"),
+        ));
+
+        self.default_ui = res.alloc_tex(texture::load(
+            display,
+            &assets::get_asset("./res/default_ui.jpg"),
+        ));
+
+        let basic_shader = res.alloc_shader(shader::load(
+            display,
+            &assets::get_asset("./res/basic.vs"),
+            &assets::get_asset("./res/basic.fs"),
+        ));
+
+        let ground_material =
+            Material::new(basic_shader, bricks, bricksnrm, [1.0, 1.0, 1.0], [5.0, 5.0]);
+
+        let plane = GameObject::new(
+            res,
+            "plane".to_string(),
+            Vector3::new(0.0, -5.0, 0.0),
+            Quaternion::new(0.0, 0.0, 0.0, 1.0),
+            plane_model,
+            ground_material,
+        )
+        .with_tag("ground")
+        .add_collider(physics, PhysicsShape::BoxShape);
+
+        plane.spawn(entities);
+
+        // a dim sun and a warm point light above the spheres
+        let sun = entities.create_entity();
+        entities.set_name(sun, "sun".to_string());
+        entities.transforms.insert(
+            sun,
+            Transform::new(
+                Vector3::new(0.0, 20.0, 0.0),
+                Quaternion::look_rotation(Vector3::new(-0.5, -1.0, 0.0)),
+            ),
+        );
+        entities
+            .lights
+            .insert(sun, Light::directional([1.0, 1.0, 1.0], 0.6));
+
+        let lamp = entities.create_entity();
+        entities.set_name(lamp, "lamp".to_string());
+        entities.transforms.insert(
+            lamp,
+            Transform::new(
+                Vector3::new(2.0, 17.0, 2.0),
+                Quaternion::new(0.0, 0.0, 0.0, 1.0),
+            ),
+        );
+        entities
+            .lights
+            .insert(lamp, Light::point([1.0, 0.7, 0.4], 4.0, 20.0));
+
+        let sphere_prefab = prefabs.load("./res/prefabs/sphere.prefab");
+        let brick_sphere_prefab = sphere_prefab.clone().with_overrides(&[
+            "diffuse ./res/nicebrick.jpg",
+            "normal ./res/nicebrick_nrm.jpg",
+            "tiling 1.0 1.0",
+        ]);
+
+        for x in 0..5 {
+            for y in 0..5 {
+                for z in 0..5 {
+                    let a = (x + y + z) % 2;
+
+                    let prefab = if a == 1 {
+                        &sphere_prefab
+                    } else {
+                        &brick_sphere_prefab
+                    };
+
+                    prefabs.instantiate(
+                        display,
+                        res,
+                        physics,
+                        entities,
+                        prefab,
+                        Transform::new(
+                            Vector3::new(x as f32, 10.0 + y as f32, z as f32),
+                            Quaternion::new(0.0, 0.0, 0.0, 1.0),
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    fn update(&mut self, engine: &mut Engine) {
+        // keep the cursor in the window while looking around
+        let looking = engine.input.action_pressed("look_lock");
+        engine.input.set_cursor_locked(looking);
+        engine.input.set_cursor_visible(!looking);
+
+        engine.render.camera.update(&engine.time, &engine.input);
+        engine.editor.visible = !self.menu_open;
+    }
+
+    fn render_gui(&mut self, engine: &mut Engine) {
+        let ui = &mut engine.ui;
+
+        // copy the width and height variables of ui context
+        let width = ui.win_width;
+        let height = ui.win_height;
+
+        ui.set_font_size(25);
+        ui.set_font_color([1.0, 1.0, 1.0, 1.0]);
+
+        if self.menu_open {
+            // Render a solid background color
+            ui.set_quad_color([0.1, 0.3, 0.5, 1.0]);
+            ui.render_quad(self.default_ui, 0.0, 0.0, width, height);
+
+            let close_button_x = width / 2.0;
+            let close_button_y = height / 2.0;
+
+            ui.set_quad_color([0.2, 0.2, 0.2, 0.8]);
+            // Render a button
+            if ui.render_button(
+                self.default_ui,
+                "Close",
+                close_button_x,
+                close_button_y,
+                40.0,
+                15.0,
+            ) {
+                self.menu_open = false;
+            }
+
+            ui.set_font_size(45);
+
+            // Render Title text
+            ui.render_text("Title Screen!", width / 2.0, height - 200.0);
+        } else {
+            let button_center_x = 40.0;
+            let button_center_y = height - 26.0 / 2.0;
+
+            ui.set_quad_color([0.2, 0.2, 0.2, 0.5]);
+
+            if ui.render_button(
+                self.default_ui,
+                "Menu",
+                button_center_x,
+                button_center_y,
+                40.0,
+                15.0,
+            ) {
+                self.menu_open = true;
+            }
+        }
+    }
+}
+
+fn main() {
+    assets::compile_assets();
+
+    let game = DemoGame {
+        default_ui: 0,
+        menu_open: false,
+    };
+
+    let args: Vec<String> = std::env::args().collect();
+
+    // cargo run --example demo -- -headless simulates a few seconds without opening a window,
+    // add -replay <file> to drive it with a recording made with -record <file>
+    if args.iter().any(|arg| arg == "-headless") {
+        let mut config = HeadlessConfig::new(180);
+        if let Some(path) = arg_value(&args, "-replay") {
+            let recording = InputRecording::load(&path).expect("Failed to load input recording");
+            config = HeadlessConfig::new(recording.len() as u64).with_replay(recording);
+        }
+
+        let mut game = game;
+        let engine = runner::run_headless(&mut game, config);
+
+        let sphere = engine.entities.find_by_name("sphere").unwrap();
+        let position = engine.entities.transforms.get(sphere).unwrap().position;
+
+        println!(
+            "Simulated {} frames, first sphere is at y = {}",
+            engine.time.frame_count(),
+            position.y
+        );
+
+        return;
+    }
+
+    let mut config = RunnerConfig::new("Rust Engine", 1024, 768);
+    config.record_input = arg_value(&args, "-record");
+    config.replay_input = arg_value(&args, "-replay");
+
+    runner::run(game, config);
+}
+
+fn arg_value(args: &[String], name: &str) -> Option<String> {
+    args.iter()
+        .position(|arg| arg == name)
+        .and_then(|idx| args.get(idx + 1))
+        .cloned()
+}
@@ -0,0 +1,172 @@
+// compares scene tree queries with a linear scan over the same boxes. the tree finds a few more
+// objects since it stores the boxes grown by its margin
+//
+//   cargo run --release --example scene_tree_bench [object count]
+extern crate game_engine;
+
+use game_engine::bounds::{Aabb, Frustum};
+use game_engine::camera::{CameraState, Ray};
+use game_engine::ecs::Entity;
+use game_engine::quaternion::Quaternion;
+use game_engine::scene_tree::SceneTree;
+use game_engine::vector::Vector3;
+use std::env;
+use std::time::Instant;
+
+const QUERIES: usize = 1000;
+const WORLD_SIZE: f32 = 1000.0;
+
+// small deterministic generator so every run uses the same scene
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self) -> f32 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (self.0 >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    fn point(&mut self) -> Vector3 {
+        Vector3::new(
+            self.next() * WORLD_SIZE,
+            self.next() * WORLD_SIZE,
+            self.next() * WORLD_SIZE,
+        )
+    }
+}
+
+fn main() {
+    let count: usize = env::args()
+        .nth(1)
+        .and_then(|count| count.parse().ok())
+        .unwrap_or(10000);
+
+    let mut random = Lcg(1);
+    let boxes: Vec<Aabb> = (0..count)
+        .map(|_| Aabb::from_center(random.point(), Vector3::new(1.0, 1.0, 1.0)))
+        .collect();
+
+    let start = Instant::now();
+    let mut tree = SceneTree::new();
+    for (index, bounds) in boxes.iter().enumerate() {
+        tree.insert(Entity::new(index, 0), *bounds);
+    }
+    println!("{} objects, build: {:?}", count, start.elapsed());
+
+    let regions: Vec<Aabb> = (0..QUERIES)
+        .map(|_| Aabb::from_center(random.point(), Vector3::new(20.0, 20.0, 20.0)))
+        .collect();
+
+    let start = Instant::now();
+    let mut found = 0;
+    for region in &regions {
+        found += boxes
+            .iter()
+            .filter(|bounds| bounds.intersects(region))
+            .count();
+    }
+    println!("aabb linear:    {:?} ({} found)", start.elapsed(), found);
+
+    let start = Instant::now();
+    let mut found = 0;
+    for region in &regions {
+        found += tree.query_aabb(region).len();
+    }
+    println!("aabb tree:      {:?} ({} found)", start.elapsed(), found);
+
+    let spheres: Vec<(Vector3, f32)> = (0..QUERIES)
+        .map(|_| (random.point(), 5.0 + random.next() * 20.0))
+        .collect();
+
+    let start = Instant::now();
+    let mut found = 0;
+    for &(center, radius) in &spheres {
+        found += boxes
+            .iter()
+            .filter(|bounds| bounds.intersects_sphere(center, radius))
+            .count();
+    }
+    println!("sphere linear:  {:?} ({} found)", start.elapsed(), found);
+
+    let start = Instant::now();
+    let mut found = 0;
+    for &(center, radius) in &spheres {
+        found += tree.query_sphere(center, radius).len();
+    }
+    println!("sphere tree:    {:?} ({} found)", start.elapsed(), found);
+
+    // cameras somewhere in the world looking at a random point, fewer of them since each one
+    // sees a good part of the scene
+    let mut camera = CameraState::new(1280, 720);
+    let frustums: Vec<Frustum> = (0..QUERIES / 10)
+        .map(|_| {
+            camera.set_transform(random.point(), Quaternion::new(0.0, 0.0, 0.0, 1.0));
+            camera.look_at(random.point());
+            Frustum::from_matrices(camera.get_perspective(), camera.get_view())
+        })
+        .collect();
+
+    let start = Instant::now();
+    let mut found = 0;
+    for frustum in &frustums {
+        found += boxes
+            .iter()
+            .filter(|bounds| frustum.intersects_aabb(bounds))
+            .count();
+    }
+    println!("frustum linear: {:?} ({} found)", start.elapsed(), found);
+
+    let start = Instant::now();
+    let mut found = 0;
+    for frustum in &frustums {
+        found += tree.query_frustum(frustum).len();
+    }
+    println!("frustum tree:   {:?} ({} found)", start.elapsed(), found);
+
+    let rays: Vec<Ray> = (0..QUERIES)
+        .map(|_| Ray {
+            origin: random.point(),
+            direction: (random.point() - random.point()).normalized(),
+        })
+        .collect();
+
+    let start = Instant::now();
+    let mut found = 0;
+    for ray in &rays {
+        found += boxes
+            .iter()
+            .filter(|bounds| bounds.intersect_ray(ray, WORLD_SIZE).is_some())
+            .count();
+    }
+    println!("ray linear:     {:?} ({} found)", start.elapsed(), found);
+
+    let start = Instant::now();
+    let mut found = 0;
+    for ray in &rays {
+        found += tree.query_ray(ray, WORLD_SIZE).len();
+    }
+    println!("ray tree:       {:?} ({} found)", start.elapsed(), found);
+
+    // every object moves a little, most stay inside their enlarged box
+    let start = Instant::now();
+    let mut reinserted = 0;
+    for (index, bounds) in boxes.iter().enumerate() {
+        let offset = Vector3::new(
+            random.next() - 0.5,
+            random.next() - 0.5,
+            random.next() - 0.5,
+        );
+        let moved = Aabb::new(bounds.min + offset * 0.5, bounds.max + offset * 0.5);
+
+        if tree.update(Entity::new(index, 0), moved) {
+            reinserted += 1;
+        }
+    }
+    println!(
+        "update:         {:?} ({} reinserted)",
+        start.elapsed(),
+        reinserted
+    );
+}
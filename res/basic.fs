@@ -1,49 +1,108 @@
 #version 140
 
-uniform vec3 light_dir;
-uniform float ambient_light;
+// has to match MAX_LIGHTS in lighting.rs
+#define MAX_LIGHTS 8
+
+#define DIRECTIONAL 0
+#define POINT 1
+#define SPOT 2
+
+uniform vec3 ambient_light;
 uniform sampler2D diffuse;
 uniform sampler2D normal_map;
 uniform vec3 view_pos;
-uniform vec3 color;
 uniform vec2 tiling;
 
+// the lights picked for this draw, strongest first
+uniform int light_count;
+uniform int light_kind[MAX_LIGHTS];
+uniform vec3 light_position[MAX_LIGHTS];
+uniform vec3 light_direction[MAX_LIGHTS];
+// colour times intensity
+uniform vec3 light_color[MAX_LIGHTS];
+uniform float light_range[MAX_LIGHTS];
+// constant, linear, quadratic
+uniform vec3 light_attenuation[MAX_LIGHTS];
+// cosines of the inner and outer spot angles
+uniform vec2 light_cone[MAX_LIGHTS];
+
 in vec3 _normal;
 in vec2 _texcoord;
 in vec3 _frag_pos;
 in mat3 _tbn_matrix;
+in vec4 _color;
 
 out vec4 result;
 
-vec4 CalcLight(vec3 direction, vec3 normal, vec3 worldPos)
+vec3 CalcLight(vec3 direction, vec3 color, vec3 normal, vec3 worldPos)
 {
     float specularIntensity = 2.0;
     float specularPower = 32;
-    float lightIntensity = 1.0;
 
 	float diffuseFactor = clamp(dot(normal, -direction), 0.0, 1.0);
-    
-    vec4 diffuseColor = vec4(0,0,0,0);
-    vec4 specularColor = vec4(0,0,0,0);
-    
+
+    vec3 diffuseColor = vec3(0, 0, 0);
+    vec3 specularColor = vec3(0, 0, 0);
+
     if(diffuseFactor > 0)
     {
-        diffuseColor = vec4(1.0, 1.0, 1.0, 1.0) * lightIntensity * diffuseFactor;
-        
+        diffuseColor = color * diffuseFactor;
+
         vec3 directionToEye = normalize(view_pos - worldPos);
         vec3 reflectDirection = normalize(reflect(direction, normal));
         float specularFactor = dot(directionToEye, reflectDirection);
         specularFactor = pow(specularFactor, specularPower);
-        
+
         if(specularFactor > 0)
         {
-            specularColor = vec4(1.0, 1.0, 1.0, 1.0) * specularIntensity * specularFactor;
+            specularColor = color * specularIntensity * specularFactor;
         }
     }
-    
+
     return diffuseColor + specularColor;
 }
 
+vec3 CalcLights(vec3 normal, vec3 worldPos)
+{
+    vec3 total = vec3(0, 0, 0);
+
+    for(int i = 0; i < MAX_LIGHTS; i++)
+    {
+        if(i >= light_count)
+            break;
+
+        if(light_kind[i] == DIRECTIONAL)
+        {
+            total += CalcLight(light_direction[i], light_color[i], normal, worldPos);
+            continue;
+        }
+
+        vec3 toLight = light_position[i] - worldPos;
+        float distance = length(toLight);
+
+        if(distance >= light_range[i])
+            continue;
+
+        vec3 direction = -toLight / distance;
+
+        // same falloff as SceneLight::attenuation_at, faded out towards the range so the edge
+        // doesn't show
+        vec3 att = light_attenuation[i];
+        float falloff = att.x + att.y * distance + att.z * distance * distance;
+        float attenuation = (1.0 - distance / light_range[i]) / max(falloff, 0.0001);
+
+        if(light_kind[i] == SPOT)
+        {
+            float cosAngle = dot(direction, light_direction[i]);
+            attenuation *= smoothstep(light_cone[i].y, light_cone[i].x, cosAngle);
+        }
+
+        total += CalcLight(direction, light_color[i], normal, worldPos) * attenuation;
+    }
+
+    return total;
+}
+
 void main() {
     vec2 texcoord = _texcoord * tiling;
 
@@ -53,6 +112,7 @@ void main() {
     normal = normalize(normal * 2.0 - 1.0);
     normal = normalize(_tbn_matrix * normal);
 
-    result = diffuseTex * (ambient_light + CalcLight(light_dir, normal, _frag_pos));            
-    result *= vec4(color, 1);
+    vec3 lighting = ambient_light + CalcLights(normal, _frag_pos);
+
+    result = vec4(diffuseTex.rgb * lighting, diffuseTex.a) * _color;
 }
\ No newline at end of file
@@ -2,19 +2,37 @@
 
 uniform mat4 persp_matrix;
 uniform mat4 view_matrix;
-uniform mat4 model_matrix;
 
 in vec3 position;
 in vec3 normal;
 in vec3 tangent;
 in vec2 texcoord;
 
+#ifdef INSTANCING
+// per instance
+in mat4 instance_model;
+in vec4 instance_color;
+#else
+// drivers without instanced arrays draw one object at a time
+uniform mat4 model_matrix;
+uniform vec3 color;
+uniform float opacity;
+#endif
+
 out vec2 _texcoord;
 out vec3 _normal;
 out vec3 _frag_pos;
 out mat3 _tbn_matrix;
+out vec4 _color;
 
 void main() {
+#ifdef INSTANCING
+    mat4 model_matrix = instance_model;
+    vec4 object_color = instance_color;
+#else
+    vec4 object_color = vec4(color, opacity);
+#endif
+
     mat4 mvp = persp_matrix * view_matrix * model_matrix;
 
     gl_Position = mvp * vec4(position, 1.0);
@@ -23,6 +41,7 @@ void main() {
 
     _normal = (model_matrix * vec4(normal, 0.0)).xyz;
     _texcoord = texcoord;
+    _color = object_color;
 
     vec3 n = normalize((model_matrix * vec4(normal, 0.0)).xyz);
 	vec3 t = normalize((model_matrix * vec4(tangent, 0.0)).xyz);
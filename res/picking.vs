@@ -2,15 +2,28 @@
 
 uniform mat4 persp_matrix;
 uniform mat4 view_matrix;
-uniform mat4 model_matrix;
-uniform uint id;
 
 in vec3 position;
+
+#ifdef INSTANCING
+// per instance
+in mat4 instance_model;
+in uint instance_id;
+#else
+uniform mat4 model_matrix;
+uniform uint object_id;
+#endif
+
 flat out uint v_id;
 
 void main() {
+#ifdef INSTANCING
+    mat4 mvp = persp_matrix * view_matrix * instance_model;
+    v_id = instance_id;
+#else
     mat4 mvp = persp_matrix * view_matrix * model_matrix;
+    v_id = object_id;
+#endif
 
     gl_Position = mvp * vec4(position, 1.0);
-    v_id = id;
 }
\ No newline at end of file
@@ -0,0 +1,541 @@
+// maps named actions and axes to keys, mouse buttons and mouse movement so game code never has to
+// check raw key codes. bindings are text files in the asset pack, one binding per line:
+//
+//   action look_lock key LControl
+//   action select mouse left
+//   action jump pad South
+//   axis move_x key A key D
+//   axis move_x pad_axis LeftStickX 1.0
+//   axis look_x mouse_x 0.02
+//   axis zoom wheel 1.0
+//
+// an action or axis can have any number of bindings, keys use their VirtualKeyCode names
+extern crate glium;
+
+use assets;
+use colored::*;
+use gamepad::{GamepadAxis, GamepadButton};
+use glium::glutin;
+use input::{key_from_name, mouse_button_from_name, mouse_button_name};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(glutin::VirtualKeyCode),
+    Mouse(glutin::MouseButton),
+    // the button on any connected pad
+    Gamepad(GamepadButton),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AxisBinding {
+    // -1 while the first binding is held and 1 while the second one is
+    Buttons(Binding, Binding),
+    // mouse movement since the last frame multiplied by the scale
+    MouseX(f32),
+    MouseY(f32),
+    // scrolled lines since the last frame multiplied by the scale
+    Wheel(f32),
+    // the axis with dead zones applied multiplied by the scale
+    Gamepad(GamepadAxis, f32),
+}
+
+// a binding used by more than one action or axis
+#[derive(Clone, Debug)]
+pub struct BindingConflict {
+    pub binding: Binding,
+    pub users: Vec<String>,
+}
+
+#[derive(Clone)]
+pub struct ActionMap {
+    actions: HashMap<String, Vec<Binding>>,
+    axes: HashMap<String, Vec<AxisBinding>>,
+}
+
+#[allow(dead_code)]
+impl ActionMap {
+    pub fn new() -> ActionMap {
+        ActionMap {
+            actions: HashMap::new(),
+            axes: HashMap::new(),
+        }
+    }
+
+    // the bindings the fly camera and editor expect, used until a binding file is loaded
+    pub fn default_bindings() -> ActionMap {
+        let mut map = ActionMap::new();
+
+        map.bind_action("look_lock", Binding::Key(glutin::VirtualKeyCode::LControl));
+        map.bind_axis(
+            "move_x",
+            AxisBinding::Buttons(
+                Binding::Key(glutin::VirtualKeyCode::A),
+                Binding::Key(glutin::VirtualKeyCode::D),
+            ),
+        );
+        map.bind_axis("move_x", AxisBinding::Gamepad(GamepadAxis::LeftStickX, 1.0));
+        map.bind_axis(
+            "move_z",
+            AxisBinding::Buttons(
+                Binding::Key(glutin::VirtualKeyCode::S),
+                Binding::Key(glutin::VirtualKeyCode::W),
+            ),
+        );
+        map.bind_axis("move_z", AxisBinding::Gamepad(GamepadAxis::LeftStickY, 1.0));
+        map.bind_axis("look_x", AxisBinding::MouseX(0.02));
+        map.bind_axis("look_y", AxisBinding::MouseY(0.02));
+
+        map.bind_action("orbit", Binding::Mouse(glutin::MouseButton::Right));
+        map.bind_action("pan", Binding::Mouse(glutin::MouseButton::Middle));
+        map.bind_axis("zoom", AxisBinding::Wheel(1.0));
+
+        map
+    }
+
+    // load a binding file from the asset pack
+    pub fn load(path: &str) -> ActionMap {
+        let source = assets::get_asset(path);
+
+        ActionMap::parse(&String::from_utf8_lossy(&source))
+    }
+
+    // write the bindings to a file on disk, used to keep bindings changed at runtime
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.serialize())
+    }
+
+    pub fn parse(source: &str) -> ActionMap {
+        let mut map = ActionMap::new();
+
+        for line in source.lines() {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+
+            if parts.is_empty() || parts[0].starts_with('#') {
+                continue;
+            }
+
+            let parsed = match (parts[0], parts.get(1)) {
+                ("action", Some(name)) => parse_binding(&parts[2..]).map(|binding| {
+                    map.bind_action(name, binding);
+                }),
+                ("axis", Some(name)) => parse_axis_binding(&parts[2..]).map(|binding| {
+                    map.bind_axis(name, binding);
+                }),
+                _ => None,
+            };
+
+            if parsed.is_none() {
+                println!("{}", format!("Invalid binding '{}'", line).red());
+            }
+        }
+
+        for conflict in map.conflicts() {
+            println!(
+                "{}",
+                format!(
+                    "{} is bound to {}",
+                    binding_to_string(conflict.binding),
+                    conflict.users.join(", ")
+                )
+                .yellow()
+            );
+        }
+
+        map
+    }
+
+    pub fn serialize(&self) -> String {
+        let mut out = String::new();
+
+        for name in sorted_keys(&self.actions) {
+            for binding in &self.actions[name] {
+                out.push_str(&format!(
+                    "action {} {}\n",
+                    name,
+                    binding_to_string(*binding)
+                ));
+            }
+        }
+
+        for name in sorted_keys(&self.axes) {
+            for binding in &self.axes[name] {
+                let binding = match *binding {
+                    AxisBinding::Buttons(negative, positive) => format!(
+                        "{} {}",
+                        binding_to_string(negative),
+                        binding_to_string(positive)
+                    ),
+                    AxisBinding::MouseX(scale) => format!("mouse_x {}", scale),
+                    AxisBinding::MouseY(scale) => format!("mouse_y {}", scale),
+                    AxisBinding::Wheel(scale) => format!("wheel {}", scale),
+                    AxisBinding::Gamepad(axis, scale) => format!("pad_axis {:?} {}", axis, scale),
+                };
+                out.push_str(&format!("axis {} {}\n", name, binding));
+            }
+        }
+
+        out
+    }
+
+    // add a binding to an action, returns the other actions and axes already using the binding
+    pub fn bind_action(&mut self, action: &str, binding: Binding) -> Vec<String> {
+        let mut users = self.users_of(binding);
+        users.retain(|user| user != action);
+
+        let bindings = self
+            .actions
+            .entry(action.to_string())
+            .or_insert_with(Vec::new);
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+
+        users
+    }
+
+    // replace every binding of an action with the given one
+    pub fn rebind_action(&mut self, action: &str, binding: Binding) -> Vec<String> {
+        self.clear_action(action);
+        self.bind_action(action, binding)
+    }
+
+    pub fn unbind_action(&mut self, action: &str, binding: Binding) {
+        if let Some(bindings) = self.actions.get_mut(action) {
+            bindings.retain(|b| *b != binding);
+        }
+    }
+
+    pub fn clear_action(&mut self, action: &str) {
+        self.actions.remove(action);
+    }
+
+    pub fn action_bindings(&self, action: &str) -> &[Binding] {
+        match self.actions.get(action) {
+            Some(bindings) => &bindings[..],
+            None => &[],
+        }
+    }
+
+    // add a binding to an axis, returns the other actions and axes already using its buttons
+    pub fn bind_axis(&mut self, axis: &str, binding: AxisBinding) -> Vec<String> {
+        let mut users = Vec::new();
+        if let AxisBinding::Buttons(negative, positive) = binding {
+            users.extend(self.users_of(negative));
+            users.extend(self.users_of(positive));
+        }
+        users.retain(|user| user != axis);
+        users.sort();
+        users.dedup();
+
+        let bindings = self.axes.entry(axis.to_string()).or_insert_with(Vec::new);
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+
+        users
+    }
+
+    pub fn rebind_axis(&mut self, axis: &str, binding: AxisBinding) -> Vec<String> {
+        self.clear_axis(axis);
+        self.bind_axis(axis, binding)
+    }
+
+    pub fn clear_axis(&mut self, axis: &str) {
+        self.axes.remove(axis);
+    }
+
+    pub fn axis_bindings(&self, axis: &str) -> &[AxisBinding] {
+        match self.axes.get(axis) {
+            Some(bindings) => &bindings[..],
+            None => &[],
+        }
+    }
+
+    // names of every action and axis the binding is part of
+    pub fn users_of(&self, binding: Binding) -> Vec<String> {
+        let mut users = Vec::new();
+
+        for name in sorted_keys(&self.actions) {
+            if self.actions[name].contains(&binding) {
+                users.push(name.clone());
+            }
+        }
+
+        for name in sorted_keys(&self.axes) {
+            let uses = self.axes[name]
+                .iter()
+                .any(|axis_binding| match *axis_binding {
+                    AxisBinding::Buttons(negative, positive) => {
+                        negative == binding || positive == binding
+                    }
+                    _ => false,
+                });
+
+            if uses {
+                users.push(name.clone());
+            }
+        }
+
+        users
+    }
+
+    // every binding that is used by more than one action or axis
+    pub fn conflicts(&self) -> Vec<BindingConflict> {
+        let mut bindings: Vec<Binding> = Vec::new();
+
+        for name in sorted_keys(&self.actions) {
+            bindings.extend(self.actions[name].iter().cloned());
+        }
+        for name in sorted_keys(&self.axes) {
+            for axis_binding in &self.axes[name] {
+                if let AxisBinding::Buttons(negative, positive) = *axis_binding {
+                    bindings.push(negative);
+                    bindings.push(positive);
+                }
+            }
+        }
+
+        let mut conflicts: Vec<BindingConflict> = Vec::new();
+        for binding in bindings {
+            if conflicts.iter().any(|conflict| conflict.binding == binding) {
+                continue;
+            }
+
+            let users = self.users_of(binding);
+            if users.len() > 1 {
+                conflicts.push(BindingConflict {
+                    binding: binding,
+                    users: users,
+                });
+            }
+        }
+
+        conflicts
+    }
+}
+
+fn sorted_keys<T>(map: &HashMap<String, T>) -> Vec<&String> {
+    let mut keys: Vec<&String> = map.keys().collect();
+    keys.sort();
+    keys
+}
+
+pub fn binding_to_string(binding: Binding) -> String {
+    match binding {
+        Binding::Key(key) => format!("key {:?}", key),
+        Binding::Mouse(button) => format!("mouse {}", mouse_button_name(button)),
+        Binding::Gamepad(button) => format!("pad {:?}", button),
+    }
+}
+
+// parses "key W", "mouse left" or "pad South"
+pub fn parse_binding(parts: &[&str]) -> Option<Binding> {
+    match (parts.first(), parts.get(1)) {
+        (Some(&"key"), Some(name)) => key_from_name(name).map(Binding::Key),
+        (Some(&"mouse"), Some(name)) => mouse_button_from_name(name).map(Binding::Mouse),
+        (Some(&"pad"), Some(name)) => GamepadButton::from_name(name).map(Binding::Gamepad),
+        _ => None,
+    }
+}
+
+// parses "key A key D", "mouse_x 0.02", "mouse_y 0.02", "wheel 1.0" or "pad_axis LeftStickX 1.0"
+fn parse_axis_binding(parts: &[&str]) -> Option<AxisBinding> {
+    match parts.first() {
+        Some(&"pad_axis") => Some(AxisBinding::Gamepad(
+            GamepadAxis::from_name(parts.get(1)?)?,
+            parts.get(2)?.parse().ok()?,
+        )),
+        Some(&"mouse_x") => Some(AxisBinding::MouseX(parts.get(1)?.parse().ok()?)),
+        Some(&"mouse_y") => Some(AxisBinding::MouseY(parts.get(1)?.parse().ok()?)),
+        Some(&"wheel") => Some(AxisBinding::Wheel(parts.get(1)?.parse().ok()?)),
+        Some(_) if parts.len() >= 4 => Some(AxisBinding::Buttons(
+            parse_binding(&parts[0..2])?,
+            parse_binding(&parts[2..4])?,
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(key: glutin::VirtualKeyCode) -> Binding {
+        Binding::Key(key)
+    }
+
+    #[test]
+    fn parse_reads_actions_and_axes() {
+        let map = ActionMap::parse(
+            "# comment\n\
+             action look_lock key LControl\n\
+             action select mouse left\n\
+             action select mouse 4\n\
+             action jump pad South\n\
+             \n\
+             axis move_x key A key D\n\
+             axis move_x pad_axis LeftStickX 0.5\n\
+             axis look_x mouse_x 0.02\n\
+             axis look_y mouse_y -0.02\n\
+             axis zoom wheel 2\n",
+        );
+
+        assert_eq!(
+            map.action_bindings("look_lock"),
+            &[key(glutin::VirtualKeyCode::LControl)]
+        );
+        assert_eq!(
+            map.action_bindings("select"),
+            &[
+                Binding::Mouse(glutin::MouseButton::Left),
+                Binding::Mouse(glutin::MouseButton::Other(4)),
+            ]
+        );
+        assert_eq!(
+            map.action_bindings("jump"),
+            &[Binding::Gamepad(GamepadButton::South)]
+        );
+        assert_eq!(
+            map.axis_bindings("move_x"),
+            &[
+                AxisBinding::Buttons(
+                    key(glutin::VirtualKeyCode::A),
+                    key(glutin::VirtualKeyCode::D)
+                ),
+                AxisBinding::Gamepad(GamepadAxis::LeftStickX, 0.5),
+            ]
+        );
+        assert_eq!(map.axis_bindings("look_x"), &[AxisBinding::MouseX(0.02)]);
+        assert_eq!(map.axis_bindings("look_y"), &[AxisBinding::MouseY(-0.02)]);
+        assert_eq!(map.axis_bindings("zoom"), &[AxisBinding::Wheel(2.0)]);
+    }
+
+    #[test]
+    fn malformed_lines_are_skipped() {
+        let map = ActionMap::parse(
+            "action\n\
+             action jump\n\
+             action jump key NotAKey\n\
+             action jump pad NotAButton\n\
+             action jump keyboard Space\n\
+             axis move_x key A\n\
+             axis move_x key A mouse nope\n\
+             axis look_x mouse_x fast\n\
+             axis look_x pad_axis LeftStickZ 1.0\n\
+             axis zoom wheel\n\
+             bind jump key Space\n\
+             action fire key Space\n",
+        );
+
+        assert!(map.action_bindings("jump").is_empty());
+        assert!(map.axis_bindings("move_x").is_empty());
+        assert!(map.axis_bindings("look_x").is_empty());
+        assert!(map.axis_bindings("zoom").is_empty());
+        // the good line after the bad ones still loads
+        assert_eq!(
+            map.action_bindings("fire"),
+            &[key(glutin::VirtualKeyCode::Space)]
+        );
+    }
+
+    #[test]
+    fn serialize_round_trips() {
+        let mut map = ActionMap::default_bindings();
+        map.bind_action("jump", Binding::Gamepad(GamepadButton::South));
+        map.bind_action("select", Binding::Mouse(glutin::MouseButton::Other(7)));
+        map.bind_axis("zoom", AxisBinding::Wheel(-0.25));
+
+        let source = map.serialize();
+        let parsed = ActionMap::parse(&source);
+
+        assert_eq!(parsed.serialize(), source);
+        for action in &["look_lock", "orbit", "pan", "jump", "select"] {
+            assert_eq!(parsed.action_bindings(action), map.action_bindings(action));
+        }
+        for axis in &["move_x", "move_z", "look_x", "look_y", "zoom"] {
+            assert_eq!(parsed.axis_bindings(axis), map.axis_bindings(axis));
+        }
+    }
+
+    #[test]
+    fn users_of_finds_actions_and_axis_buttons() {
+        let mut map = ActionMap::new();
+        map.bind_action("jump", key(glutin::VirtualKeyCode::Space));
+        map.bind_action("accept", key(glutin::VirtualKeyCode::Space));
+        map.bind_axis(
+            "move_z",
+            AxisBinding::Buttons(
+                key(glutin::VirtualKeyCode::S),
+                key(glutin::VirtualKeyCode::Space),
+            ),
+        );
+        map.bind_axis("zoom", AxisBinding::Wheel(1.0));
+
+        assert_eq!(
+            map.users_of(key(glutin::VirtualKeyCode::Space)),
+            vec!["accept", "jump", "move_z"]
+        );
+        assert_eq!(map.users_of(key(glutin::VirtualKeyCode::S)), vec!["move_z"]);
+        assert!(map.users_of(key(glutin::VirtualKeyCode::W)).is_empty());
+
+        let conflicts = map.conflicts();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].binding, key(glutin::VirtualKeyCode::Space));
+        assert_eq!(conflicts[0].users, vec!["accept", "jump", "move_z"]);
+    }
+
+    #[test]
+    fn binding_returns_the_other_users() {
+        let mut map = ActionMap::new();
+
+        assert!(map
+            .bind_action("jump", key(glutin::VirtualKeyCode::Space))
+            .is_empty());
+        // binding the same key again to the same action is not a conflict
+        assert!(map
+            .bind_action("jump", key(glutin::VirtualKeyCode::Space))
+            .is_empty());
+        assert_eq!(map.action_bindings("jump").len(), 1);
+
+        assert_eq!(
+            map.bind_action("accept", key(glutin::VirtualKeyCode::Space)),
+            vec!["jump"]
+        );
+        assert_eq!(
+            map.bind_axis(
+                "move_z",
+                AxisBinding::Buttons(
+                    key(glutin::VirtualKeyCode::Space),
+                    key(glutin::VirtualKeyCode::Space)
+                )
+            ),
+            vec!["accept", "jump"]
+        );
+
+        // rebinding drops the old bindings before looking for conflicts
+        assert_eq!(
+            map.rebind_action("jump", key(glutin::VirtualKeyCode::J)),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            map.action_bindings("jump"),
+            &[key(glutin::VirtualKeyCode::J)]
+        );
+        assert_eq!(
+            map.rebind_action("accept", key(glutin::VirtualKeyCode::J)),
+            vec!["jump"]
+        );
+        assert_eq!(
+            map.rebind_axis("move_z", AxisBinding::Wheel(1.0)),
+            Vec::<String>::new()
+        );
+        assert!(map.users_of(key(glutin::VirtualKeyCode::Space)).is_empty());
+
+        map.unbind_action("accept", key(glutin::VirtualKeyCode::J));
+        assert!(map.action_bindings("accept").is_empty());
+        assert_eq!(map.users_of(key(glutin::VirtualKeyCode::J)), vec!["jump"]);
+    }
+}
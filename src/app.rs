@@ -0,0 +1,98 @@
+extern crate glium;
+
+use ecs::EntityContext;
+use editor::Editor;
+use glium::glutin;
+use input::Input;
+use physics_engine::PhysicsContext;
+use prefab::PrefabContext;
+use renderer::RenderContext;
+use resource_manager::ResourceContext;
+use time::{ManualClock, Time};
+use ui_renderer::UIContext;
+
+// everything the runner owns, handed to the app in every lifecycle hook
+pub struct Engine {
+    // None when running headless
+    pub display: Option<glium::Display>,
+    pub render: RenderContext,
+    pub resources: ResourceContext,
+    pub physics: PhysicsContext,
+    pub entities: EntityContext,
+    pub prefabs: PrefabContext,
+    pub ui: UIContext,
+    pub input: Input,
+    pub time: Time,
+    pub editor: Editor,
+
+    pub cursor_position: Option<(i32, i32)>,
+
+    // set this to stop the runner at the end of the current frame
+    pub quit: bool,
+}
+
+impl Engine {
+    pub fn new(display: glium::Display, width: i32, height: i32) -> Engine {
+        Engine::create(Some(display), width, height, Time::new(), Input::new())
+    }
+
+    // an engine without a window or gl context, time only moves when the runner advances it
+    // and gamepads are left alone so the machine's pads can't leak into the simulation
+    pub fn new_headless(width: i32, height: i32) -> Engine {
+        let time = Time::with_clock(Box::new(ManualClock::new()));
+        let input = Input::with_gamepad_backend(None);
+
+        Engine::create(None, width, height, time, input)
+    }
+
+    fn create(
+        display: Option<glium::Display>,
+        width: i32,
+        height: i32,
+        time: Time,
+        input: Input,
+    ) -> Engine {
+        let render = RenderContext::new(width, height, display.as_ref());
+        let mut resources = ResourceContext::new();
+        let mut entities = EntityContext::new();
+        let ui = UIContext::new(display.as_ref(), width as f32, height as f32);
+        let editor = Editor::new(display.as_ref(), &mut entities, &mut resources);
+
+        Engine {
+            display: display,
+            render: render,
+            resources: resources,
+            physics: PhysicsContext::new(),
+            entities: entities,
+            prefabs: PrefabContext::new(),
+            ui: ui,
+            input: input,
+            time: time,
+            editor: editor,
+            cursor_position: None,
+            quit: false,
+        }
+    }
+
+    pub fn is_headless(&self) -> bool {
+        self.display.is_none()
+    }
+}
+
+// a game built on the engine, the runner calls these hooks in this order every frame:
+// fixed_update (zero or more times), update, render_gui and then on_event for each window event
+#[allow(unused_variables)]
+pub trait App {
+    fn init(&mut self, engine: &mut Engine);
+
+    // called once per physics step, before the step runs
+    fn fixed_update(&mut self, engine: &mut Engine) {}
+
+    fn update(&mut self, engine: &mut Engine) {}
+
+    fn render_gui(&mut self, engine: &mut Engine) {}
+
+    fn on_event(&mut self, engine: &mut Engine, event: &glutin::Event) {}
+
+    fn shutdown(&mut self, engine: &mut Engine) {}
+}
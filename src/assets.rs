@@ -14,6 +14,10 @@ use std::fs::Metadata;
 use std::io::prelude::*;
 use std::io::SeekFrom;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// set by set_read_from_disk, every asset is then read from res instead of the pack
+static READ_FROM_DISK: AtomicBool = AtomicBool::new(false);
 
 struct MetaFile {
     pub metadata: Metadata,
@@ -91,7 +95,21 @@ pub fn compile_assets() {
     }
 }
 
+// skip the pack and read every asset straight from res, used by the tests so they don't have
+// to build a pack in the working directory
+pub fn set_read_from_disk(read_from_disk: bool) {
+    READ_FROM_DISK.store(read_from_disk, Ordering::SeqCst);
+}
+
 pub fn get_asset(asset_path: &str) -> Vec<u8> {
+    if READ_FROM_DISK.load(Ordering::SeqCst) {
+        return get_asset_from_disk(asset_path);
+    }
+
+    get_asset_from_pack(asset_path)
+}
+
+fn get_asset_from_pack(asset_path: &str) -> Vec<u8> {
     // open idx and find file
     let mut reader = BinaryReader::new("data.idx", OpenType::Open);
 
@@ -137,3 +155,12 @@ pub fn get_asset(asset_path: &str) -> Vec<u8> {
 
     buffer
 }
+
+// reads the file under res directly instead of from the pack, so edits show up while the game
+// runs. falls back to the pack when the file isn't there, like in a shipped build
+pub fn get_asset_from_disk(asset_path: &str) -> Vec<u8> {
+    match fs::read(asset_path) {
+        Ok(buffer) => buffer,
+        Err(_) => get_asset_from_pack(asset_path),
+    }
+}
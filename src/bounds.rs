@@ -0,0 +1,416 @@
+// bounding volumes used for culling and scene queries
+use camera::Ray;
+use math_helper;
+use quaternion::Quaternion;
+use vector::Vector3;
+
+// axis aligned box in world space
+#[derive(Copy, Clone)]
+pub struct Aabb {
+    pub min: Vector3,
+    pub max: Vector3,
+}
+
+#[allow(dead_code)]
+impl Aabb {
+    pub fn new(min: Vector3, max: Vector3) -> Aabb {
+        Aabb { min: min, max: max }
+    }
+
+    pub fn from_center(center: Vector3, half_size: Vector3) -> Aabb {
+        Aabb {
+            min: center - half_size,
+            max: center + half_size,
+        }
+    }
+
+    // the world box around a rotated box, size is the full size like MeshRenderer::bounding_box
+    pub fn from_oriented(center: Vector3, size: [f32; 3], rotation: Quaternion) -> Aabb {
+        let right = rotation.right();
+        let up = rotation.up();
+        let forward = rotation.forward();
+        let half = [size[0] / 2.0, size[1] / 2.0, size[2] / 2.0];
+
+        // each world axis gets the projected lengths of the three rotated half axes
+        let extent =
+            |r: f32, u: f32, f: f32| r.abs() * half[0] + u.abs() * half[1] + f.abs() * half[2];
+        let half_size = Vector3::new(
+            extent(right.x, up.x, forward.x),
+            extent(right.y, up.y, forward.y),
+            extent(right.z, up.z, forward.z),
+        );
+
+        Aabb::from_center(center, half_size)
+    }
+
+    // the world box of a local box with the given centre and size placed at position
+    pub fn from_local(
+        position: Vector3,
+        rotation: Quaternion,
+        local_center: [f32; 3],
+        size: [f32; 3],
+    ) -> Aabb {
+        let offset = Vector3::new(local_center[0], local_center[1], local_center[2]);
+
+        Aabb::from_oriented(position + rotation.rotate(offset), size, rotation)
+    }
+
+    pub fn center(&self) -> Vector3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn half_size(&self) -> Vector3 {
+        (self.max - self.min) * 0.5
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: Vector3::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            max: Vector3::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        }
+    }
+
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x
+            && self.max.x >= other.min.x
+            && self.min.y <= other.max.y
+            && self.max.y >= other.min.y
+            && self.min.z <= other.max.z
+            && self.max.z >= other.min.z
+    }
+
+    pub fn contains(&self, other: &Aabb) -> bool {
+        self.min.x <= other.min.x
+            && self.min.y <= other.min.y
+            && self.min.z <= other.min.z
+            && self.max.x >= other.max.x
+            && self.max.y >= other.max.y
+            && self.max.z >= other.max.z
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        let size = self.max - self.min;
+        2.0 * (size.x * size.y + size.y * size.z + size.z * size.x)
+    }
+
+    pub fn expanded(&self, margin: f32) -> Aabb {
+        let margin = Vector3::new(margin, margin, margin);
+        Aabb {
+            min: self.min - margin,
+            max: self.max + margin,
+        }
+    }
+
+    // distance along the ray where it enters the box, 0 when it starts inside
+    pub fn intersect_ray(&self, ray: &Ray, max_distance: f32) -> Option<f32> {
+        let origin = ray.origin.raw();
+        let direction = ray.direction.raw();
+        let min = self.min.raw();
+        let max = self.max.raw();
+
+        let mut near = 0.0;
+        let mut far = max_distance;
+
+        for axis in 0..3 {
+            if direction[axis].abs() < 1e-8 {
+                if origin[axis] < min[axis] || origin[axis] > max[axis] {
+                    return None;
+                }
+                continue;
+            }
+
+            let t1 = (min[axis] - origin[axis]) / direction[axis];
+            let t2 = (max[axis] - origin[axis]) / direction[axis];
+
+            near = t1.min(t2).max(near);
+            far = t1.max(t2).min(far);
+
+            if near > far {
+                return None;
+            }
+        }
+
+        Some(near)
+    }
+
+    pub fn intersects_sphere(&self, center: Vector3, radius: f32) -> bool {
+        let closest = Vector3::new(
+            center.x.max(self.min.x).min(self.max.x),
+            center.y.max(self.min.y).min(self.max.y),
+            center.z.max(self.min.z).min(self.max.z),
+        );
+
+        (closest - center).length() <= radius
+    }
+}
+
+// a plane with the normal pointing to the inside, points with distance >= 0 are inside
+#[derive(Copy, Clone)]
+pub struct Plane {
+    pub normal: Vector3,
+    pub distance: f32,
+}
+
+impl Plane {
+    pub fn distance_to(&self, point: Vector3) -> f32 {
+        self.normal.dot(point) + self.distance
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct Frustum {
+    planes: [Plane; 6],
+}
+
+#[allow(dead_code)]
+impl Frustum {
+    // extracts the planes from the perspective and view matrices of a camera
+    pub fn from_matrices(persp_matrix: [[f32; 4]; 4], view_matrix: [[f32; 4]; 4]) -> Frustum {
+        // mat_mul works on the arrays as rows, so this is projection * view for the column-major
+        // matrices the shaders see
+        let m = math_helper::mat_mul(view_matrix, persp_matrix);
+        let row = |i: usize| [m[0][i], m[1][i], m[2][i], m[3][i]];
+        let (r0, r1, r2, r3) = (row(0), row(1), row(2), row(3));
+
+        let plane = |sign: f32, r: [f32; 4]| {
+            make_plane(
+                r3[0] + sign * r[0],
+                r3[1] + sign * r[1],
+                r3[2] + sign * r[2],
+                r3[3] + sign * r[3],
+            )
+        };
+
+        Frustum {
+            planes: [
+                plane(1.0, r0),
+                plane(-1.0, r0),
+                plane(1.0, r1),
+                plane(-1.0, r1),
+                plane(1.0, r2),
+                plane(-1.0, r2),
+            ],
+        }
+    }
+
+    pub fn planes(&self) -> &[Plane; 6] {
+        &self.planes
+    }
+
+    pub fn contains_point(&self, point: Vector3) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.distance_to(point) >= 0.0)
+    }
+
+    // conservative, boxes near the corners of the frustum may pass while being outside
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // the corner furthest along the plane normal
+            let corner = Vector3::new(
+                if plane.normal.x >= 0.0 {
+                    aabb.max.x
+                } else {
+                    aabb.min.x
+                },
+                if plane.normal.y >= 0.0 {
+                    aabb.max.y
+                } else {
+                    aabb.min.y
+                },
+                if plane.normal.z >= 0.0 {
+                    aabb.max.z
+                } else {
+                    aabb.min.z
+                },
+            );
+
+            plane.distance_to(corner) >= 0.0
+        })
+    }
+
+    pub fn intersects_sphere(&self, center: Vector3, radius: f32) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.distance_to(center) >= -radius)
+    }
+}
+
+// the far plane of an infinite projection has no normal, it is turned into a plane that
+// everything is in front of
+fn make_plane(a: f32, b: f32, c: f32, d: f32) -> Plane {
+    let normal = Vector3::new(a, b, c);
+    let length = normal.length();
+
+    if length < 1e-6 {
+        return Plane {
+            normal: Vector3::new(0.0, 0.0, 0.0),
+            distance: 1.0,
+        };
+    }
+
+    Plane {
+        normal: normal * (1.0 / length),
+        distance: d / length,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use camera::Projection;
+    use std::f32::consts::PI;
+
+    fn frustum(projection: Projection) -> Frustum {
+        // the camera sits at the origin looking down +z
+        Frustum::from_matrices(projection.matrix(1.0), math_helper::identity_matrix())
+    }
+
+    fn perspective_frustums() -> Vec<Frustum> {
+        let mut projections = Vec::new();
+        for &(infinite_far, reverse_z) in
+            &[(false, false), (false, true), (true, false), (true, true)]
+        {
+            let mut projection = Projection::perspective(PI / 2.0, 0.1, 100.0);
+            projection.infinite_far = infinite_far;
+            projection.reverse_z = reverse_z;
+            projections.push(projection);
+        }
+
+        projections.into_iter().map(frustum).collect()
+    }
+
+    fn cube(x: f32, y: f32, z: f32, half: f32) -> Aabb {
+        Aabb::from_center(Vector3::new(x, y, z), Vector3::new(half, half, half))
+    }
+
+    fn assert_near(a: Vector3, b: Vector3) {
+        assert!((a - b).length() < 1e-5, "{:?} != {:?}", a.raw(), b.raw());
+    }
+
+    #[test]
+    fn boxes_inside_outside_and_straddling() {
+        for frustum in perspective_frustums() {
+            // in front, behind and beside the camera
+            assert!(frustum.intersects_aabb(&cube(0.0, 0.0, 10.0, 1.0)));
+            assert!(!frustum.intersects_aabb(&cube(0.0, 0.0, -10.0, 1.0)));
+            assert!(!frustum.intersects_aabb(&cube(20.0, 0.0, 10.0, 1.0)));
+            assert!(!frustum.intersects_aabb(&cube(0.0, -20.0, 10.0, 1.0)));
+
+            // across the near plane and across a side plane
+            assert!(frustum.intersects_aabb(&cube(0.0, 0.0, 0.1, 1.0)));
+            assert!(frustum.intersects_aabb(&cube(10.5, 0.0, 10.0, 1.0)));
+
+            assert!(frustum.contains_point(Vector3::new(0.0, 0.0, 1.0)));
+            assert!(!frustum.contains_point(Vector3::new(0.0, 0.0, 0.01)));
+        }
+    }
+
+    #[test]
+    fn far_plane_clips_unless_infinite() {
+        let frustums = perspective_frustums();
+        let distant = cube(0.0, 0.0, 1000.0, 1.0);
+        let across_far = cube(0.0, 0.0, 100.0, 1.0);
+
+        for (index, frustum) in frustums.iter().enumerate() {
+            let infinite = index >= 2;
+
+            assert_eq!(frustum.intersects_aabb(&distant), infinite);
+            assert!(frustum.intersects_aabb(&across_far));
+            assert_eq!(
+                frustum.contains_point(Vector3::new(0.0, 0.0, 1.0e6)),
+                infinite
+            );
+        }
+    }
+
+    #[test]
+    fn reverse_z_gives_the_same_planes() {
+        let frustums = perspective_frustums();
+
+        for &(normal, reversed) in &[(0, 1), (2, 3)] {
+            for point in &[
+                Vector3::new(0.0, 0.0, 5.0),
+                Vector3::new(4.9, 0.0, 5.0),
+                Vector3::new(5.1, 0.0, 5.0),
+                Vector3::new(0.0, 0.0, 0.05),
+                Vector3::new(0.0, 0.0, 99.0),
+                Vector3::new(0.0, 0.0, 101.0),
+            ] {
+                assert_eq!(
+                    frustums[normal].contains_point(*point),
+                    frustums[reversed].contains_point(*point)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn orthographic_frustum_is_a_box() {
+        let frustum = frustum(Projection::orthographic(5.0, 0.1, 100.0));
+
+        assert!(frustum.intersects_aabb(&cube(4.5, 4.5, 50.0, 0.1)));
+        assert!(frustum.intersects_aabb(&cube(5.0, 0.0, 50.0, 0.5)));
+        assert!(!frustum.intersects_aabb(&cube(6.0, 0.0, 50.0, 0.5)));
+        assert!(!frustum.intersects_aabb(&cube(0.0, 0.0, 101.0, 0.5)));
+    }
+
+    #[test]
+    fn from_oriented_without_rotation_keeps_the_size() {
+        let bounds = Aabb::from_oriented(
+            Vector3::new(1.0, 2.0, 3.0),
+            [2.0, 4.0, 6.0],
+            Quaternion::new(0.0, 0.0, 0.0, 1.0),
+        );
+
+        assert_near(bounds.min, Vector3::new(0.0, 0.0, 0.0));
+        assert_near(bounds.max, Vector3::new(2.0, 4.0, 6.0));
+    }
+
+    #[test]
+    fn from_oriented_follows_the_rotation() {
+        let quarter_turn = Quaternion::new_axis_angle(Vector3::new(0.0, 1.0, 0.0), PI / 2.0);
+        let bounds =
+            Aabb::from_oriented(Vector3::new(0.0, 0.0, 0.0), [2.0, 4.0, 6.0], quarter_turn);
+
+        // x and z swap
+        assert_near(bounds.half_size(), Vector3::new(3.0, 2.0, 1.0));
+
+        // a cube turned by 45 degrees grows by sqrt 2 on the axes it turned in
+        let eighth_turn = Quaternion::new_axis_angle(Vector3::new(0.0, 0.0, 1.0), PI / 4.0);
+        let bounds = Aabb::from_oriented(Vector3::new(0.0, 0.0, 0.0), [2.0, 2.0, 2.0], eighth_turn);
+
+        assert_near(
+            bounds.half_size(),
+            Vector3::new(2.0f32.sqrt(), 2.0f32.sqrt(), 1.0),
+        );
+    }
+
+    #[test]
+    fn from_oriented_contains_the_rotated_corners() {
+        let rotation =
+            Quaternion::new_axis_angle(Vector3::new(1.0, 1.0, 0.0).normalized(), 0.7).normalized();
+        let center = Vector3::new(5.0, -3.0, 2.0);
+        let bounds = Aabb::from_oriented(center, [1.0, 2.0, 3.0], rotation);
+        let grown = bounds.expanded(1e-4);
+
+        for &x in &[-0.5, 0.5] {
+            for &y in &[-1.0, 1.0] {
+                for &z in &[-1.5, 1.5] {
+                    let corner = center + rotation.rotate(Vector3::new(x, y, z));
+                    let point = Aabb::new(corner, corner);
+
+                    assert!(grown.contains(&point));
+                }
+            }
+        }
+    }
+}
@@ -1,29 +1,191 @@
 extern crate glium;
 
-use glium::glutin;
+use input::Input;
 use math_helper;
 use quaternion::Quaternion;
+use time::Time;
 use vector::Vector3;
 
+// keeps the orbit camera from flipping over the top
+const MAX_PITCH: f32 = 1.55;
+// how close and how far zooming can take the orbit camera
+const MIN_ORBIT_DISTANCE: f32 = 0.1;
+const MAX_ORBIT_DISTANCE: f32 = 1000.0;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ProjectionMode {
+    Perspective,
+    Orthographic,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Projection {
+    pub mode: ProjectionMode,
+    // vertical field of view in radians
+    pub fov: f32,
+    pub near: f32,
+    pub far: f32,
+    // half the height of the view in world units, used by the orthographic mode
+    pub ortho_size: f32,
+    // maps the near plane to the far end of the depth range, the renderer flips its depth test
+    // and clear value to match. the depth range stays -1..1 since no clip control is set up, so
+    // this doesn't gain any precision on its own, it only flips the depth order
+    pub reverse_z: bool,
+    // ignores far and never clips distant geometry, perspective only
+    pub infinite_far: bool,
+}
+
+#[allow(dead_code)]
+impl Projection {
+    pub fn perspective(fov: f32, near: f32, far: f32) -> Projection {
+        Projection {
+            mode: ProjectionMode::Perspective,
+            fov: fov,
+            near: near,
+            far: far,
+            ortho_size: 5.0,
+            reverse_z: false,
+            infinite_far: false,
+        }
+    }
+
+    pub fn orthographic(size: f32, near: f32, far: f32) -> Projection {
+        Projection {
+            mode: ProjectionMode::Orthographic,
+            ortho_size: size,
+            ..Projection::perspective(1.04719755, near, far)
+        }
+    }
+
+    // the depth test and clear value that go with this projection
+    pub fn depth_test(&self) -> glium::DepthTest {
+        if self.reverse_z {
+            glium::DepthTest::IfMore
+        } else {
+            glium::DepthTest::IfLess
+        }
+    }
+
+    pub fn clear_depth(&self) -> f32 {
+        if self.reverse_z {
+            0.0
+        } else {
+            1.0
+        }
+    }
+
+    pub fn matrix(&self, aspect_ratio: f32) -> [[f32; 4]; 4] {
+        let (near, far) = (self.near, self.far);
+
+        // note: remember that this is column-major, so the lines of code are actually columns
+        match self.mode {
+            ProjectionMode::Perspective => {
+                let f = 1.0 / (self.fov / 2.0).tan();
+
+                // clip z = z * a + b, the depth ends up as a + b / z
+                let (a, b) = match (self.infinite_far, self.reverse_z) {
+                    (false, false) => (
+                        (far + near) / (far - near),
+                        -(2.0 * far * near) / (far - near),
+                    ),
+                    (false, true) => (
+                        -(far + near) / (far - near),
+                        (2.0 * far * near) / (far - near),
+                    ),
+                    (true, false) => (1.0, -2.0 * near),
+                    (true, true) => (-1.0, 2.0 * near),
+                };
+
+                [
+                    [f / aspect_ratio, 0.0, 0.0, 0.0],
+                    [0.0, f, 0.0, 0.0],
+                    [0.0, 0.0, a, 1.0],
+                    [0.0, 0.0, b, 0.0],
+                ]
+            }
+            ProjectionMode::Orthographic => {
+                let height = self.ortho_size;
+                let width = self.ortho_size * aspect_ratio;
+
+                let mut a = 2.0 / (far - near);
+                let mut b = -(far + near) / (far - near);
+                if self.reverse_z {
+                    a = -a;
+                    b = -b;
+                }
+
+                [
+                    [1.0 / width, 0.0, 0.0, 0.0],
+                    [0.0, 1.0 / height, 0.0, 0.0],
+                    [0.0, 0.0, a, 0.0],
+                    [0.0, 0.0, b, 1.0],
+                ]
+            }
+        }
+    }
+}
+
+// orthographic editor views looking at the orbit focus
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ViewPreset {
+    // looking down
+    Top,
+    // looking along +z
+    Front,
+    // looking along -x
+    Side,
+}
+
+// a ray in world space, direction is normalized
+#[derive(Copy, Clone)]
+pub struct Ray {
+    pub origin: Vector3,
+    pub direction: Vector3,
+}
+
+impl Ray {
+    pub fn point_at(&self, distance: f32) -> Vector3 {
+        self.origin + self.direction * distance
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CameraMode {
+    // moves with move_x and move_z, looks around while look_lock is held
+    Fly,
+    // circles the focus point while orbit is held, pans while pan is held and zooms with zoom
+    Orbit,
+}
+
 pub struct CameraState {
     pub position: Vector3,
     pub rotation: Quaternion,
 
+    mode: CameraMode,
+
+    pub orbit_focus: Vector3,
+    pub orbit_distance: f32,
+    orbit_yaw: f32,
+    orbit_pitch: f32,
+
+    pub move_speed: f32,
+    pub pan_speed: f32,
+    pub zoom_speed: f32,
+
+    // seconds it takes to ease into a new orbit focus
+    pub transition_time: f32,
+    transition: Option<(Vector3, Quaternion, f32)>, // start position, start rotation, elapsed
+
     recalc_proj: bool,
     recalc_view: bool,
     view_matrix: [[f32; 4]; 4],
     proj_matrix: [[f32; 4]; 4],
 
-    aspect_ratio: f32,
-
-    moving_up: bool,
-    moving_left: bool,
-    moving_down: bool,
-    moving_right: bool,
-    moving_forward: bool,
-    moving_backward: bool,
+    projection: Projection,
 
-    mouse_locked: bool,
+    screen_width: i32,
+    screen_height: i32,
+    aspect_ratio: f32,
 }
 
 impl CameraState {
@@ -32,54 +194,324 @@ impl CameraState {
             position: Vector3::new(0.0, 0.0, 0.0),
             rotation: Quaternion::new(0.0, 0.0, 0.0, 1.0),
 
+            mode: CameraMode::Fly,
+
+            orbit_focus: Vector3::new(0.0, 0.0, 0.0),
+            orbit_distance: 10.0,
+            orbit_yaw: 0.0,
+            orbit_pitch: 0.0,
+
+            move_speed: 5.5,
+            pan_speed: 0.5,
+            zoom_speed: 0.1,
+
+            transition_time: 0.3,
+            transition: None,
+
             recalc_proj: true,
             recalc_view: true,
             view_matrix: math_helper::new_matrix(),
             proj_matrix: math_helper::new_matrix(),
+            projection: Projection::perspective(1.04719755, 0.1, 1024.0),
+
+            screen_width: screen_width,
+            screen_height: screen_height,
             aspect_ratio: screen_width as f32 / screen_height as f32,
-            moving_up: false,
-            moving_left: false,
-            moving_down: false,
-            moving_right: false,
-            moving_forward: false,
-            moving_backward: false,
-            mouse_locked: false,
         }
     }
 
+    pub fn get_screen_size(&self) -> (i32, i32) {
+        (self.screen_width, self.screen_height)
+    }
+
     pub fn resize(&mut self, width: i32, height: i32) {
+        self.screen_width = width;
+        self.screen_height = height;
         self.aspect_ratio = width as f32 / height as f32;
         self.recalc_proj = true;
     }
 
-    pub fn update(&mut self, delta_time: f32) {
-        let move_speed = 5.5;
-        if self.moving_forward {
+    pub fn update(&mut self, time: &Time, input: &Input) {
+        // the camera keeps moving while the game is paused or slowed down
+        let delta_time = time.unscaled_delta_time();
+
+        match self.mode {
+            CameraMode::Fly => self.update_fly(delta_time, input),
+            CameraMode::Orbit => self.update_orbit(delta_time, input),
+        }
+    }
+
+    pub fn get_mode(&self) -> CameraMode {
+        self.mode
+    }
+
+    // orbiting starts around the point orbit_distance in front and eases into the orbit view,
+    // which levels the roll and clamps the pitch. flying keeps the current view
+    pub fn set_mode(&mut self, mode: CameraMode) {
+        if mode == self.mode {
+            return;
+        }
+
+        if mode == CameraMode::Orbit {
             let forward = self.rotation.forward();
-            self.position.x += forward.x * move_speed * delta_time;
-            self.position.y += forward.y * move_speed * delta_time;
-            self.position.z += forward.z * move_speed * delta_time;
-            self.recalc_view = true;
+            self.orbit_focus = self.position + forward * self.orbit_distance;
+            self.orbit_yaw = forward.x.atan2(forward.z);
+            self.orbit_pitch = (-forward.y)
+                .max(-1.0)
+                .min(1.0)
+                .asin()
+                .max(-MAX_PITCH)
+                .min(MAX_PITCH);
+            self.transition = Some((self.position, self.rotation, 0.0));
+        } else {
+            // the fly camera starts wherever the orbit transition got to
+            self.transition = None;
+        }
+
+        self.mode = mode;
+    }
+
+    pub fn get_projection(&self) -> Projection {
+        self.projection
+    }
+
+    pub fn set_projection(&mut self, projection: Projection) {
+        self.projection = projection;
+        self.recalc_proj = true;
+    }
+
+    // switch between perspective and orthographic, the ortho size is picked so that the
+    // orbit focus stays the same size on screen
+    pub fn toggle_orthographic(&mut self) {
+        let mut projection = self.projection;
+
+        projection.mode = match projection.mode {
+            ProjectionMode::Perspective => {
+                projection.ortho_size = self.orbit_distance * (projection.fov / 2.0).tan();
+                ProjectionMode::Orthographic
+            }
+            ProjectionMode::Orthographic => ProjectionMode::Perspective,
+        };
+
+        self.set_projection(projection);
+    }
+
+    // orbit the focus from a fixed direction with an orthographic projection
+    pub fn set_view_preset(&mut self, preset: ViewPreset) {
+        self.set_mode(CameraMode::Orbit);
+        self.transition = Some((self.position, self.rotation, 0.0));
+
+        let (yaw, pitch) = match preset {
+            ViewPreset::Top => (0.0, MAX_PITCH),
+            ViewPreset::Front => (0.0, 0.0),
+            ViewPreset::Side => (-std::f32::consts::FRAC_PI_2, 0.0),
+        };
+        self.orbit_yaw = yaw;
+        self.orbit_pitch = pitch;
+
+        if self.projection.mode == ProjectionMode::Perspective {
+            self.toggle_orthographic();
+        }
+    }
+
+    // the ray going through a pixel, with 0,0 at the top left of the window
+    pub fn screen_ray(&self, x: f32, y: f32) -> Ray {
+        let ndc_x = 2.0 * x / self.screen_width as f32 - 1.0;
+        let ndc_y = 1.0 - 2.0 * y / self.screen_height as f32;
+
+        let forward = self.rotation.forward();
+        let right = self.rotation.right();
+        let up = self.rotation.up();
+
+        match self.projection.mode {
+            ProjectionMode::Perspective => {
+                let tan_half_fov = (self.projection.fov / 2.0).tan();
+                let direction = forward
+                    + right * (ndc_x * tan_half_fov * self.aspect_ratio)
+                    + up * (ndc_y * tan_half_fov);
+
+                Ray {
+                    origin: self.position,
+                    direction: direction.normalized(),
+                }
+            }
+            ProjectionMode::Orthographic => {
+                let size = self.projection.ortho_size;
+                let origin = self.position
+                    + right * (ndc_x * size * self.aspect_ratio)
+                    + up * (ndc_y * size);
+
+                Ray {
+                    origin: origin,
+                    direction: forward,
+                }
+            }
+        }
+    }
+
+    // the pixel a world point ends up on, None when it is behind the camera or the near plane
+    pub fn world_to_screen(&self, point: Vector3) -> Option<[f32; 2]> {
+        let relative = point - self.position;
+
+        let x = relative.dot(self.rotation.right());
+        let y = relative.dot(self.rotation.up());
+        let z = relative.dot(self.rotation.forward());
+
+        // in front of the near plane is clipped in both modes
+        if z <= self.projection.near {
+            return None;
+        }
+
+        let (ndc_x, ndc_y) = match self.projection.mode {
+            ProjectionMode::Perspective => {
+                let tan_half_fov = (self.projection.fov / 2.0).tan();
+                (
+                    x / (z * tan_half_fov * self.aspect_ratio),
+                    y / (z * tan_half_fov),
+                )
+            }
+            ProjectionMode::Orthographic => {
+                let size = self.projection.ortho_size;
+                (x / (size * self.aspect_ratio), y / size)
+            }
+        };
+
+        Some([
+            (ndc_x + 1.0) / 2.0 * self.screen_width as f32,
+            (1.0 - ndc_y) / 2.0 * self.screen_height as f32,
+        ])
+    }
+
+    // orbit around a new focus point, easing over from the current view
+    pub fn orbit_around(&mut self, focus: Vector3) {
+        self.set_mode(CameraMode::Orbit);
+        self.transition = Some((self.position, self.rotation, 0.0));
+        self.orbit_focus = focus;
+    }
+
+    // orbit around a bounding sphere at the distance where it fills the view
+    pub fn frame(&mut self, center: Vector3, radius: f32) {
+        self.orbit_around(center);
+
+        let fov = self.projection.fov;
+        let fov = if self.aspect_ratio < 1.0 {
+            // the horizontal fov is the narrow one on tall windows
+            2.0 * ((fov / 2.0).tan() * self.aspect_ratio).atan()
+        } else {
+            fov
+        };
+
+        // a little margin so the object doesn't touch the edges
+        let radius = radius.max(0.01) * 1.1;
+        self.orbit_distance = radius / (fov / 2.0).sin();
+
+        if self.projection.mode == ProjectionMode::Orthographic {
+            let mut projection = self.projection;
+            projection.ortho_size = radius / self.aspect_ratio.min(1.0);
+            self.set_projection(projection);
         }
-        if self.moving_backward {
+    }
+
+    fn update_fly(&mut self, delta_time: f32, input: &Input) {
+        let move_x = input.action_axis("move_x");
+        let move_z = input.action_axis("move_z");
+
+        if move_x != 0.0 || move_z != 0.0 {
             let forward = self.rotation.forward();
-            self.position.x -= forward.x * move_speed * delta_time;
-            self.position.y -= forward.y * move_speed * delta_time;
-            self.position.z -= forward.z * move_speed * delta_time;
+            let right = self.rotation.right();
+
+            self.position = self.position
+                + (forward * move_z + right * move_x) * (self.move_speed * delta_time);
             self.recalc_view = true;
         }
-        if self.moving_left {
+
+        if input.action_pressed("look_lock") {
+            let look_x = input.action_axis("look_x");
+            let look_y = input.action_axis("look_y");
             let right = self.rotation.right();
-            self.position.x -= right.x * move_speed * delta_time;
-            self.position.y -= right.y * move_speed * delta_time;
-            self.position.z -= right.z * move_speed * delta_time;
-            self.recalc_view = true;
+
+            if look_x != 0.0 {
+                self.rotate(Vector3::new(0.0, 1.0, 0.0), look_x);
+            }
+            if look_y != 0.0 {
+                self.rotate(right, look_y);
+            }
         }
-        if self.moving_right {
+    }
+
+    fn update_orbit(&mut self, delta_time: f32, input: &Input) {
+        let look_x = input.action_axis("look_x");
+        let look_y = input.action_axis("look_y");
+
+        if input.action_pressed("orbit") {
+            self.orbit_yaw += look_x;
+            self.orbit_pitch = (self.orbit_pitch + look_y).max(-MAX_PITCH).min(MAX_PITCH);
+        } else if input.action_pressed("pan") {
+            // drag the scene along with the cursor, faster when further away
             let right = self.rotation.right();
-            self.position.x += right.x * move_speed * delta_time;
-            self.position.y += right.y * move_speed * delta_time;
-            self.position.z += right.z * move_speed * delta_time;
+            let up = self.rotation.up();
+            let pan = self.pan_speed * self.orbit_distance;
+
+            self.orbit_focus = self.orbit_focus - right * (look_x * pan) + up * (look_y * pan);
+        }
+
+        let zoom = input.action_axis("zoom");
+        if zoom != 0.0 {
+            let scale = (1.0 - zoom * self.zoom_speed).max(0.1);
+            self.orbit_distance = (self.orbit_distance * scale)
+                .max(MIN_ORBIT_DISTANCE)
+                .min(MAX_ORBIT_DISTANCE);
+
+            // orthographic views zoom by changing their size, the distance doesn't show
+            if self.projection.mode == ProjectionMode::Orthographic {
+                let mut projection = self.projection;
+                projection.ortho_size = (projection.ortho_size * scale).max(0.01);
+                self.set_projection(projection);
+            }
+        }
+
+        let rotation =
+            Quaternion::new_axis_angle(Vector3::new(0.0, 1.0, 0.0), self.orbit_yaw).mul_quat(
+                Quaternion::new_axis_angle(Vector3::new(1.0, 0.0, 0.0), self.orbit_pitch),
+            );
+        let position = self.orbit_focus - rotation.forward() * self.orbit_distance;
+
+        match self.transition {
+            Some((start_position, start_rotation, elapsed)) => {
+                let elapsed = elapsed + delta_time;
+                let t = (elapsed / self.transition_time.max(0.0001)).min(1.0);
+                // smoothstep so the move eases in and out
+                let t = t * t * (3.0 - 2.0 * t);
+
+                self.position = start_position.lerp(position, t);
+                self.rotation = start_rotation.slerp(rotation, t);
+
+                self.transition = if t < 1.0 {
+                    Some((start_position, start_rotation, elapsed))
+                } else {
+                    None
+                };
+            }
+            None => {
+                self.position = position;
+                self.rotation = rotation;
+            }
+        }
+
+        self.recalc_view = true;
+    }
+
+    pub fn set_transform(&mut self, position: Vector3, rotation: Quaternion) {
+        self.position = position;
+        self.rotation = rotation;
+        self.recalc_view = true;
+    }
+
+    pub fn look_at(&mut self, target: Vector3) {
+        let direction = target - self.position;
+
+        if direction.length() > 0.0001 {
+            self.rotation = Quaternion::look_rotation(direction);
             self.recalc_view = true;
         }
     }
@@ -119,77 +551,292 @@ impl CameraState {
 
     pub fn get_perspective(&mut self) -> [[f32; 4]; 4] {
         if self.recalc_proj {
-            // 60 degrees fov
-            let fov: f32 = 1.04719755;
-            let zfar = 1024.0;
-            let znear = 0.1;
-
-            let f = 1.0 / (fov / 2.0).tan();
-
-            // note: remember that this is column-major, so the lines of code are actually columns
-            self.proj_matrix = [
-                [f / self.aspect_ratio, 0.0, 0.0, 0.0],
-                [0.0, f, 0.0, 0.0],
-                [0.0, 0.0, (zfar + znear) / (zfar - znear), 1.0],
-                [0.0, 0.0, -(2.0 * zfar * znear) / (zfar - znear), 0.0],
-            ];
+            self.proj_matrix = self.projection.matrix(self.aspect_ratio);
         }
         self.recalc_proj = false;
 
         self.proj_matrix
     }
+}
 
-    fn process_key(&mut self, event: &glutin::WindowEvent) {
-        let input = match *event {
-            glutin::WindowEvent::KeyboardInput { input, .. } => input,
-            _ => return,
-        };
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glium::glutin;
 
-        let pressed = input.state == glutin::ElementState::Pressed;
+    const WIDTH: i32 = 800;
+    const HEIGHT: i32 = 600;
 
-        let key = match input.virtual_keycode {
-            Some(key) => key,
-            None => return,
-        };
+    fn projections() -> Vec<Projection> {
+        let perspective = Projection::perspective(1.2, 0.1, 100.0);
 
-        match key {
-            glutin::VirtualKeyCode::Up => self.moving_up = pressed,
-            glutin::VirtualKeyCode::Down => self.moving_down = pressed,
-            glutin::VirtualKeyCode::A => self.moving_left = pressed,
-            glutin::VirtualKeyCode::D => self.moving_right = pressed,
-            glutin::VirtualKeyCode::W => self.moving_forward = pressed,
-            glutin::VirtualKeyCode::S => self.moving_backward = pressed,
-            glutin::VirtualKeyCode::LControl => self.mouse_locked = pressed,
-            _ => (),
-        };
+        let mut reverse_z = perspective;
+        reverse_z.reverse_z = true;
+
+        let mut infinite_far = perspective;
+        infinite_far.infinite_far = true;
+
+        let mut infinite_reverse_z = infinite_far;
+        infinite_reverse_z.reverse_z = true;
+
+        vec![
+            perspective,
+            reverse_z,
+            infinite_far,
+            infinite_reverse_z,
+            Projection::orthographic(4.0, 0.1, 100.0),
+        ]
     }
 
-    fn process_mouse(&mut self, event: &glutin::DeviceEvent) {
-        let delta = match *event {
-            glutin::DeviceEvent::MouseMotion { delta, .. } => delta,
-            _ => return,
-        };
+    fn camera(projection: Projection) -> CameraState {
+        let mut camera = CameraState::new(WIDTH, HEIGHT);
+        let rotation = Quaternion::new_axis_angle(Vector3::new(0.0, 1.0, 0.0), 0.7)
+            .mul_quat(Quaternion::new_axis_angle(Vector3::new(1.0, 0.0, 0.0), 0.3));
 
-        let mouse_delta_x = delta.0;
-        let mouse_delta_y = delta.1;
+        camera.set_transform(Vector3::new(1.0, 2.0, -3.0), rotation);
+        camera.set_projection(projection);
+        camera
+    }
 
-        if self.mouse_locked {
-            let right = self.rotation.right();
+    // the pixel and depth of a point going through the same matrices as the shaders
+    fn project(camera: &mut CameraState, point: Vector3) -> ([f32; 2], f32) {
+        let m = math_helper::mat_mul(camera.get_view(), camera.get_perspective());
+        let p = [point.x, point.y, point.z, 1.0];
+        let clip: Vec<f32> = (0..4)
+            .map(|row| (0..4).map(|col| m[col][row] * p[col]).sum::<f32>())
+            .collect();
+
+        let (ndc_x, ndc_y, ndc_z) = (clip[0] / clip[3], clip[1] / clip[3], clip[2] / clip[3]);
+        (
+            [
+                (ndc_x + 1.0) / 2.0 * WIDTH as f32,
+                (1.0 - ndc_y) / 2.0 * HEIGHT as f32,
+            ],
+            ndc_z,
+        )
+    }
+
+    fn assert_pixel(a: [f32; 2], b: [f32; 2]) {
+        assert!(
+            (a[0] - b[0]).abs() < 0.01 && (a[1] - b[1]).abs() < 0.01,
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn screen_rays_land_back_on_their_pixel() {
+        for projection in projections() {
+            let mut camera = camera(projection);
+
+            for &(x, y) in &[(0.0, 0.0), (400.0, 300.0), (123.5, 456.25), (799.0, 1.0)] {
+                let ray = camera.screen_ray(x, y);
+
+                for &distance in &[0.5, 5.0, 50.0] {
+                    let point = ray.point_at(distance);
 
-            if mouse_delta_x != 0.0 {
-                self.rotate(Vector3::new(0.0, 1.0, 0.0), mouse_delta_x as f32 / 50.0);
+                    let screen = camera.world_to_screen(point).unwrap();
+                    assert_pixel(screen, [x, y]);
+
+                    // and the matrices the renderer uses agree
+                    let (pixel, depth) = project(&mut camera, point);
+                    assert_pixel(pixel, [x, y]);
+                    assert!(depth >= -1.0 && depth <= 1.0, "{}", depth);
+                }
             }
-            if mouse_delta_y != 0.0 {
-                self.rotate(right, mouse_delta_y as f32 / 50.0);
+        }
+    }
+
+    #[test]
+    fn reverse_z_flips_the_depth_order() {
+        for projection in projections() {
+            let mut camera = camera(projection);
+            let ray = camera.screen_ray(200.0, 100.0);
+
+            let (_, near) = project(&mut camera, ray.point_at(1.0));
+            let (_, far) = project(&mut camera, ray.point_at(10.0));
+
+            if projection.reverse_z {
+                assert!(near > far, "{:?}", projection);
+            } else {
+                assert!(near < far, "{:?}", projection);
             }
         }
     }
 
-    pub fn process_input(&mut self, event: &glutin::WindowEvent) {
-        self.process_key(event);
+    #[test]
+    fn points_behind_the_camera_are_not_on_screen() {
+        for projection in projections() {
+            let camera = camera(projection);
+            let forward = camera.rotation.forward();
+
+            let behind = camera.position - forward * 5.0;
+            let before_near = camera.position + forward * 0.05;
+
+            assert_eq!(camera.world_to_screen(behind), None, "{:?}", projection);
+            assert_eq!(
+                camera.world_to_screen(before_near),
+                None,
+                "{:?}",
+                projection
+            );
+        }
+    }
+
+    fn orbit_camera() -> CameraState {
+        let mut camera = CameraState::new(WIDTH, HEIGHT);
+        camera.set_mode(CameraMode::Orbit);
+        camera.transition = None;
+        camera
+    }
+
+    // one frame of input with the button held, the mouse moved and the wheel scrolled
+    fn orbit_frame(
+        camera: &mut CameraState,
+        button: Option<glutin::MouseButton>,
+        motion: [f32; 2],
+        scroll: f32,
+    ) {
+        let mut input = Input::with_gamepad_backend(None);
+        input.start_event_proc();
+        if let Some(button) = button {
+            input.set_mouse_button(button, true);
+        }
+        input.add_mouse_motion(motion[0], motion[1]);
+        input.add_scroll(0.0, scroll);
+
+        camera.update_orbit(1.0 / 60.0, &input);
+    }
+
+    #[test]
+    fn orbit_pitch_is_clamped() {
+        let mut camera = orbit_camera();
+
+        orbit_frame(
+            &mut camera,
+            Some(glutin::MouseButton::Right),
+            [0.0, 1000.0],
+            0.0,
+        );
+        assert_eq!(camera.orbit_pitch, MAX_PITCH);
+
+        orbit_frame(
+            &mut camera,
+            Some(glutin::MouseButton::Right),
+            [0.0, -5000.0],
+            0.0,
+        );
+        assert_eq!(camera.orbit_pitch, -MAX_PITCH);
+
+        // the mouse does nothing without the orbit button
+        orbit_frame(&mut camera, None, [0.0, 1000.0], 0.0);
+        assert_eq!(camera.orbit_pitch, -MAX_PITCH);
+    }
+
+    #[test]
+    fn zoom_stays_within_its_limits() {
+        let mut camera = orbit_camera();
+
+        orbit_frame(&mut camera, None, [0.0, 0.0], 1.0);
+        assert!((camera.orbit_distance - 9.0).abs() < 1e-5);
+
+        for _ in 0..100 {
+            orbit_frame(&mut camera, None, [0.0, 0.0], 50.0);
+        }
+        assert_eq!(camera.orbit_distance, MIN_ORBIT_DISTANCE);
+
+        for _ in 0..100 {
+            orbit_frame(&mut camera, None, [0.0, 0.0], -50.0);
+        }
+        assert_eq!(camera.orbit_distance, MAX_ORBIT_DISTANCE);
+    }
+
+    #[test]
+    fn pan_drags_the_focus_across_the_view() {
+        let mut camera = orbit_camera();
+        let start = camera.orbit_focus;
+        let (right, up) = (camera.rotation.right(), camera.rotation.up());
+
+        // look_x and look_y are 0.02 per pixel, pan moves pan_speed * distance per unit
+        orbit_frame(
+            &mut camera,
+            Some(glutin::MouseButton::Middle),
+            [50.0, 0.0],
+            0.0,
+        );
+        let moved = camera.orbit_focus - start;
+        assert!((moved.dot(right) + 5.0).abs() < 1e-4, "{:?}", moved.raw());
+        assert!(moved.dot(up).abs() < 1e-4);
+
+        let start = camera.orbit_focus;
+        orbit_frame(
+            &mut camera,
+            Some(glutin::MouseButton::Middle),
+            [0.0, 50.0],
+            0.0,
+        );
+        let moved = camera.orbit_focus - start;
+        assert!(moved.dot(right).abs() < 1e-4);
+        assert!((moved.dot(up) - 5.0).abs() < 1e-4, "{:?}", moved.raw());
+
+        // panning slides the camera with the focus instead of turning it
+        assert!(
+            ((camera.position - camera.orbit_focus).length() - camera.orbit_distance).abs() < 1e-4
+        );
+    }
+
+    #[test]
+    fn frame_fits_the_sphere_in_the_narrow_fov() {
+        let radius = 2.0;
+        let half_fov = 1.04719755f32 / 2.0;
+
+        let mut camera = CameraState::new(WIDTH, HEIGHT);
+        camera.frame(Vector3::new(1.0, 2.0, 3.0), radius);
+        assert_eq!(camera.get_mode(), CameraMode::Orbit);
+        assert_eq!(camera.orbit_focus.raw(), [1.0, 2.0, 3.0]);
+        assert!((camera.orbit_distance - radius * 1.1 / half_fov.sin()).abs() < 1e-4);
+
+        // on a tall window the horizontal fov decides
+        let mut camera = CameraState::new(HEIGHT, WIDTH);
+        camera.frame(Vector3::new(0.0, 0.0, 0.0), radius);
+        let half_horizontal = (half_fov.tan() * HEIGHT as f32 / WIDTH as f32).atan();
+        assert!((camera.orbit_distance - radius * 1.1 / half_horizontal.sin()).abs() < 1e-4);
+
+        let mut camera = CameraState::new(WIDTH, HEIGHT);
+        camera.set_projection(Projection::orthographic(5.0, 0.1, 100.0));
+        camera.frame(Vector3::new(0.0, 0.0, 0.0), radius);
+        assert!((camera.get_projection().ortho_size - radius * 1.1).abs() < 1e-5);
     }
 
-    pub fn process_input_device(&mut self, event: &glutin::DeviceEvent) {
-        self.process_mouse(event);
+    #[test]
+    fn switching_to_orbit_eases_into_the_view() {
+        let mut camera = CameraState::new(WIDTH, HEIGHT);
+        // pitched past the orbit clamp so the orbit view differs from the fly view
+        camera.set_transform(
+            Vector3::new(0.0, 0.0, 0.0),
+            Quaternion::new_axis_angle(Vector3::new(1.0, 0.0, 0.0), 1.57),
+        );
+        let start = camera.rotation;
+
+        camera.set_mode(CameraMode::Orbit);
+        assert!(camera.transition.is_some());
+
+        let transition_time = camera.transition_time;
+        camera.update_orbit(transition_time / 2.0, &Input::with_gamepad_backend(None));
+        assert!(camera.transition.is_some());
+        assert!(camera.rotation != start);
+        let halfway = camera.rotation;
+
+        camera.update_orbit(transition_time, &Input::with_gamepad_backend(None));
+        assert!(camera.transition.is_none());
+        assert!(camera.rotation != halfway);
+        assert_eq!(camera.orbit_pitch, MAX_PITCH);
+
+        // flying picks up from the current view right away
+        let position = camera.position;
+        camera.set_mode(CameraMode::Fly);
+        assert!(camera.transition.is_none());
+        assert_eq!(camera.position.raw(), position.raw());
     }
 }
@@ -0,0 +1,394 @@
+// controllers that drive a CameraState from game code. they only touch the camera position and
+// rotation and take the frame time as an argument, so they work the same without a window
+extern crate nphysics3d;
+
+use camera::{CameraState, Ray};
+use nphysics3d::object::BodyHandle;
+use physics_engine::PhysicsContext;
+use quaternion::Quaternion;
+use vector::Vector3;
+
+// moves current towards target, covering about 63% of the distance every smoothing seconds
+pub fn damp(current: f32, target: f32, smoothing: f32, delta_time: f32) -> f32 {
+    current + (target - current) * damp_factor(smoothing, delta_time)
+}
+
+pub fn damp_vector(current: Vector3, target: Vector3, smoothing: f32, delta_time: f32) -> Vector3 {
+    current.lerp(target, damp_factor(smoothing, delta_time))
+}
+
+// frame rate independent blend amount, 0 smoothing snaps straight to the target
+fn damp_factor(smoothing: f32, delta_time: f32) -> f32 {
+    if smoothing <= 0.0 {
+        1.0
+    } else {
+        1.0 - (-delta_time / smoothing).exp()
+    }
+}
+
+// third person camera on a spring arm behind a target. the arm is shortened when the physics
+// world blocks it and grows back smoothly once the way is clear
+pub struct FollowCamera {
+    // added to the target position to get the point the arm is attached to
+    pub pivot_offset: Vector3,
+    pub arm_length: f32,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub min_pitch: f32,
+    pub max_pitch: f32,
+
+    // seconds, see damp
+    pub position_smoothing: f32,
+    pub rotation_smoothing: f32,
+    pub arm_smoothing: f32,
+
+    // distance kept between the camera and whatever blocks the arm
+    pub collision_margin: f32,
+    // the body of the followed object so the arm doesn't collide with it
+    pub ignored_body: Option<BodyHandle>,
+
+    // looked at instead of the pivot when set
+    pub look_target: Option<Vector3>,
+
+    pivot: Option<Vector3>,
+    current_arm_length: f32,
+    rotation: Option<Quaternion>,
+}
+
+#[allow(dead_code)]
+impl FollowCamera {
+    pub fn new(arm_length: f32) -> FollowCamera {
+        FollowCamera {
+            pivot_offset: Vector3::new(0.0, 1.5, 0.0),
+            arm_length: arm_length,
+            yaw: 0.0,
+            pitch: 0.3,
+            min_pitch: -1.2,
+            max_pitch: 1.4,
+
+            position_smoothing: 0.1,
+            rotation_smoothing: 0.05,
+            arm_smoothing: 0.3,
+
+            collision_margin: 0.2,
+            ignored_body: None,
+
+            look_target: None,
+
+            pivot: None,
+            current_arm_length: arm_length,
+            rotation: None,
+        }
+    }
+
+    pub fn with_pivot_offset(mut self, pivot_offset: Vector3) -> Self {
+        self.pivot_offset = pivot_offset;
+        self
+    }
+
+    pub fn with_ignored_body(mut self, body: BodyHandle) -> Self {
+        self.ignored_body = Some(body);
+        self
+    }
+
+    // turn the arm around the target, typically fed from the look axes
+    pub fn rotate(&mut self, yaw: f32, pitch: f32) {
+        self.yaw += yaw;
+        self.pitch = (self.pitch + pitch).max(self.min_pitch).min(self.max_pitch);
+    }
+
+    // drop the smoothing state so the next update jumps straight to the target, used after
+    // teleporting the target
+    pub fn snap(&mut self) {
+        self.pivot = None;
+        self.rotation = None;
+        self.current_arm_length = self.arm_length;
+    }
+
+    // the arm length after collision, useful to fade out the target when the camera gets close
+    pub fn current_arm_length(&self) -> f32 {
+        self.current_arm_length
+    }
+
+    pub fn update(
+        &mut self,
+        camera: &mut CameraState,
+        target_position: Vector3,
+        physics: Option<&PhysicsContext>,
+        delta_time: f32,
+    ) {
+        let target_pivot = target_position + self.pivot_offset;
+        let pivot = match self.pivot {
+            Some(pivot) => damp_vector(pivot, target_pivot, self.position_smoothing, delta_time),
+            None => target_pivot,
+        };
+        self.pivot = Some(pivot);
+
+        let arm_rotation =
+            Quaternion::new_axis_angle(Vector3::new(0.0, 1.0, 0.0), self.yaw).mul_quat(
+                Quaternion::new_axis_angle(Vector3::new(1.0, 0.0, 0.0), self.pitch),
+            );
+        let arm_direction = arm_rotation.forward() * -1.0;
+
+        // the arm snaps in when something is in the way so the camera never ends up behind a
+        // wall, and eases back out when it is gone
+        let allowed_length = match physics {
+            Some(physics) => self.blocked_length(physics, pivot, arm_direction),
+            None => self.arm_length,
+        };
+        self.current_arm_length = if allowed_length < self.current_arm_length {
+            allowed_length
+        } else {
+            damp(
+                self.current_arm_length,
+                allowed_length,
+                self.arm_smoothing,
+                delta_time,
+            )
+        };
+
+        let position = pivot + arm_direction * self.current_arm_length;
+
+        let look_target = self.look_target.unwrap_or(pivot);
+        let target_rotation = if (look_target - position).length() > 0.0001 {
+            Quaternion::look_rotation(look_target - position)
+        } else {
+            arm_rotation
+        };
+        let rotation = match self.rotation {
+            Some(rotation) => rotation.slerp(
+                target_rotation,
+                damp_factor(self.rotation_smoothing, delta_time),
+            ),
+            None => target_rotation,
+        };
+        self.rotation = Some(rotation);
+
+        camera.set_transform(position, rotation);
+    }
+
+    // how long the arm can be before it hits something
+    fn blocked_length(&self, physics: &PhysicsContext, pivot: Vector3, direction: Vector3) -> f32 {
+        let ray = Ray {
+            origin: pivot,
+            direction: direction,
+        };
+
+        let mut length = self.arm_length;
+        for hit in physics.raycast(&ray, self.arm_length + self.collision_margin) {
+            if Some(hit.body) == self.ignored_body {
+                continue;
+            }
+
+            length = length.min(hit.distance - self.collision_margin);
+        }
+
+        length.max(0.0)
+    }
+}
+
+// trauma based shake, trauma decays over time and the shake strength is trauma squared so
+// small hits stay subtle. apply it after the controller placed the camera for the frame
+pub struct CameraShake {
+    pub trauma: f32,
+    // trauma lost per second
+    pub decay: f32,
+    pub max_offset: f32,
+    // radians
+    pub max_angle: f32,
+    // oscillations per second
+    pub frequency: f32,
+
+    time: f32,
+    seed: f32,
+}
+
+#[allow(dead_code)]
+impl CameraShake {
+    pub fn new() -> CameraShake {
+        CameraShake {
+            trauma: 0.0,
+            decay: 1.0,
+            max_offset: 0.3,
+            max_angle: 0.05,
+            frequency: 15.0,
+
+            time: 0.0,
+            seed: 0.0,
+        }
+    }
+
+    // cameras sharing a seed shake the same way
+    pub fn with_seed(mut self, seed: f32) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).max(0.0).min(1.0);
+    }
+
+    pub fn is_shaking(&self) -> bool {
+        self.trauma > 0.0
+    }
+
+    // advance the shake and return the position offset and the rotation to apply
+    pub fn update(&mut self, delta_time: f32) -> (Vector3, Quaternion) {
+        self.time += delta_time;
+
+        let strength = self.trauma * self.trauma;
+        self.trauma = (self.trauma - self.decay * delta_time).max(0.0);
+
+        if strength <= 0.0 {
+            return (
+                Vector3::new(0.0, 0.0, 0.0),
+                Quaternion::new(0.0, 0.0, 0.0, 1.0),
+            );
+        }
+
+        let t = self.time * self.frequency;
+        let offset = Vector3::new(
+            shake_noise(t, self.seed),
+            shake_noise(t, self.seed + 1.0),
+            shake_noise(t, self.seed + 2.0),
+        ) * (self.max_offset * strength);
+
+        let yaw = shake_noise(t, self.seed + 3.0) * self.max_angle * strength;
+        let pitch = shake_noise(t, self.seed + 4.0) * self.max_angle * strength;
+        let roll = shake_noise(t, self.seed + 5.0) * self.max_angle * strength;
+        let rotation = Quaternion::new_axis_angle(Vector3::new(0.0, 1.0, 0.0), yaw)
+            .mul_quat(Quaternion::new_axis_angle(
+                Vector3::new(1.0, 0.0, 0.0),
+                pitch,
+            ))
+            .mul_quat(Quaternion::new_axis_angle(
+                Vector3::new(0.0, 0.0, 1.0),
+                roll,
+            ));
+
+        (offset, rotation)
+    }
+
+    pub fn apply(&mut self, camera: &mut CameraState, delta_time: f32) {
+        let (offset, rotation) = self.update(delta_time);
+
+        if offset.length() > 0.0 {
+            let position = camera.position + camera.rotation.rotate(offset);
+            let rotation = camera.rotation.mul_quat(rotation);
+            camera.set_transform(position, rotation);
+        }
+    }
+}
+
+// smooth deterministic noise in -1..1 made from a few sines with unrelated frequencies
+fn shake_noise(t: f32, seed: f32) -> f32 {
+    let phase = seed * 12.9898;
+
+    ((t + phase).sin() * 0.5
+        + (t * 2.17 + phase * 1.3).sin() * 0.3
+        + (t * 4.73 + phase * 0.7).sin() * 0.2)
+        .max(-1.0)
+        .min(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: f32 = 1.0 / 60.0;
+
+    #[test]
+    fn damp_converges_on_the_target() {
+        let mut value = 0.0;
+        for _ in 0..120 {
+            value = damp(value, 10.0, 0.1, FRAME);
+        }
+
+        assert!((value - 10.0).abs() < 0.001);
+
+        // one smoothing period covers about 63% of the way
+        let value = damp(0.0, 1.0, 0.5, 0.5);
+        assert!((value - (1.0 - (-1.0f32).exp())).abs() < 1e-6);
+    }
+
+    #[test]
+    fn damp_doesnt_depend_on_the_frame_rate() {
+        let one_step = damp(0.0, 1.0, 0.2, 0.1);
+        let two_steps = damp(damp(0.0, 1.0, 0.2, 0.05), 1.0, 0.2, 0.05);
+
+        assert!((one_step - two_steps).abs() < 1e-6);
+    }
+
+    #[test]
+    fn zero_or_negative_smoothing_snaps() {
+        assert_eq!(damp(0.0, 10.0, 0.0, FRAME), 10.0);
+        assert_eq!(damp(0.0, 10.0, -1.0, FRAME), 10.0);
+
+        let target = Vector3::new(1.0, 2.0, 3.0);
+        let snapped = damp_vector(Vector3::new(0.0, 0.0, 0.0), target, 0.0, FRAME);
+        assert!((snapped - target).length() < 1e-6);
+    }
+
+    #[test]
+    fn rotate_clamps_the_pitch() {
+        let mut follow = FollowCamera::new(5.0);
+
+        follow.rotate(0.5, 10.0);
+        assert_eq!(follow.pitch, follow.max_pitch);
+        assert_eq!(follow.yaw, 0.5);
+
+        follow.rotate(0.5, -10.0);
+        assert_eq!(follow.pitch, follow.min_pitch);
+        assert_eq!(follow.yaw, 1.0);
+    }
+
+    #[test]
+    fn arm_snaps_in_and_eases_out() {
+        let mut camera = CameraState::new(800, 600);
+        let mut follow = FollowCamera::new(5.0);
+        let target = Vector3::new(0.0, 0.0, 0.0);
+
+        follow.update(&mut camera, target, None, FRAME);
+        assert_eq!(follow.current_arm_length(), 5.0);
+
+        // shorter arms are taken right away
+        follow.arm_length = 2.0;
+        follow.update(&mut camera, target, None, FRAME);
+        assert_eq!(follow.current_arm_length(), 2.0);
+
+        // longer ones are grown into
+        follow.arm_length = 5.0;
+        follow.update(&mut camera, target, None, FRAME);
+        let eased = follow.current_arm_length();
+        assert!(eased > 2.0 && eased < 5.0);
+
+        for _ in 0..300 {
+            follow.update(&mut camera, target, None, FRAME);
+        }
+        assert!((follow.current_arm_length() - 5.0).abs() < 0.001);
+
+        // the camera sits on the arm behind the pivot
+        let pivot = target + follow.pivot_offset;
+        assert!(((camera.position - pivot).length() - 5.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn snap_resets_the_arm() {
+        let mut camera = CameraState::new(800, 600);
+        let mut follow = FollowCamera::new(5.0);
+
+        follow.update(&mut camera, Vector3::new(0.0, 0.0, 0.0), None, FRAME);
+        follow.arm_length = 1.0;
+        follow.update(&mut camera, Vector3::new(0.0, 0.0, 0.0), None, FRAME);
+        follow.arm_length = 5.0;
+        follow.snap();
+
+        assert_eq!(follow.current_arm_length(), 5.0);
+
+        // the pivot jumps to the new target instead of being damped towards it
+        let target = Vector3::new(100.0, 0.0, 0.0);
+        follow.update(&mut camera, target, None, FRAME);
+        let pivot = target + follow.pivot_offset;
+        assert!(((camera.position - pivot).length() - 5.0).abs() < 0.001);
+    }
+}
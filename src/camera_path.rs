@@ -0,0 +1,621 @@
+// camera paths for cutscenes and fly-throughs, keyframes hold a position and a rotation and the
+// camera moves along a spline through them. paths are text files, one keyframe or event per line:
+//
+//   interpolation catmull_rom
+//   loop false
+//   key 0 0 2 -10 0 0 0 1 ease_in_out
+//   key 2.5 5 3 -5 0 0.38 0 0.92 linear
+//   handles 4 3 -7 6 3 -3
+//   event 2.5 show title
+//
+// key is time, position and rotation x y z w, followed by the easing used towards the next key.
+// event names are the rest of the line.
+// handles sets the incoming and outgoing bezier handles of the key above it, keys without
+// handles get catmull-rom tangents
+use assets;
+use camera::CameraState;
+use colored::*;
+use quaternion::Quaternion;
+use std::fs;
+use std::io;
+use vector::Vector3;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PathInterpolation {
+    Linear,
+    CatmullRom,
+    Bezier,
+}
+
+// timing curve between a key and the next one
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+    // stays on the key until the next one, for hard cuts
+    Hold,
+}
+
+impl Easing {
+    pub fn apply(&self, t: f32) -> f32 {
+        match *self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+            Easing::Hold => 0.0,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match *self {
+            Easing::Linear => "linear",
+            Easing::EaseIn => "ease_in",
+            Easing::EaseOut => "ease_out",
+            Easing::EaseInOut => "ease_in_out",
+            Easing::Hold => "hold",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Easing> {
+        match name {
+            "linear" => Some(Easing::Linear),
+            "ease_in" => Some(Easing::EaseIn),
+            "ease_out" => Some(Easing::EaseOut),
+            "ease_in_out" => Some(Easing::EaseInOut),
+            "hold" => Some(Easing::Hold),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct PathKey {
+    pub time: f32,
+    pub position: Vector3,
+    pub rotation: Quaternion,
+    pub easing: Easing,
+    // bezier handles, absolute positions
+    pub in_handle: Option<Vector3>,
+    pub out_handle: Option<Vector3>,
+}
+
+#[allow(dead_code)]
+impl PathKey {
+    pub fn new(time: f32, position: Vector3, rotation: Quaternion) -> PathKey {
+        PathKey {
+            time: time,
+            position: position,
+            rotation: rotation,
+            easing: Easing::Linear,
+            in_handle: None,
+            out_handle: None,
+        }
+    }
+
+    pub fn with_easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    pub fn with_handles(mut self, in_handle: Vector3, out_handle: Vector3) -> Self {
+        self.in_handle = Some(in_handle);
+        self.out_handle = Some(out_handle);
+        self
+    }
+}
+
+// a named event fired when playback passes its time
+#[derive(Clone)]
+pub struct PathEvent {
+    pub time: f32,
+    pub name: String,
+}
+
+#[derive(Clone)]
+pub struct CameraPath {
+    pub interpolation: PathInterpolation,
+    pub looping: bool,
+    keys: Vec<PathKey>,
+    events: Vec<PathEvent>,
+}
+
+#[allow(dead_code)]
+impl CameraPath {
+    pub fn new(interpolation: PathInterpolation) -> CameraPath {
+        CameraPath {
+            interpolation: interpolation,
+            looping: false,
+            keys: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+
+    // keys are kept sorted by time, returns the index the key ended up at
+    pub fn add_key(&mut self, key: PathKey) -> usize {
+        let index = self
+            .keys
+            .iter()
+            .position(|other| other.time > key.time)
+            .unwrap_or(self.keys.len());
+
+        self.keys.insert(index, key);
+        index
+    }
+
+    pub fn add_event(&mut self, time: f32, name: &str) {
+        self.events.push(PathEvent {
+            time: time,
+            name: name.to_string(),
+        });
+        self.events.sort_by(|a, b| a.time.total_cmp(&b.time));
+    }
+
+    pub fn keys(&self) -> &[PathKey] {
+        &self.keys
+    }
+
+    pub fn events(&self) -> &[PathEvent] {
+        &self.events
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.keys.last().map(|key| key.time).unwrap_or(0.0)
+    }
+
+    // load a path from the asset pack
+    pub fn load(path: &str) -> CameraPath {
+        let source = assets::get_asset(path);
+
+        CameraPath::parse(&String::from_utf8_lossy(&source))
+    }
+
+    // paths are usually recorded at runtime, so they are saved to a file on disk
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.serialize())
+    }
+
+    // the camera position and rotation at a time, clamped to the ends of the path
+    pub fn sample(&self, time: f32) -> Option<(Vector3, Quaternion)> {
+        let first = self.keys.first()?;
+        let last = self.keys.last()?;
+
+        if self.keys.len() == 1 || time <= first.time {
+            return Some((first.position, first.rotation));
+        }
+        if time >= last.time {
+            return Some((last.position, last.rotation));
+        }
+
+        let index = self
+            .keys
+            .iter()
+            .rposition(|key| key.time <= time)
+            .unwrap_or(0);
+        let from = &self.keys[index];
+        let to = &self.keys[index + 1];
+
+        let span = to.time - from.time;
+        let t = if span > 0.0 {
+            (time - from.time) / span
+        } else {
+            1.0
+        };
+        let t = from.easing.apply(t);
+
+        let position = match self.interpolation {
+            PathInterpolation::Linear => from.position.lerp(to.position, t),
+            PathInterpolation::CatmullRom => {
+                let before = self.key_position(index as isize - 1);
+                let after = self.key_position(index as isize + 2);
+
+                catmull_rom(before, from.position, to.position, after, t)
+            }
+            PathInterpolation::Bezier => {
+                // keys without handles get the handles a catmull-rom curve would have
+                let out_handle = from
+                    .out_handle
+                    .unwrap_or(from.position + self.tangent(index) * (1.0 / 3.0));
+                let in_handle = to
+                    .in_handle
+                    .unwrap_or(to.position - self.tangent(index + 1) * (1.0 / 3.0));
+
+                bezier(from.position, out_handle, in_handle, to.position, t)
+            }
+        };
+
+        Some((position, from.rotation.slerp(to.rotation, t)))
+    }
+
+    // the end keys are repeated so the curve starts and stops at them
+    fn key_position(&self, index: isize) -> Vector3 {
+        let index = index.max(0).min(self.keys.len() as isize - 1);
+        self.keys[index as usize].position
+    }
+
+    fn tangent(&self, index: usize) -> Vector3 {
+        let index = index as isize;
+        (self.key_position(index + 1) - self.key_position(index - 1)) * 0.5
+    }
+
+    pub fn serialize(&self) -> String {
+        let mut out = String::new();
+
+        let interpolation = match self.interpolation {
+            PathInterpolation::Linear => "linear",
+            PathInterpolation::CatmullRom => "catmull_rom",
+            PathInterpolation::Bezier => "bezier",
+        };
+        out.push_str(&format!("interpolation {}\n", interpolation));
+        out.push_str(&format!("loop {}\n", self.looping));
+
+        for key in &self.keys {
+            let p = key.position;
+            let r = key.rotation;
+            out.push_str(&format!(
+                "key {} {} {} {} {} {} {} {} {}\n",
+                key.time,
+                p.x,
+                p.y,
+                p.z,
+                r.x,
+                r.y,
+                r.z,
+                r.w,
+                key.easing.name()
+            ));
+
+            if let (Some(i), Some(o)) = (key.in_handle, key.out_handle) {
+                out.push_str(&format!(
+                    "handles {} {} {} {} {} {}\n",
+                    i.x, i.y, i.z, o.x, o.y, o.z
+                ));
+            }
+        }
+
+        for event in &self.events {
+            out.push_str(&format!("event {} {}\n", event.time, event.name));
+        }
+
+        out
+    }
+
+    pub fn parse(source: &str) -> CameraPath {
+        let mut path = CameraPath::new(PathInterpolation::CatmullRom);
+        // handles belong to the key on the line above, which is not always the last key in time
+        let mut last_key: Option<usize> = None;
+
+        for line in source.lines() {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+
+            if parts.is_empty() || parts[0].starts_with('#') {
+                continue;
+            }
+
+            let parsed = match parts[0] {
+                "interpolation" => match parts.get(1) {
+                    Some(&"linear") => Some(PathInterpolation::Linear),
+                    Some(&"catmull_rom") => Some(PathInterpolation::CatmullRom),
+                    Some(&"bezier") => Some(PathInterpolation::Bezier),
+                    _ => None,
+                }
+                .map(|interpolation| path.interpolation = interpolation),
+                "loop" => parts
+                    .get(1)
+                    .and_then(|looping| looping.parse().ok())
+                    .map(|looping| path.looping = looping),
+                "key" => parse_key(&parts[1..]).map(|key| {
+                    last_key = Some(path.add_key(key));
+                }),
+                "handles" => match (parse_floats(&parts[1..], 6), last_key) {
+                    (Some(v), Some(index)) => {
+                        path.keys[index].in_handle = Some(Vector3::new(v[0], v[1], v[2]));
+                        path.keys[index].out_handle = Some(Vector3::new(v[3], v[4], v[5]));
+                        Some(())
+                    }
+                    _ => None,
+                },
+                "event" => match parse_floats(&parts[1..], 1) {
+                    Some(ref time) if parts.len() > 2 => {
+                        path.add_event(time[0], &parts[2..].join(" "));
+                        Some(())
+                    }
+                    _ => None,
+                },
+                _ => None,
+            };
+
+            if parsed.is_none() {
+                println!(
+                    "{}",
+                    format!("Invalid line in camera path: '{}'", line).red()
+                );
+            }
+        }
+
+        path
+    }
+}
+
+// time, position, rotation and an optional easing
+fn parse_key(parts: &[&str]) -> Option<PathKey> {
+    let v = parse_floats(parts, 8)?;
+
+    let easing = match parts.get(8) {
+        Some(name) => Easing::from_name(name)?,
+        None => Easing::Linear,
+    };
+
+    Some(
+        PathKey::new(
+            v[0],
+            Vector3::new(v[1], v[2], v[3]),
+            Quaternion::new(v[4], v[5], v[6], v[7]).normalized(),
+        )
+        .with_easing(easing),
+    )
+}
+
+// nan and inf parse as floats but can't be sorted or interpolated, they are rejected
+fn parse_floats(parts: &[&str], count: usize) -> Option<Vec<f32>> {
+    if parts.len() < count {
+        return None;
+    }
+
+    parts[..count]
+        .iter()
+        .map(|part| part.parse::<f32>().ok().filter(|value| value.is_finite()))
+        .collect()
+}
+
+fn catmull_rom(p0: Vector3, p1: Vector3, p2: Vector3, p3: Vector3, t: f32) -> Vector3 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+
+    (p1 * 2.0
+        + (p2 - p0) * t
+        + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2
+        + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3)
+        * 0.5
+}
+
+fn bezier(p0: Vector3, p1: Vector3, p2: Vector3, p3: Vector3, t: f32) -> Vector3 {
+    let u = 1.0 - t;
+
+    p0 * (u * u * u) + p1 * (3.0 * u * u * t) + p2 * (3.0 * u * t * t) + p3 * (t * t * t)
+}
+
+// plays a path back on a camera
+pub struct CameraPathPlayer {
+    pub path: CameraPath,
+    pub speed: f32,
+    time: f32,
+    playing: bool,
+    fired: Vec<String>,
+}
+
+#[allow(dead_code)]
+impl CameraPathPlayer {
+    pub fn new(path: CameraPath) -> CameraPathPlayer {
+        CameraPathPlayer {
+            path: path,
+            speed: 1.0,
+            time: 0.0,
+            playing: false,
+            fired: Vec::new(),
+        }
+    }
+
+    pub fn play(&mut self) {
+        self.time = 0.0;
+        self.playing = true;
+    }
+
+    pub fn stop(&mut self) {
+        self.playing = false;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    pub fn get_time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn seek(&mut self, time: f32) {
+        self.time = time.max(0.0).min(self.path.duration());
+    }
+
+    // names of the events passed during the last update
+    pub fn fired_events(&self) -> &[String] {
+        &self.fired
+    }
+
+    // move the camera along the path, does nothing while stopped
+    pub fn update(&mut self, camera: &mut CameraState, delta_time: f32) {
+        self.fired.clear();
+
+        if !self.playing {
+            return;
+        }
+
+        let duration = self.path.duration();
+        let start = self.time;
+        let mut end = start + delta_time * self.speed;
+
+        // events right at the start of the path fire on the first update
+        self.fire_events(start, end.min(duration), start <= 0.0);
+
+        if end >= duration {
+            if self.path.looping && duration > 0.0 {
+                end %= duration;
+                self.fire_events(0.0, end, true);
+            } else {
+                end = duration;
+                self.playing = false;
+            }
+        }
+        self.time = end;
+
+        if let Some((position, rotation)) = self.path.sample(self.time) {
+            camera.set_transform(position, rotation);
+        }
+    }
+
+    // events in (start, end], or [start, end] when the start is included
+    fn fire_events(&mut self, start: f32, end: f32, include_start: bool) {
+        for event in self.path.events() {
+            let after_start = event.time > start || (include_start && event.time >= start);
+
+            if after_start && event.time <= end {
+                self.fired.push(event.name.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: Vector3, b: Vector3) {
+        assert!((a - b).length() < 1e-5, "{:?} != {:?}", a.raw(), b.raw());
+    }
+
+    #[test]
+    fn serialize_and_parse_round_trip() {
+        let mut path = CameraPath::new(PathInterpolation::Bezier);
+        path.looping = true;
+        path.add_key(
+            PathKey::new(
+                0.0,
+                Vector3::new(0.0, 2.0, -10.0),
+                Quaternion::new(0.0, 0.0, 0.0, 1.0),
+            )
+            .with_easing(Easing::EaseInOut),
+        );
+        path.add_key(
+            PathKey::new(
+                2.5,
+                Vector3::new(5.0, 3.0, -5.0),
+                Quaternion::new(0.0, 0.38, 0.0, 0.92).normalized(),
+            )
+            .with_handles(Vector3::new(4.0, 3.0, -7.0), Vector3::new(6.0, 3.0, -3.0)),
+        );
+        path.add_key(
+            PathKey::new(
+                4.125,
+                Vector3::new(0.1, 0.2, 0.3),
+                Quaternion::new(0.0, 0.0, 0.0, 1.0),
+            )
+            .with_easing(Easing::Hold),
+        );
+        path.add_event(2.5, "show title");
+        path.add_event(0.0, "start");
+
+        let parsed = CameraPath::parse(&path.serialize());
+
+        assert_eq!(parsed.interpolation, PathInterpolation::Bezier);
+        assert!(parsed.looping);
+        assert_eq!(parsed.keys().len(), 3);
+        for (a, b) in path.keys().iter().zip(parsed.keys()) {
+            assert_eq!(a.time, b.time);
+            assert_eq!(a.easing, b.easing);
+            assert_near(a.position, b.position);
+            assert!(a.rotation.dot(b.rotation) > 0.99999);
+            assert_eq!(a.in_handle.is_some(), b.in_handle.is_some());
+        }
+        assert_near(
+            parsed.keys()[1].out_handle.unwrap(),
+            Vector3::new(6.0, 3.0, -3.0),
+        );
+
+        let events: Vec<(f32, &str)> = parsed
+            .events()
+            .iter()
+            .map(|event| (event.time, event.name.as_str()))
+            .collect();
+        assert_eq!(events, vec![(0.0, "start"), (2.5, "show title")]);
+        assert_eq!(parsed.serialize(), path.serialize());
+    }
+
+    #[test]
+    fn non_finite_times_are_rejected() {
+        let path = CameraPath::parse(
+            "key NaN 0 0 0 0 0 0 1\n\
+             key inf 0 0 0 0 0 0 1\n\
+             key 1 0 NaN 0 0 0 0 1\n\
+             key 2 0 0 0 0 0 0 1\n\
+             event NaN broken\n\
+             event -inf broken\n\
+             event 1 fine",
+        );
+
+        assert_eq!(path.keys().len(), 1);
+        assert_eq!(path.keys()[0].time, 2.0);
+        assert_eq!(path.events().len(), 1);
+        assert_eq!(path.events()[0].name, "fine");
+    }
+
+    #[test]
+    fn events_fire_across_a_loop_wrap() {
+        let mut path = CameraPath::new(PathInterpolation::Linear);
+        path.looping = true;
+        path.add_key(PathKey::new(
+            0.0,
+            Vector3::new(0.0, 0.0, 0.0),
+            Quaternion::new(0.0, 0.0, 0.0, 1.0),
+        ));
+        path.add_key(PathKey::new(
+            2.0,
+            Vector3::new(2.0, 0.0, 0.0),
+            Quaternion::new(0.0, 0.0, 0.0, 1.0),
+        ));
+        path.add_event(0.0, "start");
+        path.add_event(0.25, "early");
+        path.add_event(1.0, "middle");
+        path.add_event(1.9, "late");
+
+        let mut camera = CameraState::new(800, 600);
+        let mut player = CameraPathPlayer::new(path);
+        player.play();
+
+        player.update(&mut camera, 0.5);
+        assert_eq!(player.fired_events(), &["start", "early"]);
+
+        player.update(&mut camera, 1.0);
+        assert_eq!(player.fired_events(), &["middle"]);
+
+        // 1.5 to 2.3 passes the end and starts over
+        player.update(&mut camera, 0.8);
+        assert_eq!(player.fired_events(), &["late", "start", "early"]);
+        assert!(player.is_playing());
+        assert!((player.get_time() - 0.3).abs() < 1e-5);
+        assert_near(camera.position, Vector3::new(0.3, 0.0, 0.0));
+    }
+
+    #[test]
+    fn sample_eases_between_keys() {
+        let mut path = CameraPath::new(PathInterpolation::Linear);
+        path.add_key(
+            PathKey::new(
+                0.0,
+                Vector3::new(0.0, 0.0, 0.0),
+                Quaternion::new(0.0, 0.0, 0.0, 1.0),
+            )
+            .with_easing(Easing::Hold),
+        );
+        path.add_key(PathKey::new(
+            1.0,
+            Vector3::new(1.0, 0.0, 0.0),
+            Quaternion::new(0.0, 0.0, 0.0, 1.0),
+        ));
+
+        assert_near(path.sample(0.9).unwrap().0, Vector3::new(0.0, 0.0, 0.0));
+        assert_near(path.sample(1.0).unwrap().0, Vector3::new(1.0, 0.0, 0.0));
+        assert_near(path.sample(-1.0).unwrap().0, Vector3::new(0.0, 0.0, 0.0));
+    }
+}
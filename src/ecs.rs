@@ -0,0 +1,940 @@
+// a small entity-component store, entities are an index plus a generation and every component
+// type lives in its own storage on the EntityContext
+extern crate ncollide3d;
+extern crate nphysics3d;
+
+use bounds::Aabb;
+use material::Material;
+use math_helper;
+use physics_engine::PhysicsContext;
+use quaternion::Quaternion;
+use renderer::RenderContext;
+use resource_manager::Resource;
+use scene_tree::SceneTree;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use vector::Vector3;
+
+// the index picks the slot in the storages, the generation goes up every time the slot is freed
+// so a handle kept around after its entity was destroyed doesn't find whatever reused the slot
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Entity {
+    index: usize,
+    generation: u32,
+}
+
+#[allow(dead_code)]
+impl Entity {
+    pub fn new(index: usize, generation: u32) -> Entity {
+        Entity {
+            index: index,
+            generation: generation,
+        }
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+pub const DEFAULT_LAYER: u32 = 0;
+
+// components remember the generation of the entity they were added to, lookups with a handle of
+// another generation find nothing
+pub struct ComponentStorage<T> {
+    data: Vec<Option<(u32, T)>>,
+}
+
+#[allow(dead_code)]
+impl<T> ComponentStorage<T> {
+    pub fn new() -> ComponentStorage<T> {
+        ComponentStorage { data: Vec::new() }
+    }
+
+    pub fn insert(&mut self, entity: Entity, component: T) {
+        while self.data.len() <= entity.index {
+            self.data.push(None);
+        }
+
+        self.data[entity.index] = Some((entity.generation, component));
+    }
+
+    pub fn remove(&mut self, entity: Entity) -> Option<T> {
+        if !self.has(entity) {
+            return None;
+        }
+
+        self.data[entity.index]
+            .take()
+            .map(|(_, component)| component)
+    }
+
+    pub fn has(&self, entity: Entity) -> bool {
+        self.get(entity).is_some()
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<&T> {
+        match self.data.get(entity.index) {
+            Some(&Some((generation, ref component))) if generation == entity.generation => {
+                Some(component)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+        match self.data.get_mut(entity.index) {
+            Some(&mut Some((generation, ref mut component))) if generation == entity.generation => {
+                Some(component)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (Entity, &'a T)> + 'a {
+        self.data.iter().enumerate().filter_map(|(index, slot)| {
+            slot.as_ref()
+                .map(|&(generation, ref c)| (Entity::new(index, generation), c))
+        })
+    }
+
+    pub fn iter_mut<'a>(&'a mut self) -> impl Iterator<Item = (Entity, &'a mut T)> + 'a {
+        self.data
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, slot)| {
+                slot.as_mut()
+                    .map(|&mut (generation, ref mut c)| (Entity::new(index, generation), c))
+            })
+    }
+}
+
+// query every entity that has both an A and a B component
+pub fn join<'a, A, B>(
+    a: &'a ComponentStorage<A>,
+    b: &'a ComponentStorage<B>,
+) -> impl Iterator<Item = (Entity, &'a A, &'a B)> + 'a {
+    a.iter()
+        .filter_map(move |(entity, ca)| b.get(entity).map(|cb| (entity, ca, cb)))
+}
+
+// same as join, but the first component is handed out mutably
+pub fn join_mut<'a, A, B>(
+    a: &'a mut ComponentStorage<A>,
+    b: &'a ComponentStorage<B>,
+) -> impl Iterator<Item = (Entity, &'a mut A, &'a B)> + 'a {
+    a.iter_mut()
+        .filter_map(move |(entity, ca)| b.get(entity).map(|cb| (entity, ca, cb)))
+}
+
+// Components
+
+#[derive(Copy, Clone)]
+pub struct Transform {
+    pub position: Vector3,
+    pub rotation: Quaternion,
+}
+
+#[allow(dead_code)]
+impl Transform {
+    pub fn new(position: Vector3, rotation: Quaternion) -> Transform {
+        Transform {
+            position: position,
+            rotation: rotation,
+        }
+    }
+
+    pub fn rotate(&mut self, axis: Vector3, angle: f32) {
+        let rot = Quaternion::new_axis_angle(axis, angle);
+
+        let old_rot = self.rotation;
+
+        self.rotation = rot.mul_quat(old_rot);
+    }
+
+    fn get_rotation_matrix(&self) -> [[f32; 4]; 4] {
+        self.rotation.to_rotation_matrix()
+    }
+
+    fn get_translation_matrix(&self) -> [[f32; 4]; 4] {
+        [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [self.position.x, self.position.y, self.position.z, 1.0],
+        ]
+    }
+
+    pub fn get_model_matrix(&self) -> [[f32; 4]; 4] {
+        math_helper::mat_mul(self.get_rotation_matrix(), self.get_translation_matrix())
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct MeshRenderer {
+    pub model: Resource,
+    pub material: Material,
+    pub bounding_box: [f32; 3], // Size X, Size Y, Size Z
+    // centre of the box in model space, the box is not always centred on the transform
+    pub bounding_center: [f32; 3],
+    pub overlay: bool,
+}
+
+#[allow(dead_code)]
+impl MeshRenderer {
+    // the mesh box moved and rotated by the transform
+    pub fn world_bounds(&self, transform: &Transform) -> Aabb {
+        Aabb::from_local(
+            transform.position,
+            transform.rotation,
+            self.bounding_center,
+            self.bounding_box,
+        )
+    }
+}
+
+// the previous state is what the body looked like before the last physics step,
+// transforms are interpolated between it and the current state
+#[derive(Copy, Clone)]
+pub struct RigidBody {
+    pub handle: nphysics3d::object::BodyHandle,
+    pub previous_position: Vector3,
+    pub previous_rotation: Quaternion,
+}
+
+#[derive(Copy, Clone)]
+pub struct Collider {
+    pub handle: ncollide3d::world::CollisionObjectHandle,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LightKind {
+    // lights the whole scene from one direction, the position is ignored
+    Directional,
+    Point,
+    // a cone around the forward vector
+    Spot,
+}
+
+// lights shine along the forward vector of the entity transform. point and spot lights fade out
+// towards their range, see lighting.rs
+#[derive(Copy, Clone)]
+pub struct Light {
+    pub kind: LightKind,
+    pub color: [f32; 3],
+    pub intensity: f32,
+    // distance where the light has faded to nothing
+    pub range: f32,
+    // constant, linear and quadratic falloff over the distance
+    pub attenuation: [f32; 3],
+    // half angles of the spot cone in radians, full strength inside the inner angle
+    pub inner_angle: f32,
+    pub outer_angle: f32,
+}
+
+#[allow(dead_code)]
+impl Light {
+    pub fn directional(color: [f32; 3], intensity: f32) -> Light {
+        Light {
+            kind: LightKind::Directional,
+            color: color,
+            intensity: intensity,
+            range: 0.0,
+            attenuation: [1.0, 0.0, 0.0],
+            inner_angle: 0.0,
+            outer_angle: 0.0,
+        }
+    }
+
+    pub fn point(color: [f32; 3], intensity: f32, range: f32) -> Light {
+        Light {
+            kind: LightKind::Point,
+            color: color,
+            intensity: intensity,
+            range: range,
+            attenuation: [1.0, 0.09, 0.032],
+            inner_angle: 0.0,
+            outer_angle: 0.0,
+        }
+    }
+
+    pub fn spot(
+        color: [f32; 3],
+        intensity: f32,
+        range: f32,
+        inner_angle: f32,
+        outer_angle: f32,
+    ) -> Light {
+        Light {
+            kind: LightKind::Spot,
+            inner_angle: inner_angle,
+            outer_angle: outer_angle,
+            ..Light::point(color, intensity, range)
+        }
+    }
+
+    pub fn with_attenuation(mut self, constant: f32, linear: f32, quadratic: f32) -> Self {
+        self.attenuation = [constant, linear, quadratic];
+        self
+    }
+}
+
+// the main render camera follows the transform of the first active camera entity, a camera
+// with a render_camera index drives that extra camera of the RenderContext instead
+#[derive(Copy, Clone)]
+pub struct Camera {
+    pub active: bool,
+    pub render_camera: Option<usize>,
+}
+
+// marks the root entity of an instantiated prefab so it can be rebuilt when the prefab changes
+#[derive(Clone)]
+pub struct PrefabInstance {
+    pub path: String,
+    pub overrides: Vec<String>,
+    pub children: Vec<Entity>,
+}
+
+pub struct EntityContext {
+    alive: Vec<bool>,
+    // the current generation of every slot
+    generations: Vec<u32>,
+    free_entities: Vec<usize>,
+    pending_destroy: HashSet<Entity>,
+
+    // names, tags and layers are kept private so the lookup indices stay in sync
+    names: ComponentStorage<String>,
+    tags: ComponentStorage<Vec<String>>,
+    layers: ComponentStorage<u32>,
+    name_index: HashMap<String, Vec<Entity>>,
+    tag_index: HashMap<String, Vec<Entity>>,
+    layer_index: HashMap<u32, Vec<Entity>>,
+
+    pub transforms: ComponentStorage<Transform>,
+    pub mesh_renderers: ComponentStorage<MeshRenderer>,
+    pub rigid_bodies: ComponentStorage<RigidBody>,
+    pub colliders: ComponentStorage<Collider>,
+    pub lights: ComponentStorage<Light>,
+    pub cameras: ComponentStorage<Camera>,
+    pub prefab_instances: ComponentStorage<PrefabInstance>,
+
+    // world bounds of every entity with a transform and a mesh renderer, kept up to date by
+    // scene_tree_system
+    pub scene_tree: SceneTree,
+}
+
+#[allow(dead_code)]
+impl EntityContext {
+    pub fn new() -> EntityContext {
+        EntityContext {
+            alive: Vec::new(),
+            generations: Vec::new(),
+            free_entities: Vec::new(),
+            pending_destroy: HashSet::new(),
+
+            names: ComponentStorage::new(),
+            tags: ComponentStorage::new(),
+            layers: ComponentStorage::new(),
+            name_index: HashMap::new(),
+            tag_index: HashMap::new(),
+            layer_index: HashMap::new(),
+
+            transforms: ComponentStorage::new(),
+            mesh_renderers: ComponentStorage::new(),
+            rigid_bodies: ComponentStorage::new(),
+            colliders: ComponentStorage::new(),
+            lights: ComponentStorage::new(),
+            cameras: ComponentStorage::new(),
+            prefab_instances: ComponentStorage::new(),
+
+            scene_tree: SceneTree::new(),
+        }
+    }
+
+    // entities can be created at any point in the frame, freed slots are reused first with the
+    // generation they got when they were freed
+    pub fn create_entity(&mut self) -> Entity {
+        let index = match self.free_entities.pop() {
+            Some(index) => {
+                self.alive[index] = true;
+                index
+            }
+            None => {
+                self.alive.push(true);
+                self.generations.push(0);
+                self.alive.len() - 1
+            }
+        };
+        let entity = Entity::new(index, self.generations[index]);
+
+        self.set_layer(entity, DEFAULT_LAYER);
+
+        entity
+    }
+
+    // queue an entity for destruction, this is safe to call during update
+    // since nothing is removed until maintain runs at the end of the frame
+    pub fn destroy(&mut self, entity: Entity) {
+        if self.is_alive(entity) {
+            self.pending_destroy.insert(entity);
+        }
+    }
+
+    pub fn is_pending_destroy(&self, entity: Entity) -> bool {
+        self.pending_destroy.contains(&entity)
+    }
+
+    // remove the entity, its components and its physics objects right away
+    pub fn destroy_immediate(&mut self, entity: Entity, physics: &mut PhysicsContext) {
+        if !self.is_alive(entity) {
+            return;
+        }
+
+        if let Some(body) = self.rigid_bodies.remove(entity) {
+            physics.remove_rigid_body(&body.handle);
+        }
+
+        if let Some(collider) = self.colliders.remove(entity) {
+            physics.remove_collider(&collider.handle);
+        }
+
+        if let Some(name) = self.names.remove(entity) {
+            index_remove(&mut self.name_index, name, entity);
+        }
+
+        if let Some(tags) = self.tags.remove(entity) {
+            for tag in tags {
+                index_remove(&mut self.tag_index, tag, entity);
+            }
+        }
+
+        if let Some(layer) = self.layers.remove(entity) {
+            index_remove(&mut self.layer_index, layer, entity);
+        }
+
+        self.transforms.remove(entity);
+        self.mesh_renderers.remove(entity);
+        self.scene_tree.remove(entity);
+        self.lights.remove(entity);
+        self.cameras.remove(entity);
+
+        // children spawned by a prefab go away together with their root
+        if let Some(instance) = self.prefab_instances.remove(entity) {
+            for child in instance.children {
+                self.destroy_immediate(child, physics);
+            }
+        }
+
+        self.alive[entity.index] = false;
+        self.generations[entity.index] = self.generations[entity.index].wrapping_add(1);
+        self.free_entities.push(entity.index);
+    }
+
+    // destroy every entity that was queued with destroy during this frame
+    pub fn maintain(&mut self, physics: &mut PhysicsContext) {
+        // sorted so the physics objects go away in the same order every run
+        let mut pending: Vec<Entity> = self.pending_destroy.drain().collect();
+        pending.sort();
+
+        for entity in pending {
+            self.destroy_immediate(entity, physics);
+        }
+    }
+
+    // the world space box of the entity's mesh, None without a transform or mesh renderer
+    pub fn world_bounds(&self, entity: Entity) -> Option<Aabb> {
+        match (self.transforms.get(entity), self.mesh_renderers.get(entity)) {
+            (Some(transform), Some(mesh_renderer)) => Some(mesh_renderer.world_bounds(transform)),
+            _ => None,
+        }
+    }
+
+    // move the entity in the scene tree right away instead of waiting for scene_tree_system
+    pub fn update_bounds(&mut self, entity: Entity) {
+        match self.world_bounds(entity) {
+            Some(bounds) => {
+                self.scene_tree.update(entity, bounds);
+            }
+            None => {
+                self.scene_tree.remove(entity);
+            }
+        }
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        entity.index < self.alive.len()
+            && self.alive[entity.index]
+            && self.generations[entity.index] == entity.generation
+    }
+
+    pub fn entities(&self) -> Vec<Entity> {
+        let mut entities = Vec::new();
+
+        for i in 0..self.alive.len() {
+            if self.alive[i] {
+                entities.push(Entity::new(i, self.generations[i]));
+            }
+        }
+
+        entities
+    }
+
+    // the setters ignore stale handles, writing through one would overwrite the slot of the
+    // entity that reused it
+    pub fn set_name(&mut self, entity: Entity, name: String) {
+        if !self.is_alive(entity) {
+            return;
+        }
+
+        if let Some(old_name) = self.names.remove(entity) {
+            index_remove(&mut self.name_index, old_name, entity);
+        }
+
+        index_insert(&mut self.name_index, name.clone(), entity);
+        self.names.insert(entity, name);
+    }
+
+    pub fn get_name(&self, entity: Entity) -> Option<&String> {
+        self.names.get(entity)
+    }
+
+    pub fn add_tag(&mut self, entity: Entity, tag: String) {
+        if !self.is_alive(entity) || self.has_tag(entity, &tag) {
+            return;
+        }
+
+        index_insert(&mut self.tag_index, tag.clone(), entity);
+
+        if !self.tags.has(entity) {
+            self.tags.insert(entity, Vec::new());
+        }
+        self.tags.get_mut(entity).unwrap().push(tag);
+    }
+
+    pub fn remove_tag(&mut self, entity: Entity, tag: &str) {
+        if !self.has_tag(entity, tag) {
+            return;
+        }
+
+        index_remove(&mut self.tag_index, tag.to_string(), entity);
+        self.tags.get_mut(entity).unwrap().retain(|t| t != tag);
+    }
+
+    pub fn has_tag(&self, entity: Entity, tag: &str) -> bool {
+        match self.tags.get(entity) {
+            Some(tags) => tags.iter().any(|t| t == tag),
+            None => false,
+        }
+    }
+
+    pub fn get_tags(&self, entity: Entity) -> &[String] {
+        match self.tags.get(entity) {
+            Some(tags) => &tags[..],
+            None => &[],
+        }
+    }
+
+    pub fn set_layer(&mut self, entity: Entity, layer: u32) {
+        if !self.is_alive(entity) {
+            return;
+        }
+
+        if let Some(old_layer) = self.layers.remove(entity) {
+            index_remove(&mut self.layer_index, old_layer, entity);
+        }
+
+        index_insert(&mut self.layer_index, layer, entity);
+        self.layers.insert(entity, layer);
+    }
+
+    pub fn get_layer(&self, entity: Entity) -> u32 {
+        match self.layers.get(entity) {
+            Some(layer) => *layer,
+            None => DEFAULT_LAYER,
+        }
+    }
+
+    // returns the first entity spawned with this name
+    pub fn find_by_name(&self, name: &str) -> Option<Entity> {
+        match self.name_index.get(name) {
+            Some(entities) => entities.first().cloned(),
+            None => None,
+        }
+    }
+
+    pub fn find_all_by_name(&self, name: &str) -> Vec<Entity> {
+        match self.name_index.get(name) {
+            Some(entities) => entities.clone(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn find_all_with_tag(&self, tag: &str) -> Vec<Entity> {
+        match self.tag_index.get(tag) {
+            Some(entities) => entities.clone(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn find_all_in_layer(&self, layer: u32) -> Vec<Entity> {
+        match self.layer_index.get(&layer) {
+            Some(entities) => entities.clone(),
+            None => Vec::new(),
+        }
+    }
+}
+
+fn index_insert<K: Hash + Eq>(index: &mut HashMap<K, Vec<Entity>>, key: K, entity: Entity) {
+    index.entry(key).or_insert_with(Vec::new).push(entity);
+}
+
+fn index_remove<K: Hash + Eq>(index: &mut HashMap<K, Vec<Entity>>, key: K, entity: Entity) {
+    let now_empty = match index.get_mut(&key) {
+        Some(entities) => {
+            entities.retain(|e| *e != entity);
+            entities.is_empty()
+        }
+        None => false,
+    };
+
+    if now_empty {
+        index.remove(&key);
+    }
+}
+
+// Systems, these are run once per frame by the game loop
+
+// remember the rigid body state before a fixed physics step
+pub fn physics_pre_step_system(entities: &mut EntityContext, physics: &mut PhysicsContext) {
+    for (_entity, body) in entities.rigid_bodies.iter_mut() {
+        body.previous_position = physics.get_rigid_body_pos(&body.handle);
+        body.previous_rotation = physics.get_rigid_body_rot(&body.handle);
+    }
+}
+
+// copy the simulated rigid body state back onto the entity transforms,
+// interpolated between the last two physics steps
+pub fn physics_system(entities: &mut EntityContext, physics: &mut PhysicsContext) {
+    let alpha = physics.interpolation_alpha();
+
+    for (_entity, transform, body) in join_mut(&mut entities.transforms, &entities.rigid_bodies) {
+        let position = physics.get_rigid_body_pos(&body.handle);
+        let rotation = physics.get_rigid_body_rot(&body.handle);
+
+        transform.position = body.previous_position.lerp(position, alpha);
+        transform.rotation = body.previous_rotation.slerp(rotation, alpha);
+    }
+}
+
+// move the render cameras to the camera entities that drive them
+pub fn camera_system(entities: &EntityContext, context: &mut RenderContext) {
+    let mut main_set = false;
+
+    for (_entity, transform, camera) in join(&entities.transforms, &entities.cameras) {
+        if !camera.active {
+            continue;
+        }
+
+        match camera.render_camera {
+            Some(index) => {
+                if let Some(render_camera) = context.cameras.get_mut(index) {
+                    render_camera
+                        .camera
+                        .set_transform(transform.position, transform.rotation);
+                }
+            }
+            None if !main_set => {
+                context
+                    .camera
+                    .set_transform(transform.position, transform.rotation);
+                main_set = true;
+            }
+            None => {}
+        }
+    }
+}
+
+// refit the scene tree to the transforms after everything has moved for the frame
+pub fn scene_tree_system(entities: &mut EntityContext) {
+    // entities that lost their mesh renderer or transform
+    for entity in entities.scene_tree.entities() {
+        if entities.world_bounds(entity).is_none() {
+            entities.scene_tree.remove(entity);
+        }
+    }
+
+    let entities_with_mesh: Vec<Entity> = entities
+        .mesh_renderers
+        .iter()
+        .map(|(entity, _)| entity)
+        .collect();
+    for entity in entities_with_mesh {
+        entities.update_bounds(entity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stale_handles_miss_the_reused_slot() {
+        let mut entities = EntityContext::new();
+        let mut physics = PhysicsContext::new();
+
+        let old = entities.create_entity();
+        entities.set_name(old, "old".to_string());
+        entities.destroy_immediate(old, &mut physics);
+
+        let new = entities.create_entity();
+        assert_eq!(new.index(), old.index());
+        assert_ne!(new.generation(), old.generation());
+
+        assert!(!entities.is_alive(old));
+        assert!(entities.is_alive(new));
+        assert_eq!(entities.entities(), vec![new]);
+
+        entities.set_name(new, "new".to_string());
+        assert_eq!(entities.get_name(old), None);
+        assert_eq!(entities.get_name(new).map(|n| n.as_str()), Some("new"));
+        assert_eq!(entities.find_by_name("old"), None);
+    }
+
+    #[test]
+    fn storages_check_the_generation() {
+        let mut storage = ComponentStorage::new();
+        let old = Entity::new(3, 0);
+        let new = Entity::new(3, 1);
+
+        storage.insert(new, 7);
+
+        assert!(!storage.has(old));
+        assert_eq!(storage.get_mut(old), None);
+        assert_eq!(storage.remove(old), None);
+        assert_eq!(storage.get(new), Some(&7));
+        assert_eq!(storage.iter().collect::<Vec<_>>(), vec![(new, &7)]);
+        assert_eq!(storage.remove(new), Some(7));
+    }
+
+    #[test]
+    fn destroy_queues_once_until_maintain() {
+        let mut entities = EntityContext::new();
+        let mut physics = PhysicsContext::new();
+
+        let entity = entities.create_entity();
+        entities.destroy(entity);
+        entities.destroy(entity);
+
+        assert!(entities.is_pending_destroy(entity));
+        assert!(entities.is_alive(entity));
+
+        entities.maintain(&mut physics);
+
+        assert!(!entities.is_pending_destroy(entity));
+        assert!(!entities.is_alive(entity));
+
+        // a stale handle can't queue the entity that reused the slot
+        let reused = entities.create_entity();
+        entities.destroy(entity);
+        assert!(!entities.is_pending_destroy(reused));
+    }
+
+    fn off_centre_mesh() -> MeshRenderer {
+        // a mesh spanning 1..3 on x and -1..1 on y and z
+        MeshRenderer {
+            model: 0,
+            material: Material::new(0, 0, 0, [1.0, 1.0, 1.0], [1.0, 1.0]),
+            bounding_box: [2.0, 2.0, 2.0],
+            bounding_center: [2.0, 0.0, 0.0],
+            overlay: false,
+        }
+    }
+
+    #[test]
+    fn world_bounds_follow_an_off_centre_mesh() {
+        let mut entities = EntityContext::new();
+        let entity = entities.create_entity();
+
+        entities.transforms.insert(
+            entity,
+            Transform::new(
+                Vector3::new(10.0, 0.0, 0.0),
+                Quaternion::new(0.0, 0.0, 0.0, 1.0),
+            ),
+        );
+        entities.mesh_renderers.insert(entity, off_centre_mesh());
+
+        let bounds = entities.world_bounds(entity).unwrap();
+        assert_eq!(bounds.min.raw(), [11.0, -1.0, -1.0]);
+        assert_eq!(bounds.max.raw(), [13.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn world_bounds_rotate_the_mesh_offset() {
+        let mut entities = EntityContext::new();
+        let entity = entities.create_entity();
+        let position = Vector3::new(0.0, 5.0, 0.0);
+        let rotation = Quaternion::new_axis_angle(Vector3::new(0.0, 1.0, 0.0), 1.2);
+
+        entities
+            .transforms
+            .insert(entity, Transform::new(position, rotation));
+        entities.mesh_renderers.insert(entity, off_centre_mesh());
+
+        let bounds = entities.world_bounds(entity).unwrap().expanded(1e-4);
+
+        for &x in &[1.0, 3.0] {
+            for &y in &[-1.0, 1.0] {
+                for &z in &[-1.0, 1.0] {
+                    let corner = position + rotation.rotate(Vector3::new(x, y, z));
+                    assert!(bounds.contains(&Aabb::new(corner, corner)));
+                }
+            }
+        }
+
+        // the origin of the transform is outside of the mesh and of its box
+        assert!(!bounds.contains(&Aabb::new(position, position)));
+    }
+
+    #[test]
+    fn duplicate_names_resolve_in_spawn_order() {
+        let mut entities = EntityContext::new();
+        let first = entities.create_entity();
+        let second = entities.create_entity();
+        let other = entities.create_entity();
+
+        entities.set_name(first, "enemy".to_string());
+        entities.set_name(second, "enemy".to_string());
+        entities.set_name(other, "player".to_string());
+
+        assert_eq!(entities.find_by_name("enemy"), Some(first));
+        assert_eq!(entities.find_all_by_name("enemy"), vec![first, second]);
+        assert_eq!(entities.find_by_name("player"), Some(other));
+        assert_eq!(entities.find_by_name("nobody"), None);
+    }
+
+    #[test]
+    fn renaming_drops_the_old_name() {
+        let mut entities = EntityContext::new();
+        let entity = entities.create_entity();
+        let twin = entities.create_entity();
+
+        entities.set_name(entity, "old".to_string());
+        entities.set_name(twin, "old".to_string());
+        entities.set_name(entity, "new".to_string());
+
+        assert_eq!(entities.find_all_by_name("old"), vec![twin]);
+        assert_eq!(entities.find_by_name("new"), Some(entity));
+        assert_eq!(entities.get_name(entity).map(|n| n.as_str()), Some("new"));
+
+        // renaming to the same name keeps a single entry
+        entities.set_name(entity, "new".to_string());
+        assert_eq!(entities.find_all_by_name("new"), vec![entity]);
+    }
+
+    #[test]
+    fn tags_are_added_once_and_removed() {
+        let mut entities = EntityContext::new();
+        let a = entities.create_entity();
+        let b = entities.create_entity();
+
+        entities.add_tag(a, "enemy".to_string());
+        entities.add_tag(a, "enemy".to_string());
+        entities.add_tag(a, "flying".to_string());
+        entities.add_tag(b, "enemy".to_string());
+
+        assert_eq!(entities.find_all_with_tag("enemy"), vec![a, b]);
+        assert_eq!(
+            entities.get_tags(a).to_vec(),
+            vec!["enemy".to_string(), "flying".to_string()]
+        );
+
+        entities.remove_tag(a, "enemy");
+        assert!(!entities.has_tag(a, "enemy"));
+        assert_eq!(entities.find_all_with_tag("enemy"), vec![b]);
+        assert_eq!(entities.find_all_with_tag("flying"), vec![a]);
+
+        // removing a tag the entity doesn't have leaves the others alone
+        entities.remove_tag(a, "enemy");
+        assert_eq!(entities.find_all_with_tag("enemy"), vec![b]);
+
+        entities.remove_tag(b, "enemy");
+        assert!(entities.find_all_with_tag("enemy").is_empty());
+        assert!(!entities.tag_index.contains_key("enemy"));
+    }
+
+    #[test]
+    fn entities_move_between_layers() {
+        let mut entities = EntityContext::new();
+        let entity = entities.create_entity();
+        let other = entities.create_entity();
+
+        assert_eq!(entities.get_layer(entity), DEFAULT_LAYER);
+        assert_eq!(
+            entities.find_all_in_layer(DEFAULT_LAYER),
+            vec![entity, other]
+        );
+
+        entities.set_layer(entity, 3);
+        assert_eq!(entities.get_layer(entity), 3);
+        assert_eq!(entities.find_all_in_layer(3), vec![entity]);
+        assert_eq!(entities.find_all_in_layer(DEFAULT_LAYER), vec![other]);
+
+        entities.set_layer(entity, 40);
+        assert!(entities.find_all_in_layer(3).is_empty());
+        assert_eq!(entities.find_all_in_layer(40), vec![entity]);
+    }
+
+    #[test]
+    fn lookups_forget_destroyed_entities() {
+        let mut entities = EntityContext::new();
+        let mut physics = PhysicsContext::new();
+        let entity = entities.create_entity();
+
+        entities.set_name(entity, "crate".to_string());
+        entities.add_tag(entity, "loot".to_string());
+        entities.set_layer(entity, 5);
+        entities.destroy_immediate(entity, &mut physics);
+
+        assert_eq!(entities.find_by_name("crate"), None);
+        assert!(entities.find_all_with_tag("loot").is_empty());
+        assert!(entities.find_all_in_layer(5).is_empty());
+        assert_eq!(entities.get_layer(entity), DEFAULT_LAYER);
+        assert!(entities.get_tags(entity).is_empty());
+    }
+
+    #[test]
+    fn stale_handles_cannot_touch_the_reused_slot() {
+        let mut entities = EntityContext::new();
+        let mut physics = PhysicsContext::new();
+
+        let old = entities.create_entity();
+        entities.set_name(old, "old".to_string());
+        entities.destroy_immediate(old, &mut physics);
+
+        let new = entities.create_entity();
+        assert_eq!(new.index(), old.index());
+        entities.set_name(new, "new".to_string());
+        entities.add_tag(new, "fresh".to_string());
+        entities.set_layer(new, 2);
+
+        // writes through the stale handle are ignored
+        entities.set_name(old, "old".to_string());
+        entities.add_tag(old, "stale".to_string());
+        entities.remove_tag(old, "fresh");
+        entities.set_layer(old, 9);
+
+        assert_eq!(entities.find_by_name("old"), None);
+        assert_eq!(entities.find_by_name("new"), Some(new));
+        assert_eq!(entities.get_name(new).map(|n| n.as_str()), Some("new"));
+        assert_eq!(entities.find_all_with_tag("fresh"), vec![new]);
+        assert!(entities.find_all_with_tag("stale").is_empty());
+        assert_eq!(entities.find_all_in_layer(2), vec![new]);
+        assert!(entities.find_all_in_layer(9).is_empty());
+        assert_eq!(entities.get_layer(new), 2);
+    }
+}
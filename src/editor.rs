@@ -1,5 +1,6 @@
 use assets;
-use game::*;
+use camera::{CameraMode, ViewPreset};
+use ecs::*;
 use gameobject::*;
 use glium::glutin;
 use input::*;
@@ -9,49 +10,59 @@ use quaternion::*;
 use renderer::*;
 use resource_manager::*;
 use shader;
+use std::collections::HashMap;
 use texture;
+use time::Time;
 use ui_renderer::*;
 use vector::*;
 
 pub struct Editor {
-    gizmo_x: Option<Resource>,
-    gizmo_y: Option<Resource>,
-    gizmo_z: Option<Resource>,
-    selected_obj: Option<Resource>,
+    pub visible: bool,
+
+    default_ui: Resource,
+    gizmo_x: Option<Entity>,
+    gizmo_y: Option<Entity>,
+    gizmo_z: Option<Entity>,
+    selected_obj: Option<Entity>,
 }
 
 impl Editor {
     pub fn new(
-        display: &glium::Display,
-        rc: &mut RenderContext,
+        display: Option<&glium::Display>,
+        entities: &mut EntityContext,
         res: &mut ResourceContext,
     ) -> Editor {
         let gizmo_model_x = res.alloc_model(Model::load(
-            &display,
+            display,
             &assets::get_asset("./res/gizmo/gizmo_x_axis.obj"),
             &assets::get_asset("./res/gizmo/gizmo_x_axis.mtl"),
         ));
 
         let gizmo_model_y = res.alloc_model(Model::load(
-            &display,
+            display,
             &assets::get_asset("./res/gizmo/gizmo_y_axis.obj"),
             &assets::get_asset("./res/gizmo/gizmo_y_axis.mtl"),
         ));
 
         let gizmo_model_z = res.alloc_model(Model::load(
-            &display,
+            display,
             &assets::get_asset("./res/gizmo/gizmo_z_axis.obj"),
             &assets::get_asset("./res/gizmo/gizmo_z_axis.mtl"),
         ));
 
         let basic_shader = res.alloc_shader(shader::load(
-            &display,
+            display,
             &assets::get_asset("./res/basic.vs"),
             &assets::get_asset("./res/basic.fs"),
         ));
 
+        let default_ui = res.alloc_tex(texture::load(
+            display,
+            &assets::get_asset("./res/default_ui.jpg"),
+        ));
+
         let white = res.alloc_tex(texture::load(
-            &display,
+            display,
             &assets::get_asset("./res/white.jpg"),
         ));
 
@@ -92,15 +103,13 @@ impl Editor {
         gizmo_z.overlay = true;
         gizmo_z.material.color = [0.0, 0.0, 1.0];
 
-        let gizmo_x = res.alloc_gameobject(gizmo_x);
-        let gizmo_y = res.alloc_gameobject(gizmo_y);
-        let gizmo_z = res.alloc_gameobject(gizmo_z);
-
-        rc.gameobjects.push(gizmo_x);
-        rc.gameobjects.push(gizmo_y);
-        rc.gameobjects.push(gizmo_z);
+        let gizmo_x = gizmo_x.spawn(entities);
+        let gizmo_y = gizmo_y.spawn(entities);
+        let gizmo_z = gizmo_z.spawn(entities);
 
         Editor {
+            visible: true,
+            default_ui: default_ui,
             gizmo_x: Some(gizmo_x),
             gizmo_y: Some(gizmo_y),
             gizmo_z: Some(gizmo_z),
@@ -108,7 +117,7 @@ impl Editor {
         }
     }
 
-    pub fn is_gizmo(&self, id: Option<Resource>) -> Option<i32> {
+    pub fn is_gizmo(&self, id: Option<Entity>) -> Option<i32> {
         let unwrapped = id.unwrap();
 
         let gizmo_x_unwrapped = self.gizmo_x.unwrap();
@@ -129,17 +138,34 @@ impl Editor {
         None
     }
 
+    // follow the selection to the entity that replaced it, like after reloading prefab instances
+    pub fn remap_selection(&mut self, entities: &EntityContext, remap: &HashMap<Entity, Entity>) {
+        if let Some(selected_obj) = self.selected_obj {
+            if let Some(&new_obj) = remap.get(&selected_obj) {
+                self.selected_obj = Some(new_obj);
+            } else if !entities.is_alive(selected_obj) {
+                self.selected_obj = None;
+            }
+        }
+    }
+
     pub fn move_x(&mut self) {}
     pub fn move_y(&mut self) {}
     pub fn move_z(&mut self) {}
 
     pub fn update(
         &mut self,
-        game_state: &mut GameState,
         rc: &mut RenderContext,
-        res: &mut ResourceContext,
+        entities: &mut EntityContext,
         input: &mut Input,
+        time: &mut Time,
     ) {
+        // pause and resume the simulation
+        if input.shortcut_triggered("toggle_pause") {
+            let paused = time.is_paused();
+            time.set_paused(!paused);
+        }
+
         if input.get_mouse_down(glutin::MouseButton::Left) && rc.picked_object.is_some() {
             if self.is_gizmo(rc.picked_object).is_some() {
                 let val = self.is_gizmo(rc.picked_object).unwrap();
@@ -152,22 +178,90 @@ impl Editor {
                 }
             } else {
                 self.selected_obj = rc.picked_object;
+
+                // the orbit camera follows the selection
+                if rc.camera.get_mode() == CameraMode::Orbit {
+                    if let Some(transform) =
+                        self.selected_obj.and_then(|e| entities.transforms.get(e))
+                    {
+                        rc.camera.orbit_around(transform.position);
+                    }
+                }
+            }
+        }
+
+        if input.shortcut_triggered("toggle_camera_mode") {
+            let mode = match rc.camera.get_mode() {
+                CameraMode::Fly => CameraMode::Orbit,
+                CameraMode::Orbit => CameraMode::Fly,
+            };
+            rc.camera.set_mode(mode);
+        }
+
+        if input.shortcut_triggered("toggle_projection") {
+            rc.camera.toggle_orthographic();
+        }
+        if input.shortcut_triggered("view_top") {
+            rc.camera.set_view_preset(ViewPreset::Top);
+        }
+        if input.shortcut_triggered("view_front") {
+            rc.camera.set_view_preset(ViewPreset::Front);
+        }
+        if input.shortcut_triggered("view_side") {
+            rc.camera.set_view_preset(ViewPreset::Side);
+        }
+
+        if input.shortcut_triggered("frame_selected") {
+            if let Some(selected_obj) = self.selected_obj {
+                let transform = entities.transforms.get(selected_obj);
+                let mesh_renderer = entities.mesh_renderers.get(selected_obj);
+
+                if let (Some(transform), Some(mesh_renderer)) = (transform, mesh_renderer) {
+                    let size = Vector3::new(
+                        mesh_renderer.bounding_box[0],
+                        mesh_renderer.bounding_box[1],
+                        mesh_renderer.bounding_box[2],
+                    );
+
+                    let bounds = mesh_renderer.world_bounds(transform);
+
+                    rc.camera.frame(bounds.center(), size.length() / 2.0);
+                }
+            }
+        }
+
+        if let Some(selected_obj) = self.selected_obj {
+            if input.shortcut_triggered("delete_selected") {
+                entities.destroy(selected_obj);
+            }
+
+            if input.shortcut_triggered("deselect") {
+                self.selected_obj = None;
+            }
+
+            if self.selected_obj.is_some()
+                && (!entities.is_alive(selected_obj) || entities.is_pending_destroy(selected_obj))
+            {
+                self.selected_obj = None;
             }
         }
 
         if self.selected_obj.is_some() {
-            let selected_obj_pos = res.get_gameobject_ref(self.selected_obj.unwrap()).position;
+            let selected_obj_pos = match entities.transforms.get(self.selected_obj.unwrap()) {
+                Some(transform) => transform.position,
+                None => return,
+            };
 
             {
-                let gizmo = res.get_gameobject_ref_mut(self.gizmo_x.unwrap());
+                let gizmo = entities.transforms.get_mut(self.gizmo_x.unwrap()).unwrap();
                 gizmo.position = selected_obj_pos;
             }
             {
-                let gizmo = res.get_gameobject_ref_mut(self.gizmo_y.unwrap());
+                let gizmo = entities.transforms.get_mut(self.gizmo_y.unwrap()).unwrap();
                 gizmo.position = selected_obj_pos;
             }
             {
-                let gizmo = res.get_gameobject_ref_mut(self.gizmo_z.unwrap());
+                let gizmo = entities.transforms.get_mut(self.gizmo_z.unwrap()).unwrap();
                 gizmo.position = selected_obj_pos;
             }
         }
@@ -176,10 +270,11 @@ impl Editor {
     pub fn render_editor(
         &mut self,
         ui: &mut UIContext,
-        game_state: &mut GameState,
-        res: &mut ResourceContext,
+        entities: &EntityContext,
+        time: &Time,
+        render_stats: &RenderStats,
     ) {
-        if game_state.menu_open {
+        if !self.visible {
             return;
         }
 
@@ -206,13 +301,7 @@ impl Editor {
 
         ui.set_font_size(20);
 
-        ui.render_quad(
-            game_state.default_ui,
-            win_pos_x,
-            win_pos_y,
-            win_width,
-            win_height,
-        );
+        ui.render_quad(self.default_ui, win_pos_x, win_pos_y, win_width, win_height);
 
         let mut element_idx = 0;
 
@@ -224,11 +313,37 @@ impl Editor {
                 - ((button_height + element_padding) * element_idx as f32),
         );
 
+        let stats_string = format!(
+            "fps: {fps:.0} ({ms:.2} ms){paused} drawn: {drawn} culled: {culled} draw calls: {calls}",
+            fps = time.fps(),
+            ms = time.frame_time() * 1000.0,
+            paused = if time.is_paused() { " paused" } else { "" },
+            drawn = render_stats.drawn,
+            culled = render_stats.culled,
+            calls = render_stats.draw_calls,
+        );
+
+        ui.render_text(&stats_string, win_pos_x, win_pos_y + element_padding);
+
+        let changes_string = format!(
+            "shader changes: {} material changes: {} mesh changes: {}",
+            render_stats.shader_changes, render_stats.material_changes, render_stats.mesh_changes,
+        );
+
+        ui.render_text(
+            &changes_string,
+            win_pos_x,
+            win_pos_y + element_padding * 2.0,
+        );
+
         // draw inspector
-        if self.selected_obj.is_some() {
-            element_idx = 1;
+        let selected_transform = match self.selected_obj {
+            Some(selected_obj) => entities.transforms.get(selected_obj),
+            None => None,
+        };
 
-            let obj = res.get_gameobject_ref(self.selected_obj.unwrap());
+        if let Some(obj) = selected_transform {
+            element_idx = 1;
 
             let pos_string = format!(
                 "x: {x:.3} y: {y:.3}, z: {z:.3}",
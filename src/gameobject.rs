@@ -2,8 +2,8 @@ extern crate glium;
 extern crate ncollide3d;
 extern crate nphysics3d;
 
+use ecs::*;
 use material::*;
-use math_helper;
 use na::Vector3 as PhysicsVec3;
 use ncollide3d::shape::{Ball, Cuboid, ShapeHandle};
 use physics_engine;
@@ -12,14 +12,18 @@ use quaternion::Quaternion;
 use resource_manager::*;
 use vector::Vector3;
 
+// convenience builder that collects the pieces of an object and spawns them as an entity
 pub struct GameObject {
     pub name: String,
     pub position: Vector3,
     pub rotation: Quaternion,
     pub model: Resource,
     pub bounding_box: [f32; 3],
+    pub bounding_center: [f32; 3],
     pub material: Material,
     pub overlay: bool,
+    pub tags: Vec<String>,
+    pub layer: u32,
 
     // Physics variables
     pub physics_enabled: bool,
@@ -38,6 +42,7 @@ impl GameObject {
         material: Material,
     ) -> GameObject {
         let bounding_box = resources.get_model_ref(model).bounding_box;
+        let bounding_center = resources.get_model_ref(model).bounding_center;
 
         GameObject {
             rigid_body_handle: None,
@@ -49,19 +54,21 @@ impl GameObject {
             material: material,
             physics_enabled: false,
             bounding_box: bounding_box,
+            bounding_center: bounding_center,
             overlay: false,
+            tags: Vec::new(),
+            layer: DEFAULT_LAYER,
         }
     }
 
-    pub fn update(&mut self, physics: &mut PhysicsContext) {
-        if self.physics_enabled && self.rigid_body_handle.is_some() {
-            let handle = self.rigid_body_handle.unwrap();
-
-            self.position = physics.get_rigid_body_pos(&handle);
-            self.rotation = physics.get_rigid_body_rot(&handle);
+    pub fn with_tag(mut self, tag: &str) -> Self {
+        self.tags.push(tag.to_string());
+        self
+    }
 
-            self.rigid_body_handle = Some(handle);
-        }
+    pub fn with_layer(mut self, layer: u32) -> Self {
+        self.layer = layer;
+        self
     }
 
     fn get_shape(&self, physics_shape: physics_engine::PhysicsShape) -> Option<ShapeHandle<f32>> {
@@ -124,20 +131,50 @@ impl GameObject {
         self.rotation = rot.mul_quat(old_rot);
     }
 
-    fn get_rotation_matrix(&self) -> [[f32; 4]; 4] {
-        self.rotation.to_rotation_matrix()
-    }
+    pub fn spawn(self, entities: &mut EntityContext) -> Entity {
+        let entity = entities.create_entity();
 
-    fn get_translation_matrix(&self) -> [[f32; 4]; 4] {
-        [
-            [1.0, 0.0, 0.0, 0.0],
-            [0.0, 1.0, 0.0, 0.0],
-            [0.0, 0.0, 1.0, 0.0],
-            [self.position.x, self.position.y, self.position.z, 1.0],
-        ]
-    }
+        entities.set_name(entity, self.name);
+        entities.set_layer(entity, self.layer);
+        for tag in self.tags {
+            entities.add_tag(entity, tag);
+        }
+        entities
+            .transforms
+            .insert(entity, Transform::new(self.position, self.rotation));
+        entities.mesh_renderers.insert(
+            entity,
+            MeshRenderer {
+                model: self.model,
+                material: self.material,
+                bounding_box: self.bounding_box,
+                bounding_center: self.bounding_center,
+                overlay: self.overlay,
+            },
+        );
+
+        if self.physics_enabled && self.rigid_body_handle.is_some() {
+            entities.rigid_bodies.insert(
+                entity,
+                RigidBody {
+                    handle: self.rigid_body_handle.unwrap(),
+                    previous_position: self.position,
+                    previous_rotation: self.rotation,
+                },
+            );
+        }
+
+        if self.collision_handle.is_some() {
+            entities.colliders.insert(
+                entity,
+                Collider {
+                    handle: self.collision_handle.unwrap(),
+                },
+            );
+        }
+
+        entities.update_bounds(entity);
 
-    pub fn get_model_matrix(&self) -> [[f32; 4]; 4] {
-        math_helper::mat_mul(self.get_rotation_matrix(), self.get_translation_matrix())
+        entity
     }
 }
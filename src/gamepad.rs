@@ -0,0 +1,475 @@
+// gamepads are read through a GamepadBackend so the input system does not depend on a specific
+// library, GilrsBackend talks to real pads and FakeGamepadBackend lets tests and tools drive input.
+// gilrs needs libudev on linux, building without the default gilrs feature leaves it out
+#[cfg(feature = "gilrs")]
+extern crate gilrs;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+pub type GamepadId = usize;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum GamepadButton {
+    South,
+    East,
+    North,
+    West,
+    LeftBumper,
+    RightBumper,
+    Select,
+    Start,
+    Mode,
+    LeftStick,
+    RightStick,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+pub const BUTTON_COUNT: usize = 15;
+
+// a dead zone of 1 or more would swallow the whole range and divide by zero when rescaling
+const MAX_DEAD_ZONE: f32 = 0.99;
+
+pub const ALL_BUTTONS: [GamepadButton; BUTTON_COUNT] = [
+    GamepadButton::South,
+    GamepadButton::East,
+    GamepadButton::North,
+    GamepadButton::West,
+    GamepadButton::LeftBumper,
+    GamepadButton::RightBumper,
+    GamepadButton::Select,
+    GamepadButton::Start,
+    GamepadButton::Mode,
+    GamepadButton::LeftStick,
+    GamepadButton::RightStick,
+    GamepadButton::DPadUp,
+    GamepadButton::DPadDown,
+    GamepadButton::DPadLeft,
+    GamepadButton::DPadRight,
+];
+
+// stick axes go from -1 to 1 with y pointing up, triggers go from 0 to 1
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum GamepadAxis {
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+    LeftTrigger,
+    RightTrigger,
+}
+
+pub const AXIS_COUNT: usize = 6;
+
+pub const ALL_AXES: [GamepadAxis; AXIS_COUNT] = [
+    GamepadAxis::LeftStickX,
+    GamepadAxis::LeftStickY,
+    GamepadAxis::RightStickX,
+    GamepadAxis::RightStickY,
+    GamepadAxis::LeftTrigger,
+    GamepadAxis::RightTrigger,
+];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GamepadStick {
+    Left,
+    Right,
+}
+
+#[allow(dead_code)]
+impl GamepadButton {
+    // looks a button up by its enum name, like "South" or "DPadUp"
+    pub fn from_name(name: &str) -> Option<GamepadButton> {
+        ALL_BUTTONS
+            .iter()
+            .cloned()
+            .find(|button| format!("{:?}", button) == name)
+    }
+}
+
+#[allow(dead_code)]
+impl GamepadAxis {
+    pub fn from_name(name: &str) -> Option<GamepadAxis> {
+        ALL_AXES
+            .iter()
+            .cloned()
+            .find(|axis| format!("{:?}", axis) == name)
+    }
+
+    pub fn is_trigger(&self) -> bool {
+        match *self {
+            GamepadAxis::LeftTrigger | GamepadAxis::RightTrigger => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GamepadEvent {
+    Connected(GamepadId),
+    Disconnected(GamepadId),
+    ButtonPressed(GamepadId, GamepadButton),
+    ButtonReleased(GamepadId, GamepadButton),
+    AxisChanged(GamepadId, GamepadAxis, f32),
+}
+
+pub trait GamepadBackend {
+    // append every event since the last poll
+    fn poll(&mut self, events: &mut Vec<GamepadEvent>);
+}
+
+// the state of one connected pad, axes are stored raw and dead zones are applied when reading them
+pub struct GamepadState {
+    current_buttons: [bool; BUTTON_COUNT],
+    last_buttons: [bool; BUTTON_COUNT],
+    axes: [f32; AXIS_COUNT],
+}
+
+#[allow(dead_code)]
+impl GamepadState {
+    pub fn new() -> GamepadState {
+        GamepadState {
+            current_buttons: [false; BUTTON_COUNT],
+            last_buttons: [false; BUTTON_COUNT],
+            axes: [0.0; AXIS_COUNT],
+        }
+    }
+
+    pub fn start_frame(&mut self) {
+        self.last_buttons = self.current_buttons;
+    }
+
+    pub fn set_button(&mut self, button: GamepadButton, pressed: bool) {
+        self.current_buttons[button as usize] = pressed;
+    }
+
+    pub fn set_axis(&mut self, axis: GamepadAxis, value: f32) {
+        let value = if axis.is_trigger() {
+            value.max(0.0).min(1.0)
+        } else {
+            value.max(-1.0).min(1.0)
+        };
+
+        self.axes[axis as usize] = value;
+    }
+
+    pub fn button(&self, button: GamepadButton, last_frame: bool) -> bool {
+        if last_frame {
+            self.last_buttons[button as usize]
+        } else {
+            self.current_buttons[button as usize]
+        }
+    }
+
+    pub fn raw_axis(&self, axis: GamepadAxis) -> f32 {
+        self.axes[axis as usize]
+    }
+
+    // stick with a radial dead zone, the range outside the dead zone is rescaled to 0..1
+    pub fn stick(&self, stick: GamepadStick, dead_zone: f32) -> [f32; 2] {
+        let (x, y) = match stick {
+            GamepadStick::Left => (
+                self.raw_axis(GamepadAxis::LeftStickX),
+                self.raw_axis(GamepadAxis::LeftStickY),
+            ),
+            GamepadStick::Right => (
+                self.raw_axis(GamepadAxis::RightStickX),
+                self.raw_axis(GamepadAxis::RightStickY),
+            ),
+        };
+
+        let dead_zone = clamp_dead_zone(dead_zone);
+        let length = (x * x + y * y).sqrt();
+        if length <= dead_zone {
+            return [0.0, 0.0];
+        }
+
+        let scaled = ((length - dead_zone) / (1.0 - dead_zone)).min(1.0);
+
+        [x / length * scaled, y / length * scaled]
+    }
+
+    pub fn trigger(&self, axis: GamepadAxis, dead_zone: f32) -> f32 {
+        let value = self.raw_axis(axis);
+        let dead_zone = clamp_dead_zone(dead_zone);
+
+        if value <= dead_zone {
+            0.0
+        } else {
+            ((value - dead_zone) / (1.0 - dead_zone)).min(1.0)
+        }
+    }
+
+    pub fn axis(&self, axis: GamepadAxis, stick_dead_zone: f32, trigger_dead_zone: f32) -> f32 {
+        match axis {
+            GamepadAxis::LeftStickX => self.stick(GamepadStick::Left, stick_dead_zone)[0],
+            GamepadAxis::LeftStickY => self.stick(GamepadStick::Left, stick_dead_zone)[1],
+            GamepadAxis::RightStickX => self.stick(GamepadStick::Right, stick_dead_zone)[0],
+            GamepadAxis::RightStickY => self.stick(GamepadStick::Right, stick_dead_zone)[1],
+            GamepadAxis::LeftTrigger | GamepadAxis::RightTrigger => {
+                self.trigger(axis, trigger_dead_zone)
+            }
+        }
+    }
+}
+
+fn clamp_dead_zone(dead_zone: f32) -> f32 {
+    dead_zone.max(0.0).min(MAX_DEAD_ZONE)
+}
+
+// the backend used when nothing else is set, None when the platform has no gamepad support
+#[cfg(feature = "gilrs")]
+pub fn default_backend() -> Option<Box<dyn GamepadBackend>> {
+    match GilrsBackend::new() {
+        Some(backend) => Some(Box::new(backend)),
+        None => None,
+    }
+}
+
+// without gilrs no pads ever connect, the fake backend keeps the code paths the same
+#[cfg(not(feature = "gilrs"))]
+pub fn default_backend() -> Option<Box<dyn GamepadBackend>> {
+    Some(Box::new(FakeGamepadBackend::new()))
+}
+
+#[cfg(feature = "gilrs")]
+pub struct GilrsBackend {
+    gilrs: gilrs::Gilrs,
+}
+
+#[cfg(feature = "gilrs")]
+impl GilrsBackend {
+    pub fn new() -> Option<GilrsBackend> {
+        match gilrs::Gilrs::new() {
+            Ok(gilrs) => Some(GilrsBackend { gilrs: gilrs }),
+            Err(err) => {
+                println!("Gamepads are not available: {}", err);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(feature = "gilrs")]
+impl GamepadBackend for GilrsBackend {
+    fn poll(&mut self, events: &mut Vec<GamepadEvent>) {
+        while let Some(gilrs::Event { id, event, .. }) = self.gilrs.next_event() {
+            let id: GamepadId = id.into();
+
+            let event = match event {
+                gilrs::EventType::Connected => GamepadEvent::Connected(id),
+                gilrs::EventType::Disconnected => GamepadEvent::Disconnected(id),
+                gilrs::EventType::ButtonPressed(button, _) => match gilrs_button(button) {
+                    Some(button) => GamepadEvent::ButtonPressed(id, button),
+                    None => continue,
+                },
+                gilrs::EventType::ButtonReleased(button, _) => match gilrs_button(button) {
+                    Some(button) => GamepadEvent::ButtonReleased(id, button),
+                    None => continue,
+                },
+                // the analog triggers are reported as buttons with a value
+                gilrs::EventType::ButtonChanged(gilrs::Button::LeftTrigger2, value, _) => {
+                    GamepadEvent::AxisChanged(id, GamepadAxis::LeftTrigger, value)
+                }
+                gilrs::EventType::ButtonChanged(gilrs::Button::RightTrigger2, value, _) => {
+                    GamepadEvent::AxisChanged(id, GamepadAxis::RightTrigger, value)
+                }
+                gilrs::EventType::AxisChanged(axis, value, _) => match gilrs_axis(axis, value) {
+                    Some((axis, value)) => GamepadEvent::AxisChanged(id, axis, value),
+                    None => continue,
+                },
+                _ => continue,
+            };
+
+            events.push(event);
+        }
+    }
+}
+
+#[cfg(feature = "gilrs")]
+fn gilrs_button(button: gilrs::Button) -> Option<GamepadButton> {
+    match button {
+        gilrs::Button::South => Some(GamepadButton::South),
+        gilrs::Button::East => Some(GamepadButton::East),
+        gilrs::Button::North => Some(GamepadButton::North),
+        gilrs::Button::West => Some(GamepadButton::West),
+        gilrs::Button::LeftTrigger => Some(GamepadButton::LeftBumper),
+        gilrs::Button::RightTrigger => Some(GamepadButton::RightBumper),
+        gilrs::Button::Select => Some(GamepadButton::Select),
+        gilrs::Button::Start => Some(GamepadButton::Start),
+        gilrs::Button::Mode => Some(GamepadButton::Mode),
+        gilrs::Button::LeftThumb => Some(GamepadButton::LeftStick),
+        gilrs::Button::RightThumb => Some(GamepadButton::RightStick),
+        gilrs::Button::DPadUp => Some(GamepadButton::DPadUp),
+        gilrs::Button::DPadDown => Some(GamepadButton::DPadDown),
+        gilrs::Button::DPadLeft => Some(GamepadButton::DPadLeft),
+        gilrs::Button::DPadRight => Some(GamepadButton::DPadRight),
+        _ => None,
+    }
+}
+
+// pads without a trigger mapping report their triggers as the z axes, gilrs scales those to -1..1
+// with -1 at rest so they are moved to the 0..1 range of our triggers
+#[cfg(feature = "gilrs")]
+fn gilrs_axis(axis: gilrs::Axis, value: f32) -> Option<(GamepadAxis, f32)> {
+    match axis {
+        gilrs::Axis::LeftStickX => Some((GamepadAxis::LeftStickX, value)),
+        gilrs::Axis::LeftStickY => Some((GamepadAxis::LeftStickY, value)),
+        gilrs::Axis::RightStickX => Some((GamepadAxis::RightStickX, value)),
+        gilrs::Axis::RightStickY => Some((GamepadAxis::RightStickY, value)),
+        gilrs::Axis::LeftZ => Some((GamepadAxis::LeftTrigger, (value + 1.0) * 0.5)),
+        gilrs::Axis::RightZ => Some((GamepadAxis::RightTrigger, (value + 1.0) * 0.5)),
+        _ => None,
+    }
+}
+
+// a backend that hands out queued events, clones share the queue so one clone can be given to
+// Input while the other one is used to push events
+#[derive(Clone)]
+pub struct FakeGamepadBackend {
+    queue: Rc<RefCell<Vec<GamepadEvent>>>,
+}
+
+#[allow(dead_code)]
+impl FakeGamepadBackend {
+    pub fn new() -> FakeGamepadBackend {
+        FakeGamepadBackend {
+            queue: Rc::new(RefCell::new(Vec::new())),
+        }
+    }
+
+    pub fn push(&self, event: GamepadEvent) {
+        self.queue.borrow_mut().push(event);
+    }
+
+    pub fn connect(&self, id: GamepadId) {
+        self.push(GamepadEvent::Connected(id));
+    }
+
+    pub fn disconnect(&self, id: GamepadId) {
+        self.push(GamepadEvent::Disconnected(id));
+    }
+
+    pub fn press(&self, id: GamepadId, button: GamepadButton) {
+        self.push(GamepadEvent::ButtonPressed(id, button));
+    }
+
+    pub fn release(&self, id: GamepadId, button: GamepadButton) {
+        self.push(GamepadEvent::ButtonReleased(id, button));
+    }
+
+    pub fn set_axis(&self, id: GamepadId, axis: GamepadAxis, value: f32) {
+        self.push(GamepadEvent::AxisChanged(id, axis, value));
+    }
+}
+
+impl GamepadBackend for FakeGamepadBackend {
+    fn poll(&mut self, events: &mut Vec<GamepadEvent>) {
+        events.extend(self.queue.borrow_mut().drain(..));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "gilrs")]
+    fn z_axes_are_reported_as_triggers() {
+        assert_eq!(
+            gilrs_axis(gilrs::Axis::LeftZ, -1.0),
+            Some((GamepadAxis::LeftTrigger, 0.0))
+        );
+        assert_eq!(
+            gilrs_axis(gilrs::Axis::RightZ, 1.0),
+            Some((GamepadAxis::RightTrigger, 1.0))
+        );
+        assert_eq!(
+            gilrs_axis(gilrs::Axis::LeftZ, 0.0),
+            Some((GamepadAxis::LeftTrigger, 0.5))
+        );
+        assert_eq!(
+            gilrs_axis(gilrs::Axis::LeftStickY, -0.25),
+            Some((GamepadAxis::LeftStickY, -0.25))
+        );
+        assert_eq!(gilrs_axis(gilrs::Axis::DPadX, 1.0), None);
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for &button in ALL_BUTTONS.iter() {
+            assert_eq!(
+                GamepadButton::from_name(&format!("{:?}", button)),
+                Some(button)
+            );
+        }
+        for &axis in ALL_AXES.iter() {
+            assert_eq!(GamepadAxis::from_name(&format!("{:?}", axis)), Some(axis));
+        }
+        assert_eq!(GamepadButton::from_name("Nope"), None);
+    }
+
+    #[test]
+    fn stick_dead_zone_is_radial_and_rescaled() {
+        let mut pad = GamepadState::new();
+
+        pad.set_axis(GamepadAxis::LeftStickX, 0.1);
+        pad.set_axis(GamepadAxis::LeftStickY, 0.1);
+        assert_eq!(pad.stick(GamepadStick::Left, 0.15), [0.0, 0.0]);
+
+        pad.set_axis(GamepadAxis::LeftStickX, 0.0);
+        pad.set_axis(GamepadAxis::LeftStickY, 0.6);
+        let stick = pad.stick(GamepadStick::Left, 0.2);
+        assert_eq!(stick[0], 0.0);
+        assert!((stick[1] - 0.5).abs() < 1e-6);
+
+        pad.set_axis(GamepadAxis::LeftStickX, -2.0);
+        pad.set_axis(GamepadAxis::LeftStickY, 0.0);
+        assert_eq!(pad.stick(GamepadStick::Left, 0.2), [-1.0, 0.0]);
+    }
+
+    #[test]
+    fn trigger_dead_zone_is_rescaled() {
+        let mut pad = GamepadState::new();
+
+        pad.set_axis(GamepadAxis::RightTrigger, 0.04);
+        assert_eq!(pad.trigger(GamepadAxis::RightTrigger, 0.05), 0.0);
+
+        pad.set_axis(GamepadAxis::RightTrigger, 0.55);
+        assert!((pad.trigger(GamepadAxis::RightTrigger, 0.1) - 0.5).abs() < 1e-6);
+
+        // triggers never go negative
+        pad.set_axis(GamepadAxis::RightTrigger, -1.0);
+        assert_eq!(pad.raw_axis(GamepadAxis::RightTrigger), 0.0);
+    }
+
+    #[test]
+    fn dead_zones_are_clamped() {
+        let mut pad = GamepadState::new();
+
+        // a dead zone of 1 or more still lets a full press through instead of dividing by zero
+        pad.set_axis(GamepadAxis::LeftStickX, 1.0);
+        pad.set_axis(GamepadAxis::RightTrigger, 1.0);
+        for &dead_zone in &[1.0, 2.0, std::f32::INFINITY] {
+            assert_eq!(pad.stick(GamepadStick::Left, dead_zone), [1.0, 0.0]);
+            assert_eq!(pad.trigger(GamepadAxis::RightTrigger, dead_zone), 1.0);
+        }
+
+        pad.set_axis(GamepadAxis::LeftStickX, 0.5);
+        pad.set_axis(GamepadAxis::RightTrigger, 0.5);
+        assert_eq!(pad.stick(GamepadStick::Left, 1.0), [0.0, 0.0]);
+        assert_eq!(pad.trigger(GamepadAxis::RightTrigger, 1.0), 0.0);
+
+        // a negative dead zone is no dead zone
+        assert_eq!(pad.stick(GamepadStick::Left, -0.5), [0.5, 0.0]);
+        assert_eq!(pad.trigger(GamepadAxis::RightTrigger, -0.5), 0.5);
+
+        pad.set_axis(GamepadAxis::LeftStickX, 0.0);
+        pad.set_axis(GamepadAxis::RightTrigger, 0.0);
+        assert_eq!(pad.stick(GamepadStick::Left, -0.5), [0.0, 0.0]);
+        assert_eq!(pad.trigger(GamepadAxis::RightTrigger, -0.5), 0.0);
+    }
+}
@@ -1,66 +1,518 @@
+use action_map::{ActionMap, AxisBinding, Binding};
+use gamepad::*;
 use glium::glutin;
+use shortcuts::{Chord, ShortcutRegistry};
+use std::collections::{HashMap, HashSet};
+
+// number of virtual key codes, VirtualKeyCode is repr(u32) and numbered from 0 with Cut as the last key
+pub const KEY_COUNT: usize = glutin::VirtualKeyCode::Cut as usize + 1;
+
+// every key in the order of its id. the length is checked against KEY_COUNT when compiling and the
+// order by the tests, so a glutin upgrade that changes the enum breaks the build instead of
+// turning stored ids into the wrong keys
+const ALL_KEYS: [glutin::VirtualKeyCode; KEY_COUNT] = {
+    use glium::glutin::VirtualKeyCode::*;
+    [
+        Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Key0, A, B, C, D, E, F, G, H, I, J, K,
+        L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z, Escape, F1, F2, F3, F4, F5, F6, F7, F8, F9,
+        F10, F11, F12, F13, F14, F15, Snapshot, Scroll, Pause, Insert, Home, Delete, End, PageDown,
+        PageUp, Left, Up, Right, Down, Back, Return, Space, Compose, Caret, Numlock, Numpad0,
+        Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9, AbntC1,
+        AbntC2, Add, Apostrophe, Apps, At, Ax, Backslash, Calculator, Capital, Colon, Comma,
+        Convert, Decimal, Divide, Equals, Grave, Kana, Kanji, LAlt, LBracket, LControl, LShift,
+        LWin, Mail, MediaSelect, MediaStop, Minus, Multiply, Mute, MyComputer, NavigateForward,
+        NavigateBackward, NextTrack, NoConvert, NumpadComma, NumpadEnter, NumpadEquals, OEM102,
+        Period, PlayPause, Power, PrevTrack, RAlt, RBracket, RControl, RShift, RWin, Semicolon,
+        Slash, Sleep, Stop, Subtract, Sysrq, Tab, Underline, Unlabeled, VolumeDown, VolumeUp, Wake,
+        WebBack, WebFavorites, WebForward, WebHome, WebRefresh, WebSearch, WebStop, Yen, Copy,
+        Paste, Cut,
+    ]
+};
+
+const KEY_WORDS: usize = (KEY_COUNT + 63) / 64;
+
+// held state of every key, one bit per VirtualKeyCode
+#[derive(Copy, Clone, PartialEq)]
+struct KeySet {
+    words: [u64; KEY_WORDS],
+}
+
+impl KeySet {
+    fn new() -> KeySet {
+        KeySet {
+            words: [0; KEY_WORDS],
+        }
+    }
+
+    fn get(&self, key: glutin::VirtualKeyCode) -> bool {
+        let id = key as usize;
+        self.words[id / 64] & (1u64 << (id % 64)) != 0
+    }
+
+    fn set(&mut self, key: glutin::VirtualKeyCode, held: bool) {
+        let id = key as usize;
+
+        if held {
+            self.words[id / 64] |= 1u64 << (id % 64);
+        } else {
+            self.words[id / 64] &= !(1u64 << (id % 64));
+        }
+    }
+}
+
+// touchpads scroll in pixels, this turns them into the same lines a mouse wheel reports
+pub const PIXELS_PER_SCROLL_LINE: f32 = 20.0;
+
 pub struct Input {
-    last_keys: Vec<bool>,
-    current_keys: Vec<bool>,
+    last_keys: KeySet,
+    current_keys: KeySet,
 
-    last_mouse: Vec<bool>,
-    current_mouse: Vec<bool>,
+    // a set instead of an array since mice can have any number of extra buttons
+    last_mouse: HashSet<glutin::MouseButton>,
+    current_mouse: HashSet<glutin::MouseButton>,
 
     mouse_position: [f32; 2],
+    // raw mouse movement since the last frame
+    mouse_delta: [f32; 2],
+    // scrolled lines since the last frame, y is positive when scrolling up
+    scroll_delta: [f32; 2],
+    // characters typed since the last frame
+    text_input: String,
+
+    cursor_locked: bool,
+    cursor_visible: bool,
+    cursor_changed: bool,
+
+    gamepad_backend: Option<Box<dyn GamepadBackend>>,
+    gamepads: HashMap<GamepadId, GamepadState>,
+    gamepads_connected: Vec<GamepadId>,
+    gamepads_disconnected: Vec<GamepadId>,
+
+    // sticks use a radial dead zone, triggers a linear one. values outside 0..0.99 are clamped
+    // when the pads are read
+    pub stick_dead_zone: f32,
+    pub trigger_dead_zone: f32,
+
+    pub actions: ActionMap,
+    pub shortcuts: ShortcutRegistry,
+    // keys and buttons used by a shortcut, actions ignore them until they are released
+    consumed: Vec<Binding>,
 }
 
 #[allow(dead_code)]
 impl Input {
     pub fn new() -> Input {
+        Input::with_gamepad_backend(default_backend())
+    }
+
+    // None disables gamepads, used when running headless
+    pub fn with_gamepad_backend(gamepad_backend: Option<Box<dyn GamepadBackend>>) -> Input {
         Input {
-            last_keys: vec![false; 161],
-            current_keys: vec![false; 161],
+            last_keys: KeySet::new(),
+            current_keys: KeySet::new(),
 
-            last_mouse: vec![false; 32], // hardcoded max mouse buttons, TODO: maybe quary the hardware for the actual size
-            current_mouse: vec![false; 32],
+            last_mouse: HashSet::new(),
+            current_mouse: HashSet::new(),
 
             mouse_position: [0.0, 0.0],
+            mouse_delta: [0.0, 0.0],
+            scroll_delta: [0.0, 0.0],
+            text_input: String::new(),
+
+            cursor_locked: false,
+            cursor_visible: true,
+            cursor_changed: false,
+
+            gamepad_backend: gamepad_backend,
+            gamepads: HashMap::new(),
+            gamepads_connected: Vec::new(),
+            gamepads_disconnected: Vec::new(),
+
+            stick_dead_zone: 0.15,
+            trigger_dead_zone: 0.05,
+
+            actions: ActionMap::default_bindings(),
+            shortcuts: ShortcutRegistry::default_shortcuts(),
+            consumed: Vec::new(),
         }
     }
 
-    pub fn get_key_down(&mut self, code: glutin::VirtualKeyCode) -> bool {
-        (self.current_keys[code as usize] && !self.last_keys[code as usize])
+    // keys and buttons used by a shortcut read as up until they are released, so a key bound
+    // on its own doesn't also fire when it completes a chord
+    pub fn get_key_down(&self, code: glutin::VirtualKeyCode) -> bool {
+        self.current_keys.get(code)
+            && !self.last_keys.get(code)
+            && !self.is_consumed(Binding::Key(code))
+    }
+
+    pub fn get_key(&self, code: glutin::VirtualKeyCode) -> bool {
+        self.current_keys.get(code) && !self.is_consumed(Binding::Key(code))
+    }
+
+    pub fn get_key_up(&self, code: glutin::VirtualKeyCode) -> bool {
+        !self.current_keys.get(code)
+            && self.last_keys.get(code)
+            && !self.is_consumed(Binding::Key(code))
     }
 
-    pub fn get_key(&mut self, code: glutin::VirtualKeyCode) -> bool {
-        self.current_keys[code as usize]
+    pub fn get_mouse_down(&self, mouse_button: glutin::MouseButton) -> bool {
+        self.current_mouse.contains(&mouse_button)
+            && !self.last_mouse.contains(&mouse_button)
+            && !self.is_consumed(Binding::Mouse(mouse_button))
     }
 
-    pub fn get_key_up(&mut self, code: glutin::VirtualKeyCode) -> bool {
-        (!self.current_keys[code as usize] && self.last_keys[code as usize])
+    pub fn get_mouse(&self, mouse_button: glutin::MouseButton) -> bool {
+        self.current_mouse.contains(&mouse_button)
+            && !self.is_consumed(Binding::Mouse(mouse_button))
     }
 
-    pub fn get_mouse_down(&mut self, mouse_button: glium::glutin::MouseButton) -> bool {
-        (self.current_mouse[mouse_code_to_id(mouse_button)]
-            && !self.last_mouse[mouse_code_to_id(mouse_button)])
+    pub fn get_mouse_up(&self, mouse_button: glutin::MouseButton) -> bool {
+        !self.current_mouse.contains(&mouse_button)
+            && self.last_mouse.contains(&mouse_button)
+            && !self.is_consumed(Binding::Mouse(mouse_button))
     }
 
-    pub fn get_mouse(&mut self, mouse_button: glium::glutin::MouseButton) -> bool {
-        self.current_mouse[mouse_code_to_id(mouse_button)]
+    // true while the key or button is held down after completing a shortcut
+    pub fn is_consumed(&self, binding: Binding) -> bool {
+        self.consumed.contains(&binding)
     }
 
-    pub fn get_mouse_up(&mut self, mouse_button: glium::glutin::MouseButton) -> bool {
-        (!self.current_mouse[mouse_code_to_id(mouse_button)]
-            && self.last_mouse[mouse_code_to_id(mouse_button)])
+    // modifiers come from the key state rather than the event modifiers so replays see them too
+    pub fn get_modifiers(&self) -> glutin::ModifiersState {
+        // read the raw state, shortcuts never consume modifiers
+        let keys = &self.current_keys;
+        let held = |left, right| keys.get(left) || keys.get(right);
+
+        glutin::ModifiersState {
+            shift: held(glutin::VirtualKeyCode::LShift, glutin::VirtualKeyCode::RShift),
+            ctrl: held(glutin::VirtualKeyCode::LControl, glutin::VirtualKeyCode::RControl),
+            alt: held(glutin::VirtualKeyCode::LAlt, glutin::VirtualKeyCode::RAlt),
+            logo: held(glutin::VirtualKeyCode::LWin, glutin::VirtualKeyCode::RWin),
+        }
+    }
+
+    // like get_key_down, but only when exactly these modifiers are held
+    pub fn get_key_down_with(
+        &self,
+        code: glutin::VirtualKeyCode,
+        modifiers: glutin::ModifiersState,
+    ) -> bool {
+        self.get_key_down(code) && self.get_modifiers() == modifiers
+    }
+
+    pub fn get_mouse_down_with(
+        &self,
+        mouse_button: glutin::MouseButton,
+        modifiers: glutin::ModifiersState,
+    ) -> bool {
+        self.get_mouse_down(mouse_button) && self.get_modifiers() == modifiers
+    }
+
+    // every key that went down this frame
+    pub fn keys_down(&self) -> Vec<glutin::VirtualKeyCode> {
+        (0..KEY_COUNT)
+            .filter_map(key_from_id)
+            .filter(|key| self.get_key_down(*key))
+            .collect()
+    }
+
+    // match this frame's key and mouse presses against the shortcut registry,
+    // the runner calls this at the start of every frame
+    pub fn update_shortcuts(&mut self, delta_time: f32) {
+        let modifiers = self.get_modifiers();
+
+        let mut chords: Vec<Chord> = self
+            .keys_down()
+            .into_iter()
+            .filter(|key| !is_modifier_key(*key))
+            .map(|key| Chord::new(modifiers, Binding::Key(key)))
+            .collect();
+
+        for button in &self.current_mouse {
+            if !self.last_mouse.contains(button) {
+                chords.push(Chord::new(modifiers, Binding::Mouse(*button)));
+            }
+        }
+
+        for chord in self.shortcuts.update(&chords, delta_time) {
+            if !self.consumed.contains(&chord.trigger) {
+                self.consumed.push(chord.trigger);
+            }
+        }
+    }
+
+    // the keys and buttons of a triggered shortcut are hidden from actions and the raw queries
+    // until released
+    pub fn shortcut_triggered(&self, name: &str) -> bool {
+        self.shortcuts.triggered(name)
     }
 
     pub fn get_mouse_pos(&mut self) -> [f32; 2] {
         return self.mouse_position;
     }
 
+    pub fn get_mouse_delta(&self) -> [f32; 2] {
+        self.mouse_delta
+    }
+
+    pub fn get_scroll_delta(&self) -> [f32; 2] {
+        self.scroll_delta
+    }
+
+    // text typed since the last frame, for ui text fields
+    pub fn get_text_input(&self) -> &str {
+        &self.text_input
+    }
+
+    // ask the runner to keep the cursor inside the window, applied at the end of the frame
+    pub fn set_cursor_locked(&mut self, locked: bool) {
+        if self.cursor_locked != locked {
+            self.cursor_locked = locked;
+            self.cursor_changed = true;
+        }
+    }
+
+    pub fn set_cursor_visible(&mut self, visible: bool) {
+        if self.cursor_visible != visible {
+            self.cursor_visible = visible;
+            self.cursor_changed = true;
+        }
+    }
+
+    pub fn is_cursor_locked(&self) -> bool {
+        self.cursor_locked
+    }
+
+    pub fn is_cursor_visible(&self) -> bool {
+        self.cursor_visible
+    }
+
+    // the requested (locked, visible) state if it changed since the last call, used by the runner
+    pub fn take_cursor_request(&mut self) -> Option<(bool, bool)> {
+        if self.cursor_changed {
+            self.cursor_changed = false;
+            Some((self.cursor_locked, self.cursor_visible))
+        } else {
+            None
+        }
+    }
+
+    pub fn set_gamepad_backend(&mut self, gamepad_backend: Option<Box<dyn GamepadBackend>>) {
+        self.gamepad_backend = gamepad_backend;
+    }
+
+    pub fn is_gamepad_connected(&self, id: GamepadId) -> bool {
+        self.gamepads.contains_key(&id)
+    }
+
+    pub fn connected_gamepads(&self) -> Vec<GamepadId> {
+        let mut ids: Vec<GamepadId> = self.gamepads.keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+
+    // pads that connected since the last frame
+    pub fn gamepads_connected(&self) -> &[GamepadId] {
+        &self.gamepads_connected
+    }
+
+    // pads that disconnected since the last frame
+    pub fn gamepads_disconnected(&self) -> &[GamepadId] {
+        &self.gamepads_disconnected
+    }
+
+    pub fn get_gamepad_button_down(&self, id: GamepadId, button: GamepadButton) -> bool {
+        match self.gamepads.get(&id) {
+            Some(pad) => pad.button(button, false) && !pad.button(button, true),
+            None => false,
+        }
+    }
+
+    pub fn get_gamepad_button(&self, id: GamepadId, button: GamepadButton) -> bool {
+        match self.gamepads.get(&id) {
+            Some(pad) => pad.button(button, false),
+            None => false,
+        }
+    }
+
+    pub fn get_gamepad_button_up(&self, id: GamepadId, button: GamepadButton) -> bool {
+        match self.gamepads.get(&id) {
+            Some(pad) => !pad.button(button, false) && pad.button(button, true),
+            None => false,
+        }
+    }
+
+    // axis value with the dead zones applied, 0 for pads that are not connected
+    pub fn get_gamepad_axis(&self, id: GamepadId, axis: GamepadAxis) -> f32 {
+        match self.gamepads.get(&id) {
+            Some(pad) => pad.axis(axis, self.stick_dead_zone, self.trigger_dead_zone),
+            None => 0.0,
+        }
+    }
+
+    pub fn get_gamepad_stick(&self, id: GamepadId, stick: GamepadStick) -> [f32; 2] {
+        match self.gamepads.get(&id) {
+            Some(pad) => pad.stick(stick, self.stick_dead_zone),
+            None => [0.0, 0.0],
+        }
+    }
+
+    // true while any binding of the action is held
+    pub fn action_pressed(&self, action: &str) -> bool {
+        self.action_held(action, false)
+    }
+
+    // true on the frame the action starts being held
+    pub fn action_down(&self, action: &str) -> bool {
+        self.action_held(action, false) && !self.action_held(action, true)
+    }
+
+    // true on the frame the action stops being held
+    pub fn action_up(&self, action: &str) -> bool {
+        !self.action_held(action, false) && self.action_held(action, true)
+    }
+
+    // the sum of every binding of the axis, 0 when nothing is bound
+    pub fn action_axis(&self, axis: &str) -> f32 {
+        let mut value = 0.0;
+
+        for binding in self.actions.axis_bindings(axis) {
+            value += match *binding {
+                AxisBinding::Buttons(negative, positive) => {
+                    let mut buttons = 0.0;
+                    if self.binding_held(negative, false) {
+                        buttons -= 1.0;
+                    }
+                    if self.binding_held(positive, false) {
+                        buttons += 1.0;
+                    }
+                    buttons
+                }
+                AxisBinding::MouseX(scale) => self.mouse_delta[0] * scale,
+                AxisBinding::MouseY(scale) => self.mouse_delta[1] * scale,
+                AxisBinding::Wheel(scale) => self.scroll_delta[1] * scale,
+                AxisBinding::Gamepad(axis, scale) => self.strongest_gamepad_axis(axis) * scale,
+            };
+        }
+
+        value
+    }
+
+    // with several pads connected an axis binding follows whichever pad pushes it the furthest
+    fn strongest_gamepad_axis(&self, axis: GamepadAxis) -> f32 {
+        self.gamepads
+            .values()
+            .map(|pad| pad.axis(axis, self.stick_dead_zone, self.trigger_dead_zone))
+            .fold(0.0, |strongest, value| {
+                if value.abs() > strongest.abs() {
+                    value
+                } else {
+                    strongest
+                }
+            })
+    }
+
+    fn action_held(&self, action: &str, last_frame: bool) -> bool {
+        self.actions
+            .action_bindings(action)
+            .iter()
+            .any(|binding| self.binding_held(*binding, last_frame))
+    }
+
+    fn binding_held(&self, binding: Binding, last_frame: bool) -> bool {
+        if self.is_consumed(binding) {
+            return false;
+        }
+
+        let (keys, mouse) = if last_frame {
+            (&self.last_keys, &self.last_mouse)
+        } else {
+            (&self.current_keys, &self.current_mouse)
+        };
+
+        match binding {
+            Binding::Key(key) => keys.get(key),
+            Binding::Mouse(button) => mouse.contains(&button),
+            Binding::Gamepad(button) => self
+                .gamepads
+                .values()
+                .any(|pad| pad.button(button, last_frame)),
+        }
+    }
+
     pub fn start_event_proc(&mut self) {
-        // copy the current keys into the last keys array
-        for i in 0..self.current_keys.len() {
-            self.last_keys[i] = self.current_keys[i];
+        // copy the current keys into the last keys
+        self.last_keys = self.current_keys;
+        self.last_mouse.clone_from(&self.current_mouse);
+
+        // a consumed key stays hidden from actions until it has been released
+        let current_keys = self.current_keys;
+        let current_mouse = &self.current_mouse;
+        self.consumed.retain(|binding| match *binding {
+            Binding::Key(key) => current_keys.get(key),
+            Binding::Mouse(button) => current_mouse.contains(&button),
+            Binding::Gamepad(_) => false,
+        });
+
+        // the per frame values start over
+        self.mouse_delta = [0.0, 0.0];
+        self.scroll_delta = [0.0, 0.0];
+        self.text_input.clear();
+
+        for pad in self.gamepads.values_mut() {
+            pad.start_frame();
+        }
+        self.gamepads_connected.clear();
+        self.gamepads_disconnected.clear();
+    }
+
+    // fetch the gamepad events since the last call, they still have to be passed to
+    // process_gamepad_event, which lets the runner record them or drop them during a replay
+    pub fn poll_gamepads(&mut self) -> Vec<GamepadEvent> {
+        let mut events = Vec::new();
+
+        if let Some(ref mut backend) = self.gamepad_backend {
+            backend.poll(&mut events);
+        }
+
+        events
+    }
+
+    pub fn process_gamepad_event(&mut self, event: &GamepadEvent) {
+        match *event {
+            GamepadEvent::Connected(id) => {
+                self.gamepads.insert(id, GamepadState::new());
+                self.gamepads_connected.push(id);
+            }
+            GamepadEvent::Disconnected(id) => {
+                if self.gamepads.remove(&id).is_some() {
+                    self.gamepads_disconnected.push(id);
+                }
+            }
+            GamepadEvent::ButtonPressed(id, button) => {
+                self.gamepad_state(id).set_button(button, true);
+            }
+            GamepadEvent::ButtonReleased(id, button) => {
+                self.gamepad_state(id).set_button(button, false);
+            }
+            GamepadEvent::AxisChanged(id, axis, value) => {
+                self.gamepad_state(id).set_axis(axis, value);
+            }
+        }
+    }
+
+    // pads that were connected before the game started never send a connected event
+    fn gamepad_state(&mut self, id: GamepadId) -> &mut GamepadState {
+        if !self.gamepads.contains_key(&id) {
+            self.gamepads_connected.push(id);
         }
 
-        for i in 0..self.current_mouse.len() {
-            self.last_mouse[i] = self.current_mouse[i];
+        self.gamepads.entry(id).or_insert_with(GamepadState::new)
+    }
+
+    pub fn process_device_events(&mut self, event: &glutin::DeviceEvent) {
+        if let glutin::DeviceEvent::MouseMotion { delta } = *event {
+            self.add_mouse_motion(delta.0 as f32, delta.1 as f32);
         }
     }
 
@@ -82,7 +534,16 @@ impl Input {
 
         if mouse_position.is_some() {
             let mouse_position = mouse_position.unwrap();
-            self.mouse_position = [mouse_position.x as f32, mouse_position.y as f32];
+            self.set_mouse_position(mouse_position.x as f32, mouse_position.y as f32);
+        }
+
+        if let glutin::WindowEvent::MouseWheel { delta, .. } = *event {
+            let (x, y) = scroll_lines(delta);
+            self.add_scroll(x, y);
+        }
+
+        if let glutin::WindowEvent::ReceivedCharacter(character) = *event {
+            self.push_character(character);
         }
 
         if keyboard_input.is_some() {
@@ -94,38 +555,422 @@ impl Input {
                 None => return,
             };
 
-            if pressed {
-                self.current_keys[key as usize] = true;
-            } else {
-                self.current_keys[key as usize] = false;
-            }
+            self.set_key(key, pressed);
         }
 
         if mouse_input.is_some() {
             let (mouse_button, mouse_state) = mouse_input.unwrap();
+            self.set_mouse_button(mouse_button, mouse_state == glutin::ElementState::Pressed);
+        }
+    }
 
-            if mouse_state == glutin::ElementState::Pressed {
-                self.current_mouse[mouse_code_to_id(mouse_button)] = true;
-            } else {
-                self.current_mouse[mouse_code_to_id(mouse_button)] = false;
-            }
+    // what a key event does to the state, also used to replay recordings without window events
+    pub fn set_key(&mut self, key: glutin::VirtualKeyCode, pressed: bool) {
+        self.current_keys.set(key, pressed);
+    }
+
+    pub fn set_mouse_button(&mut self, button: glutin::MouseButton, pressed: bool) {
+        if pressed {
+            self.current_mouse.insert(button);
+        } else {
+            self.current_mouse.remove(&button);
+        }
+    }
+
+    pub fn set_mouse_position(&mut self, x: f32, y: f32) {
+        self.mouse_position = [x, y];
+    }
+
+    pub fn add_mouse_motion(&mut self, x: f32, y: f32) {
+        self.mouse_delta[0] += x;
+        self.mouse_delta[1] += y;
+    }
+
+    // in lines
+    pub fn add_scroll(&mut self, x: f32, y: f32) {
+        self.scroll_delta[0] += x;
+        self.scroll_delta[1] += y;
+    }
+
+    pub fn push_character(&mut self, character: char) {
+        // backspace, enter and friends are read as keys
+        if !character.is_control() {
+            self.text_input.push(character);
         }
     }
 }
 
-fn mouse_code_to_id(mouse_button: glium::glutin::MouseButton) -> usize {
+// touchpads scroll in pixels and wheels in lines, the scroll delta is always in lines
+fn scroll_lines(delta: glutin::MouseScrollDelta) -> (f32, f32) {
+    match delta {
+        glutin::MouseScrollDelta::LineDelta(x, y) => (x, y),
+        glutin::MouseScrollDelta::PixelDelta(position) => (
+            position.x as f32 / PIXELS_PER_SCROLL_LINE,
+            position.y as f32 / PIXELS_PER_SCROLL_LINE,
+        ),
+    }
+}
+
+fn is_modifier_key(key: glutin::VirtualKeyCode) -> bool {
+    match key {
+        glutin::VirtualKeyCode::LShift
+        | glutin::VirtualKeyCode::RShift
+        | glutin::VirtualKeyCode::LControl
+        | glutin::VirtualKeyCode::RControl
+        | glutin::VirtualKeyCode::LAlt
+        | glutin::VirtualKeyCode::RAlt
+        | glutin::VirtualKeyCode::LWin
+        | glutin::VirtualKeyCode::RWin => true,
+        _ => false,
+    }
+}
+
+// the key with the given id, used to turn stored ids back into keys
+pub fn key_from_id(id: usize) -> Option<glutin::VirtualKeyCode> {
+    ALL_KEYS.get(id).cloned()
+}
+
+// looks a key up by the name it has in the VirtualKeyCode enum, like "W" or "LControl"
+pub fn key_from_name(name: &str) -> Option<glutin::VirtualKeyCode> {
+    (0..KEY_COUNT)
+        .filter_map(key_from_id)
+        .find(|key| format!("{:?}", key) == name)
+}
 
-    let result = match mouse_button {
-        glutin::MouseButton::Left => 0,
-        glutin::MouseButton::Right => 1,
-        glutin::MouseButton::Middle =>  2,
-        glutin::MouseButton::Other(mb) =>  mb,
-    } as i32;
+pub fn mouse_button_name(button: glutin::MouseButton) -> String {
+    match button {
+        glutin::MouseButton::Left => "left".to_string(),
+        glutin::MouseButton::Right => "right".to_string(),
+        glutin::MouseButton::Middle => "middle".to_string(),
+        glutin::MouseButton::Other(id) => id.to_string(),
+    }
+}
 
-    if result == -1 {
-        println!("Unknown mouse key code");
-        return 0;
+pub fn mouse_button_from_name(name: &str) -> Option<glutin::MouseButton> {
+    match name {
+        "left" => Some(glutin::MouseButton::Left),
+        "right" => Some(glutin::MouseButton::Right),
+        "middle" => Some(glutin::MouseButton::Middle),
+        id => id.parse().ok().map(glutin::MouseButton::Other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headless_input() -> Input {
+        Input::with_gamepad_backend(None)
+    }
+
+    #[test]
+    fn key_ids_match_the_enum() {
+        for id in 0..KEY_COUNT {
+            let key = key_from_id(id).unwrap();
+            assert_eq!(key as usize, id, "{:?}", key);
+            assert_eq!(key_from_name(&format!("{:?}", key)), Some(key));
+        }
+    }
+
+    #[test]
+    fn out_of_range_key_ids_are_rejected() {
+        assert!(key_from_id(KEY_COUNT).is_none());
+        assert!(key_from_id(KEY_COUNT + 64).is_none());
+        assert!(key_from_id(usize::max_value()).is_none());
+    }
+
+    #[test]
+    fn every_key_goes_down_and_up() {
+        let mut input = headless_input();
+
+        for id in 0..KEY_COUNT {
+            let key = key_from_id(id).unwrap();
+
+            input.start_event_proc();
+            input.set_key(key, true);
+            assert!(input.get_key(key), "{:?}", key);
+            assert!(input.get_key_down(key), "{:?}", key);
+            assert!(!input.get_key_up(key), "{:?}", key);
+            // the bit of one key never leaks into another
+            assert_eq!(input.keys_down(), vec![key]);
+
+            input.start_event_proc();
+            assert!(input.get_key(key), "{:?}", key);
+            assert!(!input.get_key_down(key), "{:?}", key);
+
+            input.start_event_proc();
+            input.set_key(key, false);
+            assert!(!input.get_key(key), "{:?}", key);
+            assert!(!input.get_key_down(key), "{:?}", key);
+            assert!(input.get_key_up(key), "{:?}", key);
+
+            input.start_event_proc();
+            assert!(!input.get_key_up(key), "{:?}", key);
+        }
     }
 
-    result as usize
+    #[test]
+    fn extended_mouse_buttons_go_down_and_up() {
+        let mut input = headless_input();
+
+        for &id in &[0u8, 4, 5, 31, 64, 200, 255] {
+            let button = glutin::MouseButton::Other(id);
+
+            input.start_event_proc();
+            input.set_mouse_button(button, true);
+            assert!(input.get_mouse(button));
+            assert!(input.get_mouse_down(button));
+            assert!(!input.get_mouse_up(button));
+            assert!(!input.get_mouse(glutin::MouseButton::Left));
+
+            input.start_event_proc();
+            assert!(input.get_mouse(button));
+            assert!(!input.get_mouse_down(button));
+
+            input.start_event_proc();
+            input.set_mouse_button(button, false);
+            assert!(!input.get_mouse(button));
+            assert!(input.get_mouse_up(button));
+
+            assert_eq!(mouse_button_from_name(&mouse_button_name(button)), Some(button));
+        }
+    }
+
+    #[test]
+    fn ctrl_s_does_not_fire_s_alone() {
+        let mut input = headless_input();
+        input.shortcuts = ShortcutRegistry::new();
+        input.shortcuts.register("save", "Ctrl+S");
+        input
+            .actions
+            .bind_action("move_back", Binding::Key(glutin::VirtualKeyCode::S));
+
+        input.start_event_proc();
+        input.set_key(glutin::VirtualKeyCode::LControl, true);
+        input.set_key(glutin::VirtualKeyCode::S, true);
+        input.update_shortcuts(0.0);
+
+        assert!(input.shortcut_triggered("save"));
+        assert!(!input.action_down("move_back"));
+        assert!(!input.action_pressed("move_back"));
+        assert!(!input.get_key_down(glutin::VirtualKeyCode::S));
+        assert!(!input.get_key(glutin::VirtualKeyCode::S));
+        assert!(input.is_consumed(Binding::Key(glutin::VirtualKeyCode::S)));
+        // the modifier is not part of the consumed chord
+        assert!(input.get_key(glutin::VirtualKeyCode::LControl));
+
+        // still hidden while held and on release
+        input.start_event_proc();
+        input.set_key(glutin::VirtualKeyCode::LControl, false);
+        input.update_shortcuts(0.0);
+        assert!(!input.action_pressed("move_back"));
+        assert!(!input.get_key(glutin::VirtualKeyCode::S));
+
+        input.start_event_proc();
+        input.set_key(glutin::VirtualKeyCode::S, false);
+        input.update_shortcuts(0.0);
+        assert!(!input.get_key_up(glutin::VirtualKeyCode::S));
+
+        // back once pressed again without ctrl
+        input.start_event_proc();
+        input.set_key(glutin::VirtualKeyCode::S, true);
+        input.update_shortcuts(0.0);
+        assert!(!input.shortcut_triggered("save"));
+        assert!(input.action_down("move_back"));
+        assert!(input.get_key_down(glutin::VirtualKeyCode::S));
+    }
+
+    #[test]
+    fn mouse_shortcuts_hide_the_click() {
+        let mut input = headless_input();
+        input.shortcuts = ShortcutRegistry::new();
+        input
+            .shortcuts
+            .register("add_to_selection", "Shift+MouseLeft");
+
+        input.start_event_proc();
+        input.set_key(glutin::VirtualKeyCode::LShift, true);
+        input.set_mouse_button(glutin::MouseButton::Left, true);
+        input.update_shortcuts(0.0);
+
+        assert!(input.shortcut_triggered("add_to_selection"));
+        assert!(!input.get_mouse_down(glutin::MouseButton::Left));
+        assert!(!input.get_mouse(glutin::MouseButton::Left));
+
+        input.start_event_proc();
+        input.set_mouse_button(glutin::MouseButton::Left, false);
+        input.update_shortcuts(0.0);
+        assert!(!input.get_mouse_up(glutin::MouseButton::Left));
+
+        // a plain click later is seen again
+        input.start_event_proc();
+        input.set_key(glutin::VirtualKeyCode::LShift, false);
+        input.set_mouse_button(glutin::MouseButton::Left, true);
+        input.update_shortcuts(0.0);
+        assert!(input.get_mouse_down(glutin::MouseButton::Left));
+    }
+
+    // one frame of the runner's gamepad handling
+    fn pump_gamepads(input: &mut Input) {
+        input.start_event_proc();
+
+        for event in input.poll_gamepads() {
+            input.process_gamepad_event(&event);
+        }
+    }
+
+    #[test]
+    fn gamepad_dead_zones_filter_small_values() {
+        let backend = FakeGamepadBackend::new();
+        let mut input = Input::with_gamepad_backend(Some(Box::new(backend.clone())));
+        input.stick_dead_zone = 0.2;
+        input.trigger_dead_zone = 0.1;
+
+        backend.connect(0);
+        backend.set_axis(0, GamepadAxis::LeftStickX, 0.1);
+        backend.set_axis(0, GamepadAxis::LeftStickY, -0.1);
+        backend.set_axis(0, GamepadAxis::LeftTrigger, 0.05);
+        pump_gamepads(&mut input);
+
+        assert_eq!(input.get_gamepad_stick(0, GamepadStick::Left), [0.0, 0.0]);
+        assert_eq!(input.get_gamepad_axis(0, GamepadAxis::LeftStickX), 0.0);
+        assert_eq!(input.get_gamepad_axis(0, GamepadAxis::LeftTrigger), 0.0);
+
+        backend.set_axis(0, GamepadAxis::LeftStickX, 0.0);
+        backend.set_axis(0, GamepadAxis::LeftStickY, -0.6);
+        backend.set_axis(0, GamepadAxis::LeftTrigger, 1.0);
+        pump_gamepads(&mut input);
+
+        assert!((input.get_gamepad_axis(0, GamepadAxis::LeftStickY) + 0.5).abs() < 1e-6);
+        assert_eq!(input.get_gamepad_axis(0, GamepadAxis::LeftTrigger), 1.0);
+
+        // bound axes see the filtered value too
+        input.actions.bind_axis(
+            "throttle",
+            AxisBinding::Gamepad(GamepadAxis::LeftTrigger, 2.0),
+        );
+        assert_eq!(input.action_axis("throttle"), 2.0);
+    }
+
+    #[test]
+    fn gamepads_connect_and_disconnect_independently() {
+        let backend = FakeGamepadBackend::new();
+        let mut input = Input::with_gamepad_backend(Some(Box::new(backend.clone())));
+
+        backend.connect(2);
+        backend.connect(0);
+        backend.press(2, GamepadButton::South);
+        pump_gamepads(&mut input);
+
+        assert_eq!(input.connected_gamepads(), vec![0, 2]);
+        assert_eq!(input.gamepads_connected(), &[2, 0]);
+        assert!(input.get_gamepad_button_down(2, GamepadButton::South));
+        assert!(!input.get_gamepad_button(0, GamepadButton::South));
+
+        backend.disconnect(2);
+        backend.press(0, GamepadButton::East);
+        pump_gamepads(&mut input);
+
+        assert!(input.gamepads_connected().is_empty());
+        assert_eq!(input.gamepads_disconnected(), &[2]);
+        assert_eq!(input.connected_gamepads(), vec![0]);
+        assert!(!input.is_gamepad_connected(2));
+        assert!(!input.get_gamepad_button(2, GamepadButton::South));
+        assert!(input.get_gamepad_button_down(0, GamepadButton::East));
+
+        // disconnecting a pad twice only reports it once, and a pad can come back
+        backend.disconnect(2);
+        backend.connect(2);
+        pump_gamepads(&mut input);
+
+        assert!(input.gamepads_disconnected().is_empty());
+        assert_eq!(input.gamepads_connected(), &[2]);
+        assert_eq!(input.connected_gamepads(), vec![0, 2]);
+        assert!(input.get_gamepad_button(0, GamepadButton::East));
+        assert!(!input.get_gamepad_button_down(0, GamepadButton::East));
+    }
+
+    #[test]
+    fn out_of_range_mouse_button_names_are_rejected() {
+        assert_eq!(mouse_button_from_name("256"), None);
+        assert_eq!(mouse_button_from_name("-1"), None);
+        assert_eq!(mouse_button_from_name("wheel"), None);
+    }
+
+    #[test]
+    fn pixel_scrolling_is_converted_to_lines() {
+        let pixels = glutin::MouseScrollDelta::PixelDelta(glutin::dpi::LogicalPosition::new(
+            2.0 * PIXELS_PER_SCROLL_LINE as f64,
+            -0.5 * PIXELS_PER_SCROLL_LINE as f64,
+        ));
+        assert_eq!(scroll_lines(pixels), (2.0, -0.5));
+
+        let lines = glutin::MouseScrollDelta::LineDelta(1.0, -3.0);
+        assert_eq!(scroll_lines(lines), (1.0, -3.0));
+
+        // both kinds add up within a frame
+        let mut input = headless_input();
+        let (x, y) = scroll_lines(pixels);
+        input.add_scroll(x, y);
+        let (x, y) = scroll_lines(lines);
+        input.add_scroll(x, y);
+        assert_eq!(input.get_scroll_delta(), [3.0, -3.5]);
+    }
+
+    #[test]
+    fn control_characters_are_not_text() {
+        let mut input = headless_input();
+
+        for &character in &['h', '\u{8}', 'é', '\r', '\n', '\t', '\u{1b}', '\u{7f}', '!'] {
+            input.push_character(character);
+        }
+
+        assert_eq!(input.get_text_input(), "hé!");
+    }
+
+    #[test]
+    fn cursor_requests_are_taken_once() {
+        let mut input = headless_input();
+        assert_eq!(input.take_cursor_request(), None);
+
+        input.set_cursor_locked(true);
+        assert!(input.is_cursor_locked());
+        assert_eq!(input.take_cursor_request(), Some((true, true)));
+        assert_eq!(input.take_cursor_request(), None);
+
+        // setting the state it is already in asks for nothing
+        input.set_cursor_locked(true);
+        assert_eq!(input.take_cursor_request(), None);
+
+        input.set_cursor_visible(false);
+        input.set_cursor_locked(false);
+        assert_eq!(input.take_cursor_request(), Some((false, false)));
+        assert_eq!(input.take_cursor_request(), None);
+    }
+
+    #[test]
+    fn per_frame_values_start_over() {
+        let mut input = headless_input();
+
+        input.start_event_proc();
+        input.set_mouse_position(10.0, 20.0);
+        input.add_mouse_motion(1.0, 2.0);
+        input.add_mouse_motion(3.0, -1.0);
+        input.add_scroll(0.0, 1.0);
+        input.push_character('a');
+        input.set_key(glutin::VirtualKeyCode::A, true);
+
+        assert_eq!(input.get_mouse_delta(), [4.0, 1.0]);
+        assert_eq!(input.get_scroll_delta(), [0.0, 1.0]);
+        assert_eq!(input.get_text_input(), "a");
+
+        input.start_event_proc();
+        assert_eq!(input.get_mouse_delta(), [0.0, 0.0]);
+        assert_eq!(input.get_scroll_delta(), [0.0, 0.0]);
+        assert_eq!(input.get_text_input(), "");
+
+        // held state and the cursor position carry over
+        assert!(input.get_key(glutin::VirtualKeyCode::A));
+        assert_eq!(input.get_mouse_pos(), [10.0, 20.0]);
+    }
 }
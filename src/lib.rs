@@ -0,0 +1,44 @@
+#[macro_use]
+extern crate glium;
+extern crate binary_rw;
+extern crate colored;
+extern crate gilrs;
+extern crate nalgebra as na;
+extern crate ncollide3d;
+extern crate nphysics3d;
+extern crate rusttype;
+extern crate stopwatch;
+extern crate tobj;
+
+pub mod action_map;
+pub mod app;
+pub mod assets;
+pub mod bounds;
+pub mod camera;
+pub mod camera_controller;
+pub mod camera_path;
+pub mod ecs;
+pub mod editor;
+pub mod gameobject;
+pub mod gamepad;
+pub mod input;
+pub mod lighting;
+pub mod material;
+pub mod math_helper;
+pub mod model;
+pub mod physics_engine;
+pub mod prefab;
+pub mod quaternion;
+pub mod raycast;
+pub mod recording;
+pub mod render_queue;
+pub mod renderer;
+pub mod resource_manager;
+pub mod runner;
+pub mod scene_tree;
+pub mod shader;
+pub mod shortcuts;
+pub mod texture;
+pub mod time;
+pub mod ui_renderer;
+pub mod vector;
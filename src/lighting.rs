@@ -0,0 +1,260 @@
+// gathers the lights of the scene once per frame and picks the ones that matter most for each
+// draw, the shader only has room for MAX_LIGHTS. all of it runs on the cpu
+use bounds::Aabb;
+use ecs::{join, EntityContext, Light, LightKind};
+use vector::Vector3;
+
+// has to match MAX_LIGHTS in basic.fs
+pub const MAX_LIGHTS: usize = 8;
+
+// a light in world space
+#[derive(Copy, Clone)]
+pub struct SceneLight {
+    pub kind: LightKind,
+    pub position: Vector3,
+    // the way the light travels, normalized
+    pub direction: Vector3,
+    pub color: [f32; 3],
+    pub intensity: f32,
+    pub range: f32,
+    pub attenuation: [f32; 3],
+    pub inner_angle: f32,
+    pub outer_angle: f32,
+}
+
+#[allow(dead_code)]
+impl SceneLight {
+    pub fn new(light: &Light, position: Vector3, direction: Vector3) -> SceneLight {
+        SceneLight {
+            kind: light.kind,
+            position: position,
+            direction: direction.normalized(),
+            color: light.color,
+            intensity: light.intensity,
+            range: light.range,
+            attenuation: light.attenuation,
+            inner_angle: light.inner_angle,
+            outer_angle: light.outer_angle,
+        }
+    }
+
+    // the light used when the scene has none
+    pub fn default_sun() -> SceneLight {
+        SceneLight::new(
+            &Light::directional([1.0, 1.0, 1.0], 1.0),
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(-0.5, -1.0, 0.0),
+        )
+    }
+
+    // colour times intensity, what the shader gets
+    pub fn radiance(&self) -> [f32; 3] {
+        [
+            self.color[0] * self.intensity,
+            self.color[1] * self.intensity,
+            self.color[2] * self.intensity,
+        ]
+    }
+
+    // same falloff as basic.fs, including the fade towards the range
+    pub fn attenuation_at(&self, distance: f32) -> f32 {
+        if self.kind == LightKind::Directional {
+            return 1.0;
+        }
+        if distance >= self.range {
+            return 0.0;
+        }
+
+        let [constant, linear, quadratic] = self.attenuation;
+        let falloff = constant + linear * distance + quadratic * distance * distance;
+        let fade = 1.0 - distance / self.range;
+
+        fade / falloff.max(0.0001)
+    }
+
+    // roughly how much the light adds to anything inside the box, 0 when it can't reach it.
+    // directional lights always reach
+    pub fn influence(&self, bounds: &Aabb) -> f32 {
+        let brightness = (self.color[0] + self.color[1] + self.color[2]) / 3.0 * self.intensity;
+
+        if self.kind == LightKind::Directional {
+            return brightness;
+        }
+
+        if !bounds.intersects_sphere(self.position, self.range) {
+            return 0.0;
+        }
+
+        if self.kind == LightKind::Spot && !self.cone_reaches(bounds) {
+            return 0.0;
+        }
+
+        let closest = Vector3::new(
+            self.position.x.max(bounds.min.x).min(bounds.max.x),
+            self.position.y.max(bounds.min.y).min(bounds.max.y),
+            self.position.z.max(bounds.min.z).min(bounds.max.z),
+        );
+
+        brightness * self.attenuation_at((closest - self.position).length())
+    }
+
+    // conservative test of the sphere around the box against the outer cone
+    fn cone_reaches(&self, bounds: &Aabb) -> bool {
+        let to_center = bounds.center() - self.position;
+        let distance = to_center.length();
+        let radius = bounds.half_size().length();
+
+        if distance <= radius {
+            return true;
+        }
+
+        let angle = (to_center.dot(self.direction) / distance)
+            .max(-1.0)
+            .min(1.0)
+            .acos();
+        let sphere_angle = (radius / distance).asin();
+
+        angle - sphere_angle <= self.outer_angle
+    }
+}
+
+// every light component with a transform, or the default sun for scenes without lights
+pub fn collect_lights(entities: &EntityContext) -> Vec<SceneLight> {
+    let lights: Vec<SceneLight> = join(&entities.transforms, &entities.lights)
+        .map(|(_entity, transform, light)| {
+            SceneLight::new(light, transform.position, transform.rotation.forward())
+        })
+        .collect();
+
+    if lights.is_empty() {
+        vec![SceneLight::default_sun()]
+    } else {
+        lights
+    }
+}
+
+// indices of the at most max lights with the most influence on the box, strongest first
+pub fn select_lights(lights: &[SceneLight], bounds: &Aabb, max: usize) -> Vec<usize> {
+    let mut ranked: Vec<(usize, f32)> = lights
+        .iter()
+        .enumerate()
+        .map(|(index, light)| (index, light.influence(bounds)))
+        .filter(|&(_, influence)| influence > 0.0)
+        .collect();
+
+    // stable, lights with equal influence keep their scene order. nan never passes the filter
+    // above, the total order is only there so nothing can panic here
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+    ranked.truncate(max);
+
+    ranked.into_iter().map(|(index, _)| index).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point_at(x: f32, intensity: f32, range: f32) -> SceneLight {
+        SceneLight::new(
+            &Light::point([1.0, 1.0, 1.0], intensity, range).with_attenuation(1.0, 0.0, 0.0),
+            Vector3::new(x, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, 1.0),
+        )
+    }
+
+    fn unit_box() -> Aabb {
+        Aabb::from_center(Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.5, 0.5, 0.5))
+    }
+
+    #[test]
+    fn attenuation_fades_to_zero_at_the_range() {
+        let light = point_at(0.0, 1.0, 10.0);
+
+        assert_eq!(light.attenuation_at(0.0), 1.0);
+        assert_eq!(light.attenuation_at(5.0), 0.5);
+        assert_eq!(light.attenuation_at(10.0), 0.0);
+        assert_eq!(light.attenuation_at(20.0), 0.0);
+    }
+
+    #[test]
+    fn attenuation_uses_the_falloff_terms() {
+        let light = SceneLight::new(
+            &Light::point([1.0, 1.0, 1.0], 1.0, 10.0).with_attenuation(1.0, 0.5, 0.25),
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, 1.0),
+        );
+
+        // fade 0.8 over a falloff of 1 + 1 + 1
+        assert!((light.attenuation_at(2.0) - 0.8 / 3.0).abs() < 1e-6);
+
+        // a zero falloff doesn't divide by zero
+        let unlit = SceneLight::new(
+            &Light::point([1.0, 1.0, 1.0], 1.0, 10.0).with_attenuation(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, 1.0),
+        );
+        assert!(unlit.attenuation_at(0.0).is_finite());
+    }
+
+    #[test]
+    fn directional_lights_dont_attenuate() {
+        let sun = SceneLight::default_sun();
+
+        assert_eq!(sun.attenuation_at(0.0), 1.0);
+        assert_eq!(sun.attenuation_at(1.0e6), 1.0);
+    }
+
+    #[test]
+    fn select_lights_orders_by_influence_and_skips_unreachable_lights() {
+        let lights = vec![
+            point_at(5.0, 1.0, 10.0),
+            point_at(100.0, 1.0, 10.0),
+            point_at(2.0, 1.0, 10.0),
+            point_at(5.0, 4.0, 10.0),
+        ];
+
+        assert_eq!(
+            select_lights(&lights, &unit_box(), MAX_LIGHTS),
+            vec![3, 2, 0]
+        );
+        assert_eq!(select_lights(&lights, &unit_box(), 2), vec![3, 2]);
+    }
+
+    #[test]
+    fn select_lights_keeps_the_scene_order_for_ties() {
+        let lights = vec![
+            point_at(3.0, 1.0, 10.0),
+            point_at(-3.0, 1.0, 10.0),
+            SceneLight::default_sun(),
+        ];
+
+        assert_eq!(
+            select_lights(&lights, &unit_box(), MAX_LIGHTS),
+            vec![2, 0, 1]
+        );
+    }
+
+    #[test]
+    fn select_lights_ignores_nan_lights() {
+        let lights = vec![
+            point_at(2.0, std::f32::NAN, 10.0),
+            point_at(std::f32::NAN, 1.0, 10.0),
+            point_at(2.0, 1.0, 10.0),
+        ];
+
+        assert_eq!(select_lights(&lights, &unit_box(), MAX_LIGHTS), vec![2]);
+    }
+
+    #[test]
+    fn spot_lights_only_reach_boxes_in_their_cone() {
+        let spot = SceneLight::new(
+            &Light::spot([1.0, 1.0, 1.0], 1.0, 20.0, 0.2, 0.3),
+            Vector3::new(0.0, 0.0, -10.0),
+            Vector3::new(0.0, 0.0, 1.0),
+        );
+        let behind = Aabb::from_center(Vector3::new(0.0, 0.0, -15.0), Vector3::new(0.5, 0.5, 0.5));
+
+        assert!(spot.influence(&unit_box()) > 0.0);
+        assert_eq!(spot.influence(&behind), 0.0);
+    }
+}
@@ -7,6 +7,8 @@ pub struct Material {
 	pub normal_tex: Resource,
 	pub color: [f32; 3],
 	pub tiling: [f32; 2],
+	// below 1 the material is blended and drawn after the opaque objects
+	pub opacity: f32,
 }
 
 impl Material {
@@ -23,6 +25,16 @@ impl Material {
 			normal_tex: normal_tex,
 			color: color,
 			tiling: tiling,
+			opacity: 1.0,
 		}
 	}
+
+	pub fn with_opacity(mut self, opacity: f32) -> Self {
+		self.opacity = opacity;
+		self
+	}
+
+	pub fn is_transparent(&self) -> bool {
+		self.opacity < 1.0
+	}
 }
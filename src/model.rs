@@ -17,13 +17,16 @@ implement_vertex!(ModelVertex, position, normal, tangent, texcoord);
 pub struct Model {
     pub vertices: Vec<ModelVertex>,
     pub indices: Vec<u16>,
-    pub vertex_buffer: glium::VertexBuffer<ModelVertex>,
-    pub index_buffer: glium::IndexBuffer<u16>,
+    // the gpu buffers are only created when there is a display
+    pub vertex_buffer: Option<glium::VertexBuffer<ModelVertex>>,
+    pub index_buffer: Option<glium::IndexBuffer<u16>>,
     pub bounding_box: [f32; 3], // Size X, Size Y, Size Z
+    // centre of the box in model space, meshes don't have to be centred on their origin
+    pub bounding_center: [f32; 3],
 }
 
 impl Model {
-    pub fn load(display: &glium::Display, obj_buffer: &[u8], mtl_buffer: &[u8]) -> Model {
+    pub fn load(display: Option<&glium::Display>, obj_buffer: &[u8], mtl_buffer: &[u8]) -> Model {
         let mut obj_buf = BufReader::new(obj_buffer);
 
         let tobj_model = tobj::load_obj_buf(&mut obj_buf, |_| {
@@ -77,14 +80,19 @@ impl Model {
         // println!("  Length of vertex array: {}", vertices.len());
         // println!("  Length of index array: {}", indices.len());
 
-        let vb = glium::VertexBuffer::new(display, &vertices).unwrap();
-        let ib = glium::IndexBuffer::new(
-            display,
-            glium::index::PrimitiveType::TrianglesList,
-            &indices,
-        ).unwrap();
+        let mut vb = None;
+        let mut ib = None;
 
-        let bounding_box = Model::calculate_bounding_box(&mut vertices);
+        if let Some(display) = display {
+            vb = Some(glium::VertexBuffer::new(display, &vertices).unwrap());
+            ib = Some(glium::IndexBuffer::new(
+                display,
+                glium::index::PrimitiveType::TrianglesList,
+                &indices,
+            ).unwrap());
+        }
+
+        let (bounding_center, bounding_box) = Model::calculate_bounding_box(&vertices);
 
         Model {
             vertices: vertices,
@@ -92,48 +100,34 @@ impl Model {
             vertex_buffer: vb,
             index_buffer: ib,
             bounding_box: bounding_box,
+            bounding_center: bounding_center,
         }
     }
 
-    fn calculate_bounding_box(vertices: &mut Vec<ModelVertex>) -> [f32; 3] {
-        let mut min_x: f32 = 50000000.0;
-        let mut max_x: f32 = -50000000.0;
-        let mut min_y: f32 = 50000000.0;
-        let mut max_y: f32 = -50000000.0;
-        let mut min_z: f32 = 50000000.0;
-        let mut max_z: f32 = -50000000.0;
-
-        for i in 0..vertices.len() {
-            let mut vpos = vertices[i].position;
-            if vpos[0] < min_x {
-                min_x = vpos[0];
-            }
-            if vpos[0] > max_x {
-                max_x = vpos[0];
-            }
+    // returns the centre and the size of the box around the vertices
+    fn calculate_bounding_box(vertices: &Vec<ModelVertex>) -> ([f32; 3], [f32; 3]) {
+        if vertices.is_empty() {
+            return ([0.0, 0.0, 0.0], [0.0, 0.0, 0.0]);
+        }
 
-            if vpos[1] < min_y {
-                min_y = vpos[1];
-            }
-            if vpos[1] > max_y {
-                max_y = vpos[1];
-            }
+        let mut min = vertices[0].position;
+        let mut max = vertices[0].position;
 
-            if vpos[2] < min_z {
-                min_z = vpos[2];
-            }
-            if vpos[2] > max_z {
-                max_z = vpos[2];
+        for vertex in vertices {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(vertex.position[axis]);
+                max[axis] = max[axis].max(vertex.position[axis]);
             }
         }
 
-        let bounding_box = [
-            min_x.abs() + max_x.abs(),
-            min_y.abs() + max_y.abs(),
-            min_z.abs() + max_z.abs(),
+        let center = [
+            (min[0] + max[0]) / 2.0,
+            (min[1] + max[1]) / 2.0,
+            (min[2] + max[2]) / 2.0,
         ];
+        let size = [max[0] - min[0], max[1] - min[1], max[2] - min[2]];
 
-        bounding_box
+        (center, size)
     }
 
     pub fn sub_vec3(l: [f32; 3], r: [f32; 3]) -> [f32; 3] {
@@ -193,3 +187,37 @@ impl Model {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vertex(position: [f32; 3]) -> ModelVertex {
+        ModelVertex {
+            position: position,
+            normal: [0.0, 0.0, 0.0],
+            tangent: [0.0, 0.0, 0.0],
+            texcoord: [0.0, 0.0],
+        }
+    }
+
+    #[test]
+    fn bounding_box_of_an_off_centre_mesh() {
+        let vertices = vec![vertex([1.0, -1.0, 0.0]), vertex([3.0, 2.0, -4.0])];
+
+        let (center, size) = Model::calculate_bounding_box(&vertices);
+
+        assert_eq!(center, [2.0, 0.5, -2.0]);
+        assert_eq!(size, [2.0, 3.0, 4.0]);
+    }
+
+    #[test]
+    fn bounding_box_of_a_centred_mesh() {
+        let vertices = vec![vertex([-1.0, -1.0, -1.0]), vertex([1.0, 1.0, 3.0])];
+
+        let (center, size) = Model::calculate_bounding_box(&vertices);
+
+        assert_eq!(center, [0.0, 0.0, 1.0]);
+        assert_eq!(size, [2.0, 2.0, 4.0]);
+    }
+}
@@ -2,29 +2,99 @@ extern crate nalgebra as na;
 extern crate ncollide3d;
 extern crate nphysics3d;
 
-use na::{Isometry3, Vector3};
+use camera::Ray;
+use na::{Isometry3, Point3, Vector3};
+use ncollide3d::query;
 use ncollide3d::shape::ShapeHandle;
+use ncollide3d::world::{CollisionGroups, CollisionObjectHandle};
 use nphysics3d::object::{BodyHandle, Material};
 use nphysics3d::volumetric::Volumetric;
 use nphysics3d::world::World;
 use quaternion;
 use vector;
 
+#[derive(Copy, Clone)]
 pub enum PhysicsShape {
     SphereShape,
     BoxShape,
 }
 
+// a collider hit by a ray, body is the ground handle for static colliders
+#[derive(Copy, Clone)]
+pub struct PhysicsRayHit {
+    pub collider: CollisionObjectHandle,
+    pub body: BodyHandle,
+    pub distance: f32,
+    pub normal: vector::Vector3,
+}
+
 pub struct PhysicsContext {
     world: World<f32>,
+
+    fixed_timestep: f32,
+    max_substeps: u32,
+    accumulator: f32,
 }
 
+#[allow(dead_code)]
 impl PhysicsContext {
     pub fn new() -> PhysicsContext {
+        let fixed_timestep = 1.0 / 60.0;
+
         let mut world = World::new();
         world.set_gravity(Vector3::y() * -2.81);
+        world.set_timestep(fixed_timestep);
+
+        PhysicsContext {
+            world: world,
+            fixed_timestep: fixed_timestep,
+            max_substeps: 5,
+            accumulator: 0.0,
+        }
+    }
+
+    // panics unless the step is positive and finite, a zero, negative or NaN step would make
+    // fixed_steps loop forever or divide by zero
+    pub fn set_fixed_timestep(&mut self, fixed_timestep: f32) {
+        assert!(
+            fixed_timestep > 0.0 && fixed_timestep.is_finite(),
+            "fixed timestep must be positive and finite, got {}",
+            fixed_timestep
+        );
 
-        PhysicsContext { world: world }
+        self.fixed_timestep = fixed_timestep;
+        self.world.set_timestep(fixed_timestep);
+    }
+
+    pub fn get_fixed_timestep(&self) -> f32 {
+        self.fixed_timestep
+    }
+
+    pub fn set_max_substeps(&mut self, max_substeps: u32) {
+        self.max_substeps = max_substeps;
+    }
+
+    // add the frame time to the accumulator and return how many fixed steps should run this frame
+    pub fn fixed_steps(&mut self, delta_time: f32) -> u32 {
+        self.accumulator += delta_time;
+
+        let mut steps = 0;
+        while self.accumulator >= self.fixed_timestep && steps < self.max_substeps {
+            self.accumulator -= self.fixed_timestep;
+            steps += 1;
+        }
+
+        // we are too far behind to catch up, drop the time instead of spiraling
+        if self.accumulator >= self.fixed_timestep {
+            self.accumulator %= self.fixed_timestep;
+        }
+
+        steps
+    }
+
+    // how far we are between the last two physics states, used to interpolate rendering
+    pub fn interpolation_alpha(&self) -> f32 {
+        self.accumulator / self.fixed_timestep
     }
 
     pub fn step(&mut self) {
@@ -81,6 +151,15 @@ impl PhysicsContext {
         handle
     }
 
+    pub fn remove_rigid_body(&mut self, handle: &nphysics3d::object::BodyHandle) {
+        // this also removes the colliders attached to the body
+        self.world.remove_bodies(&[*handle]);
+    }
+
+    pub fn remove_collider(&mut self, handle: &ncollide3d::world::CollisionObjectHandle) {
+        self.world.remove_colliders(&[*handle]);
+    }
+
     pub fn add_collider(
         &mut self,
         shape: ShapeHandle<f32>,
@@ -94,4 +173,77 @@ impl PhysicsContext {
             Material::default(),
         )
     }
+
+    // every collider the ray hits within max_distance, in no particular order
+    pub fn raycast(&self, ray: &Ray, max_distance: f32) -> Vec<PhysicsRayHit> {
+        let ray = query::Ray::new(
+            Point3::new(ray.origin.x, ray.origin.y, ray.origin.z),
+            Vector3::new(ray.direction.x, ray.direction.y, ray.direction.z),
+        );
+        let groups = CollisionGroups::new();
+
+        let mut hits = Vec::new();
+        for (collider, intersection) in self
+            .world
+            .collision_world()
+            .interferences_with_ray(&ray, &groups)
+        {
+            if intersection.toi > max_distance {
+                continue;
+            }
+
+            hits.push(PhysicsRayHit {
+                collider: collider.handle(),
+                body: collider.data().body(),
+                distance: intersection.toi,
+                normal: vector::Vector3::new(
+                    intersection.normal.x,
+                    intersection.normal.y,
+                    intersection.normal.z,
+                ),
+            });
+        }
+
+        hits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_steps_follow_the_timestep() {
+        let mut physics = PhysicsContext::new();
+        physics.set_fixed_timestep(0.5);
+
+        assert_eq!(physics.fixed_steps(0.25), 0);
+        assert_eq!(physics.fixed_steps(0.25), 1);
+        assert_eq!(physics.fixed_steps(1.0), 2);
+        assert_eq!(physics.get_fixed_timestep(), 0.5);
+    }
+
+    #[test]
+    #[should_panic(expected = "fixed timestep must be positive and finite")]
+    fn zero_timestep_is_rejected() {
+        PhysicsContext::new().set_fixed_timestep(0.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "fixed timestep must be positive and finite")]
+    fn negative_timestep_is_rejected() {
+        PhysicsContext::new().set_fixed_timestep(-1.0 / 60.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "fixed timestep must be positive and finite")]
+    fn nan_timestep_is_rejected() {
+        PhysicsContext::new().set_fixed_timestep(std::f32::NAN);
+    }
+
+    #[test]
+    #[should_panic(expected = "fixed timestep must be positive and finite")]
+    fn infinite_timestep_is_rejected() {
+        PhysicsContext::new().set_fixed_timestep(std::f32::INFINITY);
+    }
 }
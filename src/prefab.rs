@@ -0,0 +1,742 @@
+// prefabs are small text files in the asset pack describing a game object, one property per line:
+//
+//   name sphere
+//   tag ball
+//   model ./res/test.obj ./res/test.mtl
+//   diffuse ./res/grass.jpg
+//   physics rigidbody sphere
+//   child ./res/prefabs/other.prefab 0.0 1.0 0.0
+//
+// overrides use the same syntax and are applied on top of the prefab when instantiating
+extern crate glium;
+
+use assets;
+use colored::*;
+use ecs::*;
+use gameobject::GameObject;
+use material::Material;
+use model::Model;
+use physics_engine::{PhysicsContext, PhysicsShape};
+use resource_manager::*;
+use shader;
+use std::collections::HashMap;
+use texture;
+use vector::Vector3;
+
+#[derive(Copy, Clone)]
+pub enum PrefabPhysics {
+    None,
+    Collider(PhysicsShape),
+    RigidBody(PhysicsShape),
+}
+
+#[derive(Clone)]
+pub struct PrefabChild {
+    pub path: String,
+    pub offset: Vector3,
+}
+
+#[derive(Clone)]
+pub struct Prefab {
+    pub path: String,
+    pub overrides: Vec<String>,
+
+    pub name: String,
+    pub tags: Vec<String>,
+    pub layer: u32,
+    pub model: Option<(String, String)>, // obj, mtl
+    pub shader: (String, String),        // vertex, fragment
+    pub diffuse: String,
+    pub normal: String,
+    pub color: [f32; 3],
+    pub tiling: [f32; 2],
+    pub opacity: f32,
+    pub overlay: bool,
+    pub physics: PrefabPhysics,
+    pub children: Vec<PrefabChild>,
+}
+
+#[allow(dead_code)]
+impl Prefab {
+    pub fn parse(path: &str, source: &str) -> Prefab {
+        let mut prefab = Prefab {
+            path: path.to_string(),
+            overrides: Vec::new(),
+
+            name: "prefab".to_string(),
+            tags: Vec::new(),
+            layer: DEFAULT_LAYER,
+            model: None,
+            shader: ("./res/basic.vs".to_string(), "./res/basic.fs".to_string()),
+            diffuse: "./res/white.jpg".to_string(),
+            normal: "./res/white.jpg".to_string(),
+            color: [1.0, 1.0, 1.0],
+            tiling: [1.0, 1.0],
+            opacity: 1.0,
+            overlay: false,
+            physics: PrefabPhysics::None,
+            children: Vec::new(),
+        };
+
+        for line in source.lines() {
+            prefab.apply_property(line);
+        }
+
+        prefab
+    }
+
+    // returns a copy of the prefab with the given per-instance properties applied
+    pub fn with_overrides(mut self, overrides: &[&str]) -> Prefab {
+        for line in overrides {
+            self.apply_property(line);
+            self.overrides.push(line.to_string());
+        }
+
+        self
+    }
+
+    pub fn apply_property(&mut self, line: &str) {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+
+        if parts.len() == 0 || parts[0].starts_with('#') {
+            return;
+        }
+
+        match parts[0] {
+            "name" => self.name = parts[1..].join(" "),
+            "tag" => self.tags.push(string_arg(&parts, 1)),
+            "layer" => self.layer = parts.get(1).and_then(|l| l.parse().ok()).unwrap_or(0),
+            "model" => self.model = Some((string_arg(&parts, 1), string_arg(&parts, 2))),
+            "shader" => self.shader = (string_arg(&parts, 1), string_arg(&parts, 2)),
+            "diffuse" => self.diffuse = string_arg(&parts, 1),
+            "normal" => self.normal = string_arg(&parts, 1),
+            "color" => {
+                self.color = [
+                    float_arg(&parts, 1),
+                    float_arg(&parts, 2),
+                    float_arg(&parts, 3),
+                ]
+            }
+            "tiling" => self.tiling = [float_arg(&parts, 1), float_arg(&parts, 2)],
+            "opacity" => self.opacity = float_arg(&parts, 1),
+            "overlay" => self.overlay = string_arg(&parts, 1) == "true",
+            "physics" => self.physics = parse_physics(&parts),
+            "child" => self.children.push(PrefabChild {
+                path: string_arg(&parts, 1),
+                offset: Vector3::new(
+                    float_arg(&parts, 2),
+                    float_arg(&parts, 3),
+                    float_arg(&parts, 4),
+                ),
+            }),
+            _ => println!(
+                "{}",
+                format!("Unknown prefab property '{}' in {}", parts[0], self.path).red()
+            ),
+        }
+    }
+}
+
+fn string_arg(parts: &[&str], idx: usize) -> String {
+    match parts.get(idx) {
+        Some(part) => part.to_string(),
+        None => "".to_string(),
+    }
+}
+
+fn float_arg(parts: &[&str], idx: usize) -> f32 {
+    parts.get(idx).and_then(|p| p.parse().ok()).unwrap_or(0.0)
+}
+
+fn parse_physics(parts: &[&str]) -> PrefabPhysics {
+    let shape = match parts.get(2) {
+        Some(&"box") => PhysicsShape::BoxShape,
+        _ => PhysicsShape::SphereShape,
+    };
+
+    match parts.get(1) {
+        Some(&"collider") => PrefabPhysics::Collider(shape),
+        Some(&"rigidbody") => PrefabPhysics::RigidBody(shape),
+        _ => PrefabPhysics::None,
+    }
+}
+
+// caches parsed prefabs and the gpu resources they reference, keyed by asset path
+pub struct PrefabContext {
+    prefabs: HashMap<String, Prefab>,
+    models: HashMap<String, Resource>,
+    textures: HashMap<String, Resource>,
+    shaders: HashMap<(String, String), Resource>,
+
+    // after a reload the prefab files are read from disk, so edits show up without rebuilding
+    // the asset pack
+    read_from_disk: bool,
+    // the prefabs being spawned, used to catch prefabs that include themselves
+    spawning: Vec<String>,
+}
+
+#[allow(dead_code)]
+impl PrefabContext {
+    pub fn new() -> PrefabContext {
+        PrefabContext {
+            prefabs: HashMap::new(),
+            models: HashMap::new(),
+            textures: HashMap::new(),
+            shaders: HashMap::new(),
+
+            read_from_disk: false,
+            spawning: Vec::new(),
+        }
+    }
+
+    pub fn load(&mut self, path: &str) -> Prefab {
+        if !self.prefabs.contains_key(path) {
+            let source = if self.read_from_disk {
+                assets::get_asset_from_disk(path)
+            } else {
+                assets::get_asset(path)
+            };
+            let prefab = Prefab::parse(path, &String::from_utf8_lossy(&source));
+            self.prefabs.insert(path.to_string(), prefab);
+        }
+
+        self.prefabs.get(path).unwrap().clone()
+    }
+
+    // forget the parsed prefabs so the next load reads the current files under res
+    pub fn reload(&mut self) {
+        self.prefabs.clear();
+        self.read_from_disk = true;
+    }
+
+    pub fn instantiate(
+        &mut self,
+        display: Option<&glium::Display>,
+        res: &mut ResourceContext,
+        physics: &mut PhysicsContext,
+        entities: &mut EntityContext,
+        prefab: &Prefab,
+        transform: Transform,
+    ) -> Entity {
+        let (entity, children) = self.spawn(display, res, physics, entities, prefab, transform);
+
+        entities.prefab_instances.insert(
+            entity,
+            PrefabInstance {
+                path: prefab.path.clone(),
+                overrides: prefab.overrides.clone(),
+                children: children,
+            },
+        );
+
+        entity
+    }
+
+    // rebuild every prefab instance in the scene from the current prefab files,
+    // keeping the transform and overrides of each instance.
+    // every instance becomes a new entity, so handles to the old roots and their children go
+    // stale. the returned map takes each old root to its new one, children are not mapped
+    pub fn reload_instances(
+        &mut self,
+        display: Option<&glium::Display>,
+        res: &mut ResourceContext,
+        physics: &mut PhysicsContext,
+        entities: &mut EntityContext,
+    ) -> HashMap<Entity, Entity> {
+        self.reload();
+
+        let mut instances = Vec::new();
+        for (entity, instance, transform) in join(&entities.prefab_instances, &entities.transforms)
+        {
+            instances.push((entity, instance.clone(), *transform));
+        }
+
+        let mut remap = HashMap::new();
+
+        for (entity, instance, transform) in instances {
+            for child in &instance.children {
+                entities.destroy_immediate(*child, physics);
+            }
+            entities.destroy_immediate(entity, physics);
+
+            let overrides: Vec<&str> = instance.overrides.iter().map(|o| o.as_str()).collect();
+            let prefab = self.load(&instance.path).with_overrides(&overrides);
+
+            let new_entity = self.instantiate(display, res, physics, entities, &prefab, transform);
+            remap.insert(entity, new_entity);
+        }
+
+        remap
+    }
+
+    // spawns the prefab and its children, returns the root and every child entity
+    fn spawn(
+        &mut self,
+        display: Option<&glium::Display>,
+        res: &mut ResourceContext,
+        physics: &mut PhysicsContext,
+        entities: &mut EntityContext,
+        prefab: &Prefab,
+        transform: Transform,
+    ) -> (Entity, Vec<Entity>) {
+        // grouping prefabs with only children get an entity with just a transform
+        let entity = match prefab.model {
+            Some(ref model) => {
+                self.spawn_object(display, res, physics, entities, prefab, transform, model)
+            }
+            None => spawn_empty(entities, prefab, transform),
+        };
+
+        self.spawning.push(prefab.path.clone());
+
+        let mut children = Vec::new();
+        for child in &prefab.children {
+            if self.spawning.contains(&child.path) {
+                println!(
+                    "{}",
+                    format!(
+                        "Prefab {} includes {}, which is already being spawned",
+                        prefab.path, child.path
+                    )
+                    .red()
+                );
+                continue;
+            }
+
+            let child_prefab = self.load(&child.path);
+            let child_transform = Transform::new(
+                transform.position + transform.rotation.rotate(child.offset),
+                transform.rotation,
+            );
+
+            let (child_entity, grand_children) = self.spawn(
+                display,
+                res,
+                physics,
+                entities,
+                &child_prefab,
+                child_transform,
+            );
+
+            children.push(child_entity);
+            children.extend(grand_children);
+        }
+
+        self.spawning.pop();
+
+        (entity, children)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn spawn_object(
+        &mut self,
+        display: Option<&glium::Display>,
+        res: &mut ResourceContext,
+        physics: &mut PhysicsContext,
+        entities: &mut EntityContext,
+        prefab: &Prefab,
+        transform: Transform,
+        model: &(String, String),
+    ) -> Entity {
+        let model = self.load_model(display, res, &model.0, &model.1);
+        let shader_prog = self.load_shader(display, res, &prefab.shader.0, &prefab.shader.1);
+        let diffuse_tex = self.load_texture(display, res, &prefab.diffuse);
+        let normal_tex = self.load_texture(display, res, &prefab.normal);
+
+        let material = Material::new(
+            shader_prog,
+            diffuse_tex,
+            normal_tex,
+            prefab.color,
+            prefab.tiling,
+        )
+        .with_opacity(prefab.opacity);
+
+        let mut gameobject = GameObject::new(
+            res,
+            prefab.name.clone(),
+            transform.position,
+            transform.rotation,
+            model,
+            material,
+        )
+        .with_layer(prefab.layer);
+
+        for tag in &prefab.tags {
+            gameobject = gameobject.with_tag(tag);
+        }
+        gameobject.overlay = prefab.overlay;
+
+        let gameobject = match prefab.physics {
+            PrefabPhysics::None => gameobject,
+            PrefabPhysics::Collider(shape) => gameobject.add_collider(physics, shape),
+            PrefabPhysics::RigidBody(shape) => gameobject.add_rigidbody(physics, shape),
+        };
+
+        gameobject.spawn(entities)
+    }
+
+    fn load_model(
+        &mut self,
+        display: Option<&glium::Display>,
+        res: &mut ResourceContext,
+        obj: &str,
+        mtl: &str,
+    ) -> Resource {
+        if let Some(model) = self.models.get(obj) {
+            return *model;
+        }
+
+        let model = res.alloc_model(Model::load(
+            display,
+            &assets::get_asset(obj),
+            &assets::get_asset(mtl),
+        ));
+        self.models.insert(obj.to_string(), model);
+
+        model
+    }
+
+    fn load_texture(
+        &mut self,
+        display: Option<&glium::Display>,
+        res: &mut ResourceContext,
+        path: &str,
+    ) -> Resource {
+        if let Some(tex) = self.textures.get(path) {
+            return *tex;
+        }
+
+        let tex = res.alloc_tex(texture::load(display, &assets::get_asset(path)));
+        self.textures.insert(path.to_string(), tex);
+
+        tex
+    }
+
+    fn load_shader(
+        &mut self,
+        display: Option<&glium::Display>,
+        res: &mut ResourceContext,
+        vs: &str,
+        fs: &str,
+    ) -> Resource {
+        let key = (vs.to_string(), fs.to_string());
+
+        if let Some(program) = self.shaders.get(&key) {
+            return *program;
+        }
+
+        let program = res.alloc_shader(shader::load(
+            display,
+            &assets::get_asset(vs),
+            &assets::get_asset(fs),
+        ));
+        self.shaders.insert(key, program);
+
+        program
+    }
+}
+
+fn spawn_empty(entities: &mut EntityContext, prefab: &Prefab, transform: Transform) -> Entity {
+    if let PrefabPhysics::Collider(_) | PrefabPhysics::RigidBody(_) = prefab.physics {
+        println!(
+            "{}",
+            format!(
+                "Prefab {} has physics but no model to shape it",
+                prefab.path
+            )
+            .red()
+        );
+    }
+
+    let entity = entities.create_entity();
+
+    entities.set_name(entity, prefab.name.clone());
+    entities.set_layer(entity, prefab.layer);
+    for tag in &prefab.tags {
+        entities.add_tag(entity, tag.clone());
+    }
+    entities.transforms.insert(entity, transform);
+
+    entity
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quaternion::Quaternion;
+    use std::env;
+    use std::fs;
+
+    fn at(x: f32, y: f32, z: f32) -> Transform {
+        Transform::new(Vector3::new(x, y, z), Quaternion::new(0.0, 0.0, 0.0, 1.0))
+    }
+
+    // prefabs without a model only need a transform, so nothing has to be loaded from the pack
+    fn context_with(prefabs: &[(&str, &str)]) -> PrefabContext {
+        let mut context = PrefabContext::new();
+
+        for &(path, source) in prefabs {
+            context
+                .prefabs
+                .insert(path.to_string(), Prefab::parse(path, source));
+        }
+
+        context
+    }
+
+    fn instantiate(
+        context: &mut PrefabContext,
+        entities: &mut EntityContext,
+        physics: &mut PhysicsContext,
+        path: &str,
+        transform: Transform,
+    ) -> Entity {
+        let prefab = context.load(path);
+
+        context.instantiate(
+            None,
+            &mut ResourceContext::new(),
+            physics,
+            entities,
+            &prefab,
+            transform,
+        )
+    }
+
+    #[test]
+    fn parse_reads_every_property() {
+        let prefab = Prefab::parse(
+            "test.prefab",
+            "# a comment\n\
+             name big red ball\n\
+             tag ball\n\
+             tag red\n\
+             layer 3\n\
+             model ./res/ball.obj ./res/ball.mtl\n\
+             shader a.vs a.fs\n\
+             diffuse d.jpg\n\
+             normal n.jpg\n\
+             color 1 0.5 0\n\
+             tiling 2 4\n\
+             opacity 0.25\n\
+             overlay true\n\
+             physics collider box\n\
+             child other.prefab 0 1 -2\n\
+             bogus 1\n",
+        );
+
+        assert_eq!(prefab.name, "big red ball");
+        assert_eq!(prefab.tags, vec!["ball", "red"]);
+        assert_eq!(prefab.layer, 3);
+        assert_eq!(
+            prefab.model,
+            Some(("./res/ball.obj".to_string(), "./res/ball.mtl".to_string()))
+        );
+        assert_eq!(prefab.shader, ("a.vs".to_string(), "a.fs".to_string()));
+        assert_eq!(prefab.diffuse, "d.jpg");
+        assert_eq!(prefab.normal, "n.jpg");
+        assert_eq!(prefab.color, [1.0, 0.5, 0.0]);
+        assert_eq!(prefab.tiling, [2.0, 4.0]);
+        assert_eq!(prefab.opacity, 0.25);
+        assert!(prefab.overlay);
+        match prefab.physics {
+            PrefabPhysics::Collider(PhysicsShape::BoxShape) => (),
+            _ => panic!("expected a box collider"),
+        }
+        assert_eq!(prefab.children.len(), 1);
+        assert_eq!(prefab.children[0].path, "other.prefab");
+        assert_eq!(prefab.children[0].offset.raw(), [0.0, 1.0, -2.0]);
+        assert!(prefab.overrides.is_empty());
+    }
+
+    #[test]
+    fn parse_falls_back_to_defaults() {
+        let prefab = Prefab::parse("empty.prefab", "");
+
+        assert_eq!(prefab.name, "prefab");
+        assert_eq!(prefab.layer, DEFAULT_LAYER);
+        assert!(prefab.model.is_none());
+        assert_eq!(prefab.opacity, 1.0);
+        match prefab.physics {
+            PrefabPhysics::None => (),
+            _ => panic!("expected no physics"),
+        }
+    }
+
+    #[test]
+    fn overrides_apply_on_top_and_are_remembered() {
+        let prefab = Prefab::parse("test.prefab", "name ball\ntag ball\ncolor 1 1 1");
+        let overridden =
+            prefab
+                .clone()
+                .with_overrides(&["color 1 0 0", "tag red", "name red ball"]);
+
+        assert_eq!(overridden.name, "red ball");
+        assert_eq!(overridden.tags, vec!["ball", "red"]);
+        assert_eq!(overridden.color, [1.0, 0.0, 0.0]);
+        assert_eq!(
+            overridden.overrides,
+            vec!["color 1 0 0", "tag red", "name red ball"]
+        );
+
+        // the cached prefab is untouched
+        assert_eq!(prefab.color, [1.0, 1.0, 1.0]);
+
+        let mut applied = prefab.clone();
+        applied.apply_property("layer 7");
+        applied.apply_property("   ");
+        applied.apply_property("# layer 9");
+        assert_eq!(applied.layer, 7);
+        assert!(applied.overrides.is_empty());
+    }
+
+    #[test]
+    fn instantiate_records_children_and_destroys_them_with_the_root() {
+        let mut context = context_with(&[
+            (
+                "root.prefab",
+                "name root\nchild a.prefab 1 0 0\nchild b.prefab 0 2 0",
+            ),
+            ("a.prefab", "name a\nchild c.prefab 0 0 3"),
+            ("b.prefab", "name b"),
+            ("c.prefab", "name c"),
+        ]);
+        let mut entities = EntityContext::new();
+        let mut physics = PhysicsContext::new();
+
+        let root = instantiate(
+            &mut context,
+            &mut entities,
+            &mut physics,
+            "root.prefab",
+            at(10.0, 0.0, 0.0),
+        );
+
+        let children = entities
+            .prefab_instances
+            .get(root)
+            .unwrap()
+            .children
+            .clone();
+        let names: Vec<&str> = children
+            .iter()
+            .map(|child| entities.get_name(*child).unwrap().as_str())
+            .collect();
+        assert_eq!(names, vec!["a", "c", "b"]);
+
+        // offsets add up down the tree
+        let c = entities.find_by_name("c").unwrap();
+        assert_eq!(
+            entities.transforms.get(c).unwrap().position.raw(),
+            [11.0, 0.0, 3.0]
+        );
+
+        entities.destroy_immediate(root, &mut physics);
+
+        assert!(!entities.is_alive(root));
+        for child in children {
+            assert!(!entities.is_alive(child));
+        }
+        assert!(entities.entities().is_empty());
+    }
+
+    #[test]
+    fn spawn_rejects_prefabs_that_include_themselves() {
+        let mut context = context_with(&[
+            ("self.prefab", "name self\nchild self.prefab 0 1 0"),
+            ("x.prefab", "name x\nchild y.prefab 0 1 0"),
+            (
+                "y.prefab",
+                "name y\nchild x.prefab 0 1 0\nchild z.prefab 0 1 0",
+            ),
+            ("z.prefab", "name z"),
+        ]);
+        let mut entities = EntityContext::new();
+        let mut physics = PhysicsContext::new();
+
+        let root = instantiate(
+            &mut context,
+            &mut entities,
+            &mut physics,
+            "self.prefab",
+            at(0.0, 0.0, 0.0),
+        );
+        assert!(entities
+            .prefab_instances
+            .get(root)
+            .unwrap()
+            .children
+            .is_empty());
+
+        // the cycle is cut where it closes, the rest of the tree still spawns
+        let root = instantiate(
+            &mut context,
+            &mut entities,
+            &mut physics,
+            "x.prefab",
+            at(0.0, 0.0, 0.0),
+        );
+        let children = &entities.prefab_instances.get(root).unwrap().children;
+        assert_eq!(children.len(), 2);
+        assert_eq!(entities.find_all_by_name("x"), vec![root]);
+        assert_eq!(entities.find_all_by_name("y").len(), 1);
+        assert_eq!(entities.find_all_by_name("z").len(), 1);
+
+        // spawning is done, the same prefab can be used again
+        assert!(context.spawning.is_empty());
+    }
+
+    #[test]
+    fn reload_instances_picks_up_changes_and_keeps_the_instance() {
+        let path = env::temp_dir().join(format!("reload_{}.prefab", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        fs::write(&path, "name before\nlayer 1\nchild child.prefab 0 1 0").unwrap();
+
+        let mut context = context_with(&[("child.prefab", "name child")]);
+        // read the prefab under test from disk like after the first reload
+        context.read_from_disk = true;
+        let mut entities = EntityContext::new();
+        let mut physics = PhysicsContext::new();
+        let mut res = ResourceContext::new();
+
+        let prefab = context.load(&path).with_overrides(&["tag picked"]);
+        let transform = Transform::new(
+            Vector3::new(1.0, 2.0, 3.0),
+            Quaternion::new_axis_angle(Vector3::new(0.0, 1.0, 0.0), 0.5),
+        );
+        let old = context.instantiate(
+            None,
+            &mut res,
+            &mut physics,
+            &mut entities,
+            &prefab,
+            transform,
+        );
+        let old_child = entities.prefab_instances.get(old).unwrap().children[0];
+
+        fs::write(&path, "name after\nlayer 4").unwrap();
+        let remap = context.reload_instances(None, &mut res, &mut physics, &mut entities);
+        fs::remove_file(&path).unwrap();
+
+        let new = remap[&old];
+        assert_eq!(remap.len(), 1);
+        assert!(!entities.is_alive(old));
+        assert!(!entities.is_alive(old_child));
+        assert!(entities.is_alive(new));
+
+        assert_eq!(entities.get_name(new).map(|n| n.as_str()), Some("after"));
+        assert_eq!(entities.get_layer(new), 4);
+        assert!(entities.has_tag(new, "picked"));
+        assert_eq!(entities.find_by_name("before"), None);
+        assert_eq!(entities.find_by_name("child"), None);
+
+        let new_transform = entities.transforms.get(new).unwrap();
+        assert_eq!(new_transform.position.raw(), transform.position.raw());
+        assert_eq!(new_transform.rotation, transform.rotation);
+
+        let instance = entities.prefab_instances.get(new).unwrap();
+        assert_eq!(instance.overrides, vec!["tag picked"]);
+        assert!(instance.children.is_empty());
+    }
+}
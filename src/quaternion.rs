@@ -166,6 +166,30 @@ impl Quaternion {
         self.x * r.x + self.y * r.y + self.z * r.z + self.w * r.w
     }
 
+    // spherical interpolation along the shortest path between two rotations
+    pub fn slerp(&self, r: Quaternion, amount: f32) -> Quaternion {
+        let mut cos_angle = self.dot(r);
+        let mut end = r;
+
+        if cos_angle < 0.0 {
+            cos_angle = -cos_angle;
+            end = r.mul_f32(-1.0);
+        }
+
+        // the rotations are almost the same, a normalized lerp is accurate enough here
+        if cos_angle > 0.9995 {
+            return self.add(end.sub(*self).mul_f32(amount)).normalized();
+        }
+
+        let angle = cos_angle.acos();
+        let sin_angle = angle.sin();
+
+        let start_weight = ((1.0 - amount) * angle).sin() / sin_angle;
+        let end_weight = (amount * angle).sin() / sin_angle;
+
+        self.mul_f32(start_weight).add(end.mul_f32(end_weight))
+    }
+
     pub fn rotate(&self, angle: Vector3) -> Vector3 {
         let con = self.conjugate();
         let w = self.mul_vec3(angle).mul_quat(con);
@@ -175,7 +199,23 @@ impl Quaternion {
     pub fn forward(&self) -> Vector3 {
         self.rotate(Vector3::new(0.0, 0.0, 1.0))
     }
+
     pub fn right(&self) -> Vector3 {
         self.rotate(Vector3::new(1.0, 0.0, 0.0))
     }
+
+    pub fn up(&self) -> Vector3 {
+        self.rotate(Vector3::new(0.0, 1.0, 0.0))
+    }
+
+    // the rotation whose forward points along the direction, keeping the right vector level
+    pub fn look_rotation(direction: Vector3) -> Quaternion {
+        let direction = direction.normalized();
+        let yaw = direction.x.atan2(direction.z);
+        let pitch = (-direction.y).max(-1.0).min(1.0).asin();
+
+        Quaternion::new_axis_angle(Vector3::new(0.0, 1.0, 0.0), yaw).mul_quat(
+            Quaternion::new_axis_angle(Vector3::new(1.0, 0.0, 0.0), pitch),
+        )
+    }
 }
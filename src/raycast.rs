@@ -0,0 +1,460 @@
+// cpu raycasts against the scene, unlike the gpu picking buffer the result is available right
+// away so gameplay code can use it for hover and line of sight checks. objects are tested
+// against their bounding box first and then against the triangles of their model. objects are
+// found through the scene tree, so they are hit once spawned or after scene_tree_system ran
+use camera::Ray;
+use ecs::*;
+use model::ModelVertex;
+use physics_engine::PhysicsContext;
+use resource_manager::ResourceContext;
+use std::collections::{HashMap, HashSet};
+use vector::Vector3;
+
+#[derive(Copy, Clone)]
+pub struct RaycastHit {
+    pub entity: Entity,
+    pub distance: f32,
+    pub point: Vector3,
+    pub normal: Vector3,
+}
+
+pub struct RaycastOptions {
+    pub max_distance: f32,
+    // only objects in these layers are hit, every layer when empty
+    pub layers: Vec<u32>,
+    // stop at the bounding box instead of testing the triangles
+    pub bounding_box_only: bool,
+    pub include_overlay: bool,
+}
+
+#[allow(dead_code)]
+impl RaycastOptions {
+    pub fn new() -> RaycastOptions {
+        RaycastOptions {
+            max_distance: 1024.0,
+            layers: Vec::new(),
+            bounding_box_only: false,
+            include_overlay: false,
+        }
+    }
+
+    pub fn with_max_distance(mut self, max_distance: f32) -> Self {
+        self.max_distance = max_distance;
+        self
+    }
+
+    pub fn with_layer(mut self, layer: u32) -> Self {
+        self.layers.push(layer);
+        self
+    }
+
+    pub fn bounding_box_only(mut self) -> Self {
+        self.bounding_box_only = true;
+        self
+    }
+
+    pub fn include_overlay(mut self) -> Self {
+        self.include_overlay = true;
+        self
+    }
+
+    fn accepts(&self, entities: &EntityContext, entity: Entity) -> bool {
+        self.layers.is_empty() || self.layers.contains(&entities.get_layer(entity))
+    }
+}
+
+// the closest hit, physics colliders are tested as well when a physics context is given
+pub fn raycast(
+    entities: &EntityContext,
+    resources: &ResourceContext,
+    physics: Option<&PhysicsContext>,
+    ray: &Ray,
+    options: &RaycastOptions,
+) -> Option<RaycastHit> {
+    raycast_all(entities, resources, physics, ray, options)
+        .into_iter()
+        .next()
+}
+
+// every hit sorted by distance, an entity shows up at most once. the direction doesn't have to
+// be normalized, the distances are in world units either way
+pub fn raycast_all(
+    entities: &EntityContext,
+    resources: &ResourceContext,
+    physics: Option<&PhysicsContext>,
+    ray: &Ray,
+    options: &RaycastOptions,
+) -> Vec<RaycastHit> {
+    let ray = match normalized_ray(ray) {
+        Some(ray) => ray,
+        None => return Vec::new(),
+    };
+    let ray = &ray;
+
+    let mut hits: Vec<RaycastHit> = Vec::new();
+
+    // only the objects whose box in the scene tree the ray passes through are tested
+    for (entity, _) in entities.scene_tree.query_ray(ray, options.max_distance) {
+        let (transform, mesh_renderer) = match (
+            entities.transforms.get(entity),
+            entities.mesh_renderers.get(entity),
+        ) {
+            (Some(transform), Some(mesh_renderer)) => (transform, mesh_renderer),
+            _ => continue,
+        };
+
+        if mesh_renderer.overlay && !options.include_overlay {
+            continue;
+        }
+        if !options.accepts(entities, entity) {
+            continue;
+        }
+
+        // test in the object's local space where the box is axis aligned
+        let inverse = transform.rotation.conjugate();
+        let local_ray = Ray {
+            origin: inverse.rotate(ray.origin - transform.position),
+            direction: inverse.rotate(ray.direction),
+        };
+
+        let half_size = Vector3::new(
+            mesh_renderer.bounding_box[0] / 2.0,
+            mesh_renderer.bounding_box[1] / 2.0,
+            mesh_renderer.bounding_box[2] / 2.0,
+        );
+        // the box test wants the box centred on the origin
+        let box_ray = Ray {
+            origin: local_ray.origin
+                - Vector3::new(
+                    mesh_renderer.bounding_center[0],
+                    mesh_renderer.bounding_center[1],
+                    mesh_renderer.bounding_center[2],
+                ),
+            direction: local_ray.direction,
+        };
+
+        let box_hit = match ray_box(&box_ray, half_size, options.max_distance) {
+            Some(hit) => hit,
+            None => continue,
+        };
+
+        let local_hit = if options.bounding_box_only {
+            Some(box_hit)
+        } else {
+            let model = resources.get_model_ref(mesh_renderer.model);
+            ray_mesh(
+                &local_ray,
+                &model.vertices,
+                &model.indices,
+                options.max_distance,
+            )
+        };
+
+        if let Some((distance, normal)) = local_hit {
+            hits.push(RaycastHit {
+                entity: entity,
+                distance: distance,
+                point: ray.point_at(distance),
+                normal: transform.rotation.rotate(normal),
+            });
+        }
+    }
+
+    if let Some(physics) = physics {
+        let body_entities: HashMap<_, Entity> = entities
+            .rigid_bodies
+            .iter()
+            .map(|(entity, body)| (body.handle, entity))
+            .collect();
+        let collider_entities: HashMap<_, Entity> = entities
+            .colliders
+            .iter()
+            .map(|(entity, collider)| (collider.handle, entity))
+            .collect();
+
+        for physics_hit in physics.raycast(ray, options.max_distance) {
+            // colliders that don't belong to an entity are skipped
+            let entity = match body_entities
+                .get(&physics_hit.body)
+                .or_else(|| collider_entities.get(&physics_hit.collider))
+            {
+                Some(&entity) => entity,
+                None => continue,
+            };
+            if !options.accepts(entities, entity) {
+                continue;
+            }
+
+            hits.push(RaycastHit {
+                entity: entity,
+                distance: physics_hit.distance,
+                point: ray.point_at(physics_hit.distance),
+                normal: physics_hit.normal,
+            });
+        }
+    }
+
+    hits.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+
+    // keep the closest hit of each entity, the mesh and its collider can both be hit
+    let mut seen: HashSet<Entity> = HashSet::new();
+    hits.retain(|hit| seen.insert(hit.entity));
+
+    hits
+}
+
+// the ray with a unit direction, None when it has no direction or isn't finite
+fn normalized_ray(ray: &Ray) -> Option<Ray> {
+    let values = [ray.origin.raw(), ray.direction.raw()];
+    if !values.iter().flat_map(|v| v.iter()).all(|v| v.is_finite()) {
+        return None;
+    }
+
+    let length = ray.direction.length();
+    if length < 1e-8 || !length.is_finite() {
+        return None;
+    }
+
+    Some(Ray {
+        origin: ray.origin,
+        direction: ray.direction * (1.0 / length),
+    })
+}
+
+// slab test against a box centered on the origin, returns the entry distance and the normal of
+// the face that was hit, a ray starting inside the box hits it at distance 0
+fn ray_box(ray: &Ray, half_size: Vector3, max_distance: f32) -> Option<(f32, Vector3)> {
+    let origin = [ray.origin.x, ray.origin.y, ray.origin.z];
+    let direction = [ray.direction.x, ray.direction.y, ray.direction.z];
+    let half_size = [half_size.x, half_size.y, half_size.z];
+
+    let mut near = 0.0;
+    let mut far = max_distance;
+    let mut normal = Vector3::new(0.0, 0.0, 0.0);
+
+    for axis in 0..3 {
+        if direction[axis].abs() < 1e-8 {
+            // parallel to the slab, it has to start between the planes
+            if origin[axis].abs() > half_size[axis] {
+                return None;
+            }
+            continue;
+        }
+
+        let t1 = (-half_size[axis] - origin[axis]) / direction[axis];
+        let t2 = (half_size[axis] - origin[axis]) / direction[axis];
+        let (t_min, t_max) = if t1 < t2 { (t1, t2) } else { (t2, t1) };
+
+        if t_min > near {
+            near = t_min;
+
+            let mut axis_normal = [0.0, 0.0, 0.0];
+            axis_normal[axis] = -direction[axis].signum();
+            normal = Vector3::new(axis_normal[0], axis_normal[1], axis_normal[2]);
+        }
+        far = far.min(t_max);
+
+        if near > far {
+            return None;
+        }
+    }
+
+    Some((near, normal))
+}
+
+// the closest triangle hit, triangles are hit from both sides and the normal faces the ray
+fn ray_mesh(
+    ray: &Ray,
+    vertices: &[ModelVertex],
+    indices: &[u16],
+    max_distance: f32,
+) -> Option<(f32, Vector3)> {
+    let mut closest: Option<(f32, Vector3)> = None;
+
+    for triangle in indices.chunks(3) {
+        if triangle.len() < 3 {
+            break;
+        }
+
+        let p0 = vertex_position(vertices, triangle[0]);
+        let p1 = vertex_position(vertices, triangle[1]);
+        let p2 = vertex_position(vertices, triangle[2]);
+
+        let limit = match closest {
+            Some((distance, _)) => distance,
+            None => max_distance,
+        };
+
+        if let Some(distance) = ray_triangle(ray, p0, p1, p2, limit) {
+            let mut normal = (p1 - p0).cross(p2 - p0).normalized();
+            if normal.dot(ray.direction) > 0.0 {
+                normal = normal * -1.0;
+            }
+
+            closest = Some((distance, normal));
+        }
+    }
+
+    closest
+}
+
+fn vertex_position(vertices: &[ModelVertex], index: u16) -> Vector3 {
+    let position = vertices[index as usize].position;
+    Vector3::new(position[0], position[1], position[2])
+}
+
+// moller-trumbore
+fn ray_triangle(
+    ray: &Ray,
+    p0: Vector3,
+    p1: Vector3,
+    p2: Vector3,
+    max_distance: f32,
+) -> Option<f32> {
+    let edge1 = p1 - p0;
+    let edge2 = p2 - p0;
+
+    let h = ray.direction.cross(edge2);
+    let det = edge1.dot(h);
+    if det.abs() < 1e-8 {
+        return None;
+    }
+
+    let inv_det = 1.0 / det;
+    let s = ray.origin - p0;
+    let u = s.dot(h) * inv_det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let q = s.cross(edge1);
+    let v = ray.direction.dot(q) * inv_det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let distance = edge2.dot(q) * inv_det;
+    if distance < 0.0 || distance > max_distance {
+        return None;
+    }
+
+    Some(distance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ray(origin: (f32, f32, f32), direction: (f32, f32, f32)) -> Ray {
+        Ray {
+            origin: Vector3::new(origin.0, origin.1, origin.2),
+            direction: Vector3::new(direction.0, direction.1, direction.2),
+        }
+    }
+
+    fn unit_half() -> Vector3 {
+        Vector3::new(1.0, 1.0, 1.0)
+    }
+
+    fn triangle() -> (Vector3, Vector3, Vector3) {
+        (
+            Vector3::new(-1.0, -1.0, 0.0),
+            Vector3::new(1.0, -1.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+        )
+    }
+
+    #[test]
+    fn slab_test_hits_the_facing_side() {
+        let (distance, normal) =
+            ray_box(&ray((0.0, 0.0, -5.0), (0.0, 0.0, 1.0)), unit_half(), 100.0).unwrap();
+
+        assert_eq!(distance, 4.0);
+        assert_eq!(normal.raw(), [0.0, 0.0, -1.0]);
+
+        let (distance, normal) =
+            ray_box(&ray((5.0, 0.5, 0.0), (-1.0, 0.0, 0.0)), unit_half(), 100.0).unwrap();
+
+        assert_eq!(distance, 4.0);
+        assert_eq!(normal.raw(), [1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn slab_test_misses() {
+        // beside, pointing away and too short
+        assert!(ray_box(&ray((0.0, 3.0, -5.0), (0.0, 0.0, 1.0)), unit_half(), 100.0).is_none());
+        assert!(ray_box(&ray((0.0, 0.0, -5.0), (0.0, 0.0, -1.0)), unit_half(), 100.0).is_none());
+        assert!(ray_box(&ray((0.0, 0.0, -5.0), (0.0, 0.0, 1.0)), unit_half(), 3.0).is_none());
+
+        // diagonal past a corner
+        let diagonal = Vector3::new(1.0, 1.0, 0.0).normalized();
+        let past = Ray {
+            origin: Vector3::new(-3.0, 0.0, 0.0),
+            direction: diagonal,
+        };
+        assert!(ray_box(&past, unit_half(), 100.0).is_none());
+    }
+
+    #[test]
+    fn slab_test_from_inside_hits_at_zero() {
+        let (distance, _) =
+            ray_box(&ray((0.2, 0.0, 0.0), (0.0, 1.0, 0.0)), unit_half(), 100.0).unwrap();
+
+        assert_eq!(distance, 0.0);
+    }
+
+    #[test]
+    fn triangle_hit() {
+        let (p0, p1, p2) = triangle();
+
+        let distance = ray_triangle(&ray((0.0, 0.0, -2.0), (0.0, 0.0, 1.0)), p0, p1, p2, 100.0);
+        assert_eq!(distance, Some(2.0));
+
+        // from behind as well
+        let distance = ray_triangle(&ray((0.0, 0.0, 3.0), (0.0, 0.0, -1.0)), p0, p1, p2, 100.0);
+        assert_eq!(distance, Some(3.0));
+    }
+
+    #[test]
+    fn triangle_miss() {
+        let (p0, p1, p2) = triangle();
+
+        // outside the edges, behind the origin and past the max distance
+        assert!(ray_triangle(&ray((0.9, 0.9, -2.0), (0.0, 0.0, 1.0)), p0, p1, p2, 100.0).is_none());
+        assert!(
+            ray_triangle(&ray((0.0, 0.0, -2.0), (0.0, 0.0, -1.0)), p0, p1, p2, 100.0).is_none()
+        );
+        assert!(ray_triangle(&ray((0.0, 0.0, -2.0), (0.0, 0.0, 1.0)), p0, p1, p2, 1.0).is_none());
+    }
+
+    #[test]
+    fn triangle_parallel_ray_misses() {
+        let (p0, p1, p2) = triangle();
+
+        // in the plane of the triangle and just above it
+        assert!(ray_triangle(&ray((-5.0, 0.0, 0.0), (1.0, 0.0, 0.0)), p0, p1, p2, 100.0).is_none());
+        assert!(ray_triangle(&ray((-5.0, 0.0, 0.1), (1.0, 0.0, 0.0)), p0, p1, p2, 100.0).is_none());
+    }
+
+    #[test]
+    fn bad_rays_are_rejected() {
+        assert!(normalized_ray(&ray((0.0, 0.0, 0.0), (0.0, 0.0, 0.0))).is_none());
+        assert!(normalized_ray(&ray((0.0, 0.0, 0.0), (std::f32::NAN, 0.0, 1.0))).is_none());
+        assert!(normalized_ray(&ray((std::f32::INFINITY, 0.0, 0.0), (0.0, 0.0, 1.0))).is_none());
+
+        let normalized = normalized_ray(&ray((1.0, 2.0, 3.0), (0.0, 0.0, 4.0))).unwrap();
+        assert_eq!(normalized.origin.raw(), [1.0, 2.0, 3.0]);
+        assert_eq!(normalized.direction.raw(), [0.0, 0.0, 1.0]);
+
+        let entities = EntityContext::new();
+        let resources = ResourceContext::new();
+        let hits = raycast_all(
+            &entities,
+            &resources,
+            None,
+            &ray((0.0, 0.0, 0.0), (std::f32::NAN, 0.0, 0.0)),
+            &RaycastOptions::new(),
+        );
+        assert!(hits.is_empty());
+    }
+}
@@ -0,0 +1,428 @@
+// input recordings store the input events of every frame together with the frame time, replaying
+// one through the runner reproduces the session as long as physics runs on its fixed timestep.
+// recordings are saved as text, a frame line followed by the events polled during that frame:
+//
+//   frame 0.016666668
+//   key W pressed
+//   mouse left released
+//   cursor 512 384
+//   motion 1.5 -2
+//   wheel 0 1
+//   char 97
+//   pad_button 0 South pressed
+//   pad_axis 0 LeftStickX 0.5
+extern crate glium;
+
+use colored::*;
+use gamepad::{GamepadAxis, GamepadButton, GamepadEvent};
+use glium::glutin;
+use input::{
+    key_from_name, mouse_button_from_name, mouse_button_name, Input, PIXELS_PER_SCROLL_LINE,
+};
+use std::fs;
+use std::io;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RecordedEvent {
+    Key(glutin::VirtualKeyCode, bool),
+    MouseButton(glutin::MouseButton, bool),
+    CursorMoved(f64, f64),
+    MouseMotion(f64, f64),
+    // in lines
+    MouseWheel(f32, f32),
+    Character(char),
+    Gamepad(GamepadEvent),
+}
+
+#[allow(dead_code)]
+impl RecordedEvent {
+    // only the events that drive Input, the camera and the ui are recorded
+    pub fn from_event(event: &glutin::Event) -> Option<RecordedEvent> {
+        match *event {
+            glutin::Event::WindowEvent { ref event, .. } => match *event {
+                glutin::WindowEvent::KeyboardInput { input, .. } => {
+                    let pressed = input.state == glutin::ElementState::Pressed;
+                    input
+                        .virtual_keycode
+                        .map(|key| RecordedEvent::Key(key, pressed))
+                }
+                glutin::WindowEvent::MouseInput { button, state, .. } => Some(
+                    RecordedEvent::MouseButton(button, state == glutin::ElementState::Pressed),
+                ),
+                glutin::WindowEvent::CursorMoved { position, .. } => {
+                    Some(RecordedEvent::CursorMoved(position.x, position.y))
+                }
+                glutin::WindowEvent::MouseWheel { delta, .. } => match delta {
+                    glutin::MouseScrollDelta::LineDelta(x, y) => {
+                        Some(RecordedEvent::MouseWheel(x, y))
+                    }
+                    glutin::MouseScrollDelta::PixelDelta(position) => {
+                        Some(RecordedEvent::MouseWheel(
+                            position.x as f32 / PIXELS_PER_SCROLL_LINE,
+                            position.y as f32 / PIXELS_PER_SCROLL_LINE,
+                        ))
+                    }
+                },
+                glutin::WindowEvent::ReceivedCharacter(character) => {
+                    Some(RecordedEvent::Character(character))
+                }
+                _ => None,
+            },
+            glutin::Event::DeviceEvent { ref event, .. } => match *event {
+                glutin::DeviceEvent::MouseMotion { delta } => {
+                    Some(RecordedEvent::MouseMotion(delta.0, delta.1))
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    // replay the event straight into input, the runner mirrors cursor and mouse buttons to the ui.
+    // nothing is rebuilt as a glutin event so replayed events never reach App::on_event
+    pub fn apply(&self, input: &mut Input) {
+        match *self {
+            RecordedEvent::Key(key, pressed) => input.set_key(key, pressed),
+            RecordedEvent::MouseButton(button, pressed) => input.set_mouse_button(button, pressed),
+            RecordedEvent::CursorMoved(x, y) => input.set_mouse_position(x as f32, y as f32),
+            RecordedEvent::MouseMotion(x, y) => input.add_mouse_motion(x as f32, y as f32),
+            RecordedEvent::MouseWheel(x, y) => input.add_scroll(x, y),
+            RecordedEvent::Character(character) => input.push_character(character),
+            RecordedEvent::Gamepad(ref event) => input.process_gamepad_event(event),
+        }
+    }
+
+    fn to_line(&self) -> String {
+        match *self {
+            RecordedEvent::Key(key, pressed) => {
+                format!("key {:?} {}", key, state_name(pressed))
+            }
+            RecordedEvent::MouseButton(button, pressed) => {
+                format!(
+                    "mouse {} {}",
+                    mouse_button_name(button),
+                    state_name(pressed)
+                )
+            }
+            RecordedEvent::CursorMoved(x, y) => format!("cursor {} {}", x, y),
+            RecordedEvent::MouseMotion(x, y) => format!("motion {} {}", x, y),
+            RecordedEvent::MouseWheel(x, y) => format!("wheel {} {}", x, y),
+            // stored as a code point so spaces survive the whitespace split
+            RecordedEvent::Character(character) => format!("char {}", character as u32),
+            RecordedEvent::Gamepad(event) => match event {
+                GamepadEvent::Connected(id) => format!("pad_connected {}", id),
+                GamepadEvent::Disconnected(id) => format!("pad_disconnected {}", id),
+                GamepadEvent::ButtonPressed(id, button) => {
+                    format!("pad_button {} {:?} pressed", id, button)
+                }
+                GamepadEvent::ButtonReleased(id, button) => {
+                    format!("pad_button {} {:?} released", id, button)
+                }
+                GamepadEvent::AxisChanged(id, axis, value) => {
+                    format!("pad_axis {} {:?} {}", id, axis, value)
+                }
+            },
+        }
+    }
+
+    fn parse(parts: &[&str]) -> Option<RecordedEvent> {
+        match parts[0] {
+            "key" => {
+                let key = key_from_name(parts.get(1)?)?;
+
+                Some(RecordedEvent::Key(key, parse_state(parts.get(2)?)?))
+            }
+            "mouse" => Some(RecordedEvent::MouseButton(
+                mouse_button_from_name(parts.get(1)?)?,
+                parse_state(parts.get(2)?)?,
+            )),
+            "cursor" => Some(RecordedEvent::CursorMoved(
+                parts.get(1)?.parse().ok()?,
+                parts.get(2)?.parse().ok()?,
+            )),
+            "motion" => Some(RecordedEvent::MouseMotion(
+                parts.get(1)?.parse().ok()?,
+                parts.get(2)?.parse().ok()?,
+            )),
+            "wheel" => Some(RecordedEvent::MouseWheel(
+                parts.get(1)?.parse().ok()?,
+                parts.get(2)?.parse().ok()?,
+            )),
+            "char" => Some(RecordedEvent::Character(std::char::from_u32(
+                parts.get(1)?.parse().ok()?,
+            )?)),
+            "pad_connected" => Some(RecordedEvent::Gamepad(GamepadEvent::Connected(
+                parts.get(1)?.parse().ok()?,
+            ))),
+            "pad_disconnected" => Some(RecordedEvent::Gamepad(GamepadEvent::Disconnected(
+                parts.get(1)?.parse().ok()?,
+            ))),
+            "pad_button" => {
+                let id = parts.get(1)?.parse().ok()?;
+                let button = GamepadButton::from_name(parts.get(2)?)?;
+
+                let event = if parse_state(parts.get(3)?)? {
+                    GamepadEvent::ButtonPressed(id, button)
+                } else {
+                    GamepadEvent::ButtonReleased(id, button)
+                };
+
+                Some(RecordedEvent::Gamepad(event))
+            }
+            "pad_axis" => Some(RecordedEvent::Gamepad(GamepadEvent::AxisChanged(
+                parts.get(1)?.parse().ok()?,
+                GamepadAxis::from_name(parts.get(2)?)?,
+                parts.get(3)?.parse().ok()?,
+            ))),
+            _ => None,
+        }
+    }
+}
+
+fn state_name(pressed: bool) -> &'static str {
+    if pressed {
+        "pressed"
+    } else {
+        "released"
+    }
+}
+
+fn parse_state(state: &str) -> Option<bool> {
+    match state {
+        "pressed" => Some(true),
+        "released" => Some(false),
+        _ => None,
+    }
+}
+
+#[derive(Clone)]
+pub struct InputFrame {
+    // unscaled frame time the recorded frame ran with
+    pub delta_time: f32,
+    // events polled at the end of the frame, they are seen by the next frame's update
+    pub events: Vec<RecordedEvent>,
+}
+
+#[derive(Clone)]
+pub struct InputRecording {
+    pub frames: Vec<InputFrame>,
+}
+
+#[allow(dead_code)]
+impl InputRecording {
+    pub fn new() -> InputRecording {
+        InputRecording { frames: Vec::new() }
+    }
+
+    // start a new frame, events recorded after this belong to it
+    pub fn begin_frame(&mut self, delta_time: f32) {
+        self.frames.push(InputFrame {
+            delta_time: delta_time,
+            events: Vec::new(),
+        });
+    }
+
+    // returns false when the event is not one that gets recorded
+    pub fn record(&mut self, event: &glutin::Event) -> bool {
+        let recorded = match RecordedEvent::from_event(event) {
+            Some(recorded) => recorded,
+            None => return false,
+        };
+
+        self.push(recorded);
+
+        true
+    }
+
+    pub fn push(&mut self, event: RecordedEvent) {
+        if self.frames.is_empty() {
+            self.begin_frame(0.0);
+        }
+
+        self.frames.last_mut().unwrap().events.push(event);
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.serialize())
+    }
+
+    pub fn load(path: &str) -> io::Result<InputRecording> {
+        let source = fs::read_to_string(path)?;
+
+        Ok(InputRecording::parse(&source))
+    }
+
+    pub fn serialize(&self) -> String {
+        let mut out = String::new();
+
+        for frame in &self.frames {
+            out.push_str(&format!("frame {}\n", frame.delta_time));
+
+            for event in &frame.events {
+                out.push_str(&event.to_line());
+                out.push('\n');
+            }
+        }
+
+        out
+    }
+
+    pub fn parse(source: &str) -> InputRecording {
+        let mut recording = InputRecording::new();
+
+        for line in source.lines() {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+
+            if parts.is_empty() || parts[0].starts_with('#') {
+                continue;
+            }
+
+            if parts[0] == "frame" {
+                let delta_time = parts.get(1).and_then(|d| d.parse().ok()).unwrap_or(0.0);
+                recording.begin_frame(delta_time);
+                continue;
+            }
+
+            match RecordedEvent::parse(&parts) {
+                Some(event) if !recording.is_empty() => {
+                    recording.frames.last_mut().unwrap().events.push(event)
+                }
+                _ => println!(
+                    "{}",
+                    format!("Invalid line in input recording: '{}'", line).red()
+                ),
+            }
+        }
+
+        recording
+    }
+}
+
+// hands out the frames of a recording one at a time
+pub struct InputPlayback {
+    recording: InputRecording,
+    next_frame: usize,
+}
+
+#[allow(dead_code)]
+impl InputPlayback {
+    pub fn new(recording: InputRecording) -> InputPlayback {
+        InputPlayback {
+            recording: recording,
+            next_frame: 0,
+        }
+    }
+
+    pub fn next_frame(&mut self) -> Option<&InputFrame> {
+        let frame = self.recording.frames.get(self.next_frame);
+
+        if frame.is_some() {
+            self.next_frame += 1;
+        }
+
+        frame
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next_frame >= self.recording.len()
+    }
+
+    pub fn frames_played(&self) -> usize {
+        self.next_frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_recording() -> InputRecording {
+        let mut recording = InputRecording::new();
+
+        recording.begin_frame(1.0 / 60.0);
+        recording.push(RecordedEvent::Key(glutin::VirtualKeyCode::W, true));
+        recording.push(RecordedEvent::Key(glutin::VirtualKeyCode::LControl, true));
+        recording.push(RecordedEvent::MouseButton(glutin::MouseButton::Left, true));
+        recording.push(RecordedEvent::CursorMoved(512.0, 384.5));
+        recording.push(RecordedEvent::Character(' '));
+
+        recording.begin_frame(0.1);
+        recording.push(RecordedEvent::MouseMotion(1.5, -2.0));
+        recording.push(RecordedEvent::MouseWheel(0.0, 1.0));
+        recording.push(RecordedEvent::MouseButton(
+            glutin::MouseButton::Other(7),
+            false,
+        ));
+        recording.push(RecordedEvent::Gamepad(GamepadEvent::AxisChanged(
+            0,
+            GamepadAxis::LeftStickX,
+            0.5,
+        )));
+
+        recording.begin_frame(1.0 / 30.0);
+        recording.push(RecordedEvent::Key(glutin::VirtualKeyCode::W, false));
+
+        recording
+    }
+
+    #[test]
+    fn keys_are_saved_by_name() {
+        let mut recording = InputRecording::new();
+        recording.push(RecordedEvent::Key(glutin::VirtualKeyCode::LControl, true));
+
+        assert_eq!(recording.serialize(), "frame 0\nkey LControl pressed\n");
+        assert!(RecordedEvent::parse(&["key", "17", "pressed"]).is_none());
+    }
+
+    #[test]
+    fn recordings_round_trip_and_replay() {
+        let recording = sample_recording();
+        let parsed = InputRecording::parse(&recording.serialize());
+
+        assert_eq!(parsed.len(), recording.len());
+        for (a, b) in parsed.frames.iter().zip(recording.frames.iter()) {
+            assert_eq!(a.delta_time, b.delta_time);
+            assert_eq!(a.events, b.events);
+        }
+
+        let mut input = Input::with_gamepad_backend(None);
+        let mut playback = InputPlayback::new(parsed);
+
+        input.start_event_proc();
+        for event in &playback.next_frame().unwrap().events {
+            event.apply(&mut input);
+        }
+        assert!(input.get_key_down(glutin::VirtualKeyCode::W));
+        assert!(input.get_key(glutin::VirtualKeyCode::LControl));
+        assert!(input.get_mouse_down(glutin::MouseButton::Left));
+        assert_eq!(input.get_mouse_pos(), [512.0, 384.5]);
+        assert_eq!(input.get_text_input(), " ");
+
+        input.start_event_proc();
+        for event in &playback.next_frame().unwrap().events {
+            event.apply(&mut input);
+        }
+        assert!(input.get_key(glutin::VirtualKeyCode::W));
+        assert!(!input.get_key_down(glutin::VirtualKeyCode::W));
+        assert_eq!(input.get_mouse_delta(), [1.5, -2.0]);
+        assert_eq!(input.get_scroll_delta(), [0.0, 1.0]);
+        assert_eq!(input.get_text_input(), "");
+
+        input.start_event_proc();
+        for event in &playback.next_frame().unwrap().events {
+            event.apply(&mut input);
+        }
+        assert!(input.get_key_up(glutin::VirtualKeyCode::W));
+        assert!(!input.get_key(glutin::VirtualKeyCode::W));
+        assert!(playback.is_finished());
+        assert!(playback.next_frame().is_none());
+    }
+}
@@ -0,0 +1,410 @@
+// collects the draws of a pass and orders them by a sort key, so objects sharing a shader, textures
+// and model end up next to each other and the renderer switches state as little as possible.
+// nothing in here touches the gpu, the ordering can be checked without a display
+use ecs::Entity;
+use material::Material;
+use resource_manager::Resource;
+use std::collections::HashMap;
+use std::ops::Range;
+
+// passes are drawn in this order
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RenderPass {
+    Opaque,
+    Transparent,
+}
+
+impl RenderPass {
+    pub fn of(material: &Material) -> RenderPass {
+        if material.is_transparent() {
+            RenderPass::Transparent
+        } else {
+            RenderPass::Opaque
+        }
+    }
+}
+
+// sort key layout, from the highest bits down
+//   opaque:      pass 2 | shader 10 | material 14 | mesh 14 | depth 24
+//   transparent: pass 2 | far to near depth 24 | shader 10 | material 14 | mesh 14
+// opaque objects are grouped by state and drawn front to back inside a group so the depth test
+// rejects hidden pixels early, transparent ones have to be blended back to front. ids that don't
+// fit are wrapped, which only costs some extra state changes
+const SHADER_BITS: u32 = 10;
+const MATERIAL_BITS: u32 = 14;
+const MESH_BITS: u32 = 14;
+const DEPTH_BITS: u32 = 24;
+
+fn field(value: u64, bits: u32) -> u64 {
+    value & ((1 << bits) - 1)
+}
+
+// positive floats keep their order when compared as integers, the top bits are enough to sort by
+pub fn depth_bits(depth: f32) -> u64 {
+    u64::from(depth.max(0.0).to_bits() >> (31 - DEPTH_BITS))
+}
+
+pub fn sort_key(pass: RenderPass, shader: u64, material: u64, mesh: u64, depth: f32) -> u64 {
+    let pass_bits = (pass as u64) << 62;
+    let shader = field(shader, SHADER_BITS);
+    let material = field(material, MATERIAL_BITS);
+    let mesh = field(mesh, MESH_BITS);
+    let depth = field(depth_bits(depth), DEPTH_BITS);
+
+    match pass {
+        RenderPass::Opaque => {
+            pass_bits
+                | (shader << (MATERIAL_BITS + MESH_BITS + DEPTH_BITS))
+                | (material << (MESH_BITS + DEPTH_BITS))
+                | (mesh << DEPTH_BITS)
+                | depth
+        }
+        RenderPass::Transparent => {
+            let far_to_near = field(!depth, DEPTH_BITS);
+
+            pass_bits
+                | (far_to_near << (SHADER_BITS + MATERIAL_BITS + MESH_BITS))
+                | (shader << (MATERIAL_BITS + MESH_BITS))
+                | (material << MESH_BITS)
+                | mesh
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct DrawItem {
+    pub key: u64,
+    pub pass: RenderPass,
+    pub entity: Entity,
+    pub model: Resource,
+    pub material: Material,
+    pub model_matrix: [[f32; 4]; 4],
+}
+
+// how often the bound state changes when drawing the items in order
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct StateChanges {
+    pub shaders: u32,
+    pub materials: u32,
+    pub meshes: u32,
+}
+
+pub struct RenderQueue {
+    items: Vec<DrawItem>,
+    // the textures are what a material change rebinds, colour and tiling are plain uniforms, so
+    // materials only differing in those share an id
+    material_ids: HashMap<(Resource, Resource), u64>,
+}
+
+#[allow(dead_code)]
+impl RenderQueue {
+    pub fn new() -> RenderQueue {
+        RenderQueue {
+            items: Vec::new(),
+            material_ids: HashMap::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+        self.material_ids.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    // depth is the distance from the camera
+    pub fn push(
+        &mut self,
+        entity: Entity,
+        model: Resource,
+        material: Material,
+        model_matrix: [[f32; 4]; 4],
+        depth: f32,
+    ) {
+        let next_id = self.material_ids.len() as u64;
+        let material_id = *self
+            .material_ids
+            .entry((material.diffuse_tex, material.normal_tex))
+            .or_insert(next_id);
+
+        let pass = RenderPass::of(&material);
+
+        self.items.push(DrawItem {
+            key: sort_key(
+                pass,
+                material.shader_prog as u64,
+                material_id,
+                model as u64,
+                depth,
+            ),
+            pass: pass,
+            entity: entity,
+            model: model,
+            material: material,
+            model_matrix: model_matrix,
+        });
+    }
+
+    // stable, so items with equal keys keep the order they were pushed in
+    pub fn sort(&mut self) {
+        self.items.sort_by_key(|item| item.key);
+    }
+
+    pub fn items(&self) -> &[DrawItem] {
+        &self.items
+    }
+
+    pub fn state_changes(&self) -> StateChanges {
+        count_state_changes(&self.items)
+    }
+
+    // runs of sorted items that can share one instanced draw call
+    pub fn batches(&self) -> Vec<Range<usize>> {
+        batch_ranges(&self.items, can_instance)
+    }
+
+    // the picking program is the same for every object, so only the model has to match
+    pub fn picking_batches(&self) -> Vec<Range<usize>> {
+        batch_ranges(&self.items, |a, b| a.model == b.model)
+    }
+}
+
+// the colour and opacity go into the per instance data, everything else the draw binds has to
+// be the same
+pub fn can_instance(a: &DrawItem, b: &DrawItem) -> bool {
+    a.pass == b.pass
+        && a.model == b.model
+        && a.material.shader_prog == b.material.shader_prog
+        && a.material.diffuse_tex == b.material.diffuse_tex
+        && a.material.normal_tex == b.material.normal_tex
+        && a.material.tiling == b.material.tiling
+}
+
+// splits the items into ranges of neighbours that match the first item of their range. the
+// order is kept, so transparent runs still blend back to front
+pub fn batch_ranges<F: Fn(&DrawItem, &DrawItem) -> bool>(
+    items: &[DrawItem],
+    same_batch: F,
+) -> Vec<Range<usize>> {
+    split_range(0..items.len(), |a, b| same_batch(&items[a], &items[b]))
+}
+
+// the same split on indices, for things kept next to the items like their lights
+pub fn split_range<F: Fn(usize, usize) -> bool>(
+    range: Range<usize>,
+    same_run: F,
+) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = range.start;
+
+    for index in range.start + 1..range.end {
+        if !same_run(start, index) {
+            ranges.push(start..index);
+            start = index;
+        }
+    }
+
+    if start < range.end {
+        ranges.push(start..range.end);
+    }
+
+    ranges
+}
+
+// the first draw counts as a change of everything
+pub fn count_state_changes(items: &[DrawItem]) -> StateChanges {
+    let mut changes = StateChanges::default();
+    let mut previous: Option<&DrawItem> = None;
+
+    for item in items {
+        let (shader, material, mesh) = match previous {
+            Some(previous) => (
+                previous.material.shader_prog != item.material.shader_prog,
+                previous.material.diffuse_tex != item.material.diffuse_tex
+                    || previous.material.normal_tex != item.material.normal_tex,
+                previous.model != item.model,
+            ),
+            None => (true, true, true),
+        };
+
+        if shader {
+            changes.shaders += 1;
+        }
+        if material {
+            changes.materials += 1;
+        }
+        if mesh {
+            changes.meshes += 1;
+        }
+
+        previous = Some(item);
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ecs::Entity;
+
+    const IDENTITY: [[f32; 4]; 4] = [
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ];
+
+    fn material(shader: Resource, diffuse: Resource) -> Material {
+        Material::new(shader, diffuse, diffuse + 1, [1.0, 1.0, 1.0], [1.0, 1.0])
+    }
+
+    fn entity(index: usize) -> Entity {
+        Entity::new(index, 0)
+    }
+
+    fn order(queue: &RenderQueue) -> Vec<usize> {
+        queue
+            .items()
+            .iter()
+            .map(|item| item.entity.index())
+            .collect()
+    }
+
+    #[test]
+    fn opaque_items_sort_front_to_back_inside_a_state_group() {
+        let mut queue = RenderQueue::new();
+        let grass = material(1, 10);
+        let brick = material(1, 20);
+
+        queue.push(entity(0), 5, grass, IDENTITY, 30.0);
+        queue.push(entity(1), 5, brick, IDENTITY, 1.0);
+        queue.push(entity(2), 5, grass, IDENTITY, 10.0);
+        queue.push(entity(3), 5, brick, IDENTITY, 20.0);
+        queue.push(entity(4), 5, grass, IDENTITY, 0.5);
+        queue.sort();
+
+        // grass got the first material id, the groups stay together and each is near to far
+        assert_eq!(order(&queue), vec![4, 2, 0, 1, 3]);
+    }
+
+    #[test]
+    fn transparent_items_sort_back_to_front_across_groups() {
+        let mut queue = RenderQueue::new();
+        let glass = material(1, 10).with_opacity(0.5);
+        let smoke = material(2, 20).with_opacity(0.5);
+
+        queue.push(entity(0), 5, glass, IDENTITY, 1.0);
+        queue.push(entity(1), 6, smoke, IDENTITY, 3.0);
+        queue.push(entity(2), 5, material(1, 10), IDENTITY, 100.0);
+        queue.push(entity(3), 5, glass, IDENTITY, 2.0);
+        queue.push(entity(4), 6, smoke, IDENTITY, 0.0);
+        queue.sort();
+
+        // the opaque object goes first however far away it is
+        assert_eq!(order(&queue), vec![2, 1, 3, 0, 4]);
+        assert_eq!(queue.items()[0].pass, RenderPass::Opaque);
+        assert!(queue.items()[1..]
+            .iter()
+            .all(|item| item.pass == RenderPass::Transparent));
+    }
+
+    #[test]
+    fn overflowing_ids_wrap_without_touching_the_pass() {
+        for &pass in &[RenderPass::Opaque, RenderPass::Transparent] {
+            let key = sort_key(pass, u64::MAX, u64::MAX, u64::MAX, std::f32::MAX);
+            assert_eq!(key >> 62, pass as u64);
+
+            // the id one past each field wraps around to 0
+            assert_eq!(
+                sort_key(
+                    pass,
+                    1 << SHADER_BITS,
+                    1 << MATERIAL_BITS,
+                    1 << MESH_BITS,
+                    1.0
+                ),
+                sort_key(pass, 0, 0, 0, 1.0)
+            );
+        }
+
+        let largest_opaque = sort_key(
+            RenderPass::Opaque,
+            u64::MAX,
+            u64::MAX,
+            u64::MAX,
+            std::f32::INFINITY,
+        );
+        let smallest_transparent = sort_key(RenderPass::Transparent, 0, 0, 0, std::f32::INFINITY);
+        assert!(largest_opaque < smallest_transparent);
+    }
+
+    #[test]
+    fn negative_and_nan_depths_sort_first() {
+        assert_eq!(depth_bits(-5.0), 0);
+        assert_eq!(depth_bits(std::f32::NAN), 0);
+        assert!(depth_bits(0.5) < depth_bits(1.0));
+        assert!(depth_bits(1.0) < depth_bits(1000.0));
+    }
+
+    #[test]
+    fn demo_spheres_switch_materials_once() {
+        let mut queue = RenderQueue::new();
+        let grass = material(1, 10);
+        let brick = material(1, 20);
+
+        // the 5x5x5 checkerboard of grass and brick spheres from the demo
+        let mut index = 0;
+        for x in 0..5 {
+            for y in 0..5 {
+                for z in 0..5 {
+                    let material = if (x + y + z) % 2 == 1 { grass } else { brick };
+                    let depth = (x + y + z) as f32;
+
+                    queue.push(entity(index), 7, material, IDENTITY, depth);
+                    index += 1;
+                }
+            }
+        }
+
+        let unsorted = queue.state_changes();
+        assert_eq!(unsorted.materials, 125);
+
+        queue.sort();
+        assert_eq!(
+            queue.state_changes(),
+            StateChanges {
+                shaders: 1,
+                materials: 2,
+                meshes: 1,
+            }
+        );
+        assert_eq!(queue.batches().len(), 2);
+        assert_eq!(queue.picking_batches(), vec![0..125]);
+    }
+
+    #[test]
+    fn batches_split_where_the_state_changes() {
+        let mut queue = RenderQueue::new();
+        let grass = material(1, 10);
+
+        queue.push(entity(0), 5, grass, IDENTITY, 1.0);
+        queue.push(entity(1), 5, grass, IDENTITY, 2.0);
+        queue.push(entity(2), 6, grass, IDENTITY, 3.0);
+        queue.push(entity(3), 5, grass.with_opacity(0.5), IDENTITY, 4.0);
+        queue.sort();
+
+        assert_eq!(queue.batches(), vec![0..2, 2..3, 3..4]);
+        assert_eq!(
+            split_range(2..7, |a, b| a / 2 == b / 2),
+            vec![2..4, 4..6, 6..7]
+        );
+        assert!(split_range(3..3, |_, _| true).is_empty());
+    }
+}
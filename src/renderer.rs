@@ -1,39 +1,279 @@
 extern crate glium;
 extern crate tobj;
 use assets;
+use bounds::Frustum;
 use camera::*;
+use ecs::*;
+use glium::uniforms::{UniformValue, Uniforms};
+use lighting::{self, SceneLight, MAX_LIGHTS};
+use model::ModelVertex;
+use render_queue::{self, RenderPass, RenderQueue};
 use resource_manager::*;
 use shader;
+use std::ops::Range;
+use vector::Vector3;
+
+// a rectangle of the render target in 0..1 coordinates, with the origin at the top left
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+#[allow(dead_code)]
+impl Viewport {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Viewport {
+        Viewport {
+            x: x,
+            y: y,
+            width: width,
+            height: height,
+        }
+    }
+
+    pub fn full() -> Viewport {
+        Viewport::new(0.0, 0.0, 1.0, 1.0)
+    }
+
+    pub fn pixel_size(&self, target_width: u32, target_height: u32) -> (i32, i32) {
+        (
+            ((self.width * target_width as f32).round() as i32).max(1),
+            ((self.height * target_height as f32).round() as i32).max(1),
+        )
+    }
+
+    // glium rectangles start at the bottom left
+    pub fn to_rect(&self, target_width: u32, target_height: u32) -> glium::Rect {
+        let (width, height) = self.pixel_size(target_width, target_height);
+        let bottom = (1.0 - self.y - self.height) * target_height as f32;
+
+        glium::Rect {
+            left: (self.x * target_width as f32).round().max(0.0) as u32,
+            bottom: bottom.round().max(0.0) as u32,
+            width: width as u32,
+            height: height as u32,
+        }
+    }
+
+    // turns a window pixel into a pixel of the viewport, None when it is outside
+    pub fn to_local(
+        &self,
+        x: f32,
+        y: f32,
+        target_width: u32,
+        target_height: u32,
+    ) -> Option<[f32; 2]> {
+        let local_x = x - self.x * target_width as f32;
+        let local_y = y - self.y * target_height as f32;
+        let (width, height) = self.pixel_size(target_width, target_height);
+
+        if local_x < 0.0 || local_y < 0.0 || local_x >= width as f32 || local_y >= height as f32 {
+            return None;
+        }
+
+        Some([local_x, local_y])
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ClearMode {
+    Color([f32; 3]),
+    // keeps what is already in the viewport, used to draw on top of another camera
+    DepthOnly,
+    Nothing,
+}
+
+// layer masks have one bit per entity layer, entities in layer 32 and up are only drawn by
+// cameras that see every layer
+pub const ALL_LAYERS: u32 = !0;
+
+pub fn in_layer_mask(layer_mask: u32, layer: u32) -> bool {
+    if layer < 32 {
+        layer_mask & (1 << layer) != 0
+    } else {
+        layer_mask == ALL_LAYERS
+    }
+}
+
+// a texture a camera renders into, the texture is a normal texture resource so it can be used as
+// the diffuse texture of a material
+pub struct RenderTarget {
+    pub texture: Resource,
+    width: u32,
+    height: u32,
+    depth_buffer: Option<glium::framebuffer::DepthRenderBuffer>,
+}
+
+#[allow(dead_code)]
+impl RenderTarget {
+    pub fn new(
+        display: Option<&glium::Display>,
+        resources: &mut ResourceContext,
+        width: u32,
+        height: u32,
+    ) -> RenderTarget {
+        let texture = display
+            .map(|display| glium::texture::SrgbTexture2d::empty(display, width, height).unwrap());
+        let depth_buffer = display.map(|display| {
+            glium::framebuffer::DepthRenderBuffer::new(
+                display,
+                glium::texture::DepthFormat::F32,
+                width,
+                height,
+            )
+            .unwrap()
+        });
+
+        RenderTarget {
+            texture: resources.alloc_tex(texture),
+            width: width,
+            height: height,
+            depth_buffer: depth_buffer,
+        }
+    }
+
+    pub fn get_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+}
+
+// a camera drawn in addition to the main one, either into part of the window or into a texture
+pub struct RenderCamera {
+    pub camera: CameraState,
+    pub viewport: Viewport,
+    pub clear: ClearMode,
+    pub layer_mask: u32,
+    pub enabled: bool,
+    pub target: Option<RenderTarget>,
+}
+
+#[allow(dead_code)]
+impl RenderCamera {
+    pub fn new(camera: CameraState) -> RenderCamera {
+        RenderCamera {
+            camera: camera,
+            viewport: Viewport::full(),
+            clear: ClearMode::Color([0.0, 0.0, 0.0]),
+            layer_mask: ALL_LAYERS,
+            enabled: true,
+            target: None,
+        }
+    }
+
+    pub fn with_viewport(mut self, viewport: Viewport) -> Self {
+        self.viewport = viewport;
+        self
+    }
+
+    pub fn with_clear(mut self, clear: ClearMode) -> Self {
+        self.clear = clear;
+        self
+    }
+
+    pub fn with_layer_mask(mut self, layer_mask: u32) -> Self {
+        self.layer_mask = layer_mask;
+        self
+    }
+
+    pub fn with_target(mut self, target: RenderTarget) -> Self {
+        self.target = Some(target);
+        self
+    }
+
+    // the texture resource the camera renders into
+    pub fn target_texture(&self) -> Option<Resource> {
+        self.target.as_ref().map(|target| target.texture)
+    }
+
+    // keep the camera aspect ratio in line with the size of its viewport
+    fn sync_size(&mut self, window_width: u32, window_height: u32) {
+        let (target_width, target_height) = match self.target {
+            Some(ref target) => target.get_size(),
+            None => (window_width, window_height),
+        };
+
+        let size = self.viewport.pixel_size(target_width, target_height);
+        if self.camera.get_screen_size() != size {
+            self.camera.resize(size.0, size.1);
+        }
+    }
+}
+
+// what the last frame drew, summed over every camera
+#[derive(Copy, Clone, Debug, Default)]
+pub struct RenderStats {
+    pub drawn: u32,
+    pub culled: u32,
+    // how often consecutive draws switched state, see render_queue
+    pub shader_changes: u32,
+    pub material_changes: u32,
+    pub mesh_changes: u32,
+    // draw calls issued, picking included
+    pub draw_calls: u32,
+}
+
+// per instance attributes read by basic.vs and picking.vs
+#[derive(Copy, Clone)]
+pub struct InstanceData {
+    pub instance_model: [[f32; 4]; 4],
+    // the material colour with the opacity in w
+    pub instance_color: [f32; 4],
+    // the position in RenderContext::picking_entities plus one, 0 is nothing in the picking texture
+    pub instance_id: u32,
+}
+
+implement_vertex!(InstanceData, instance_model, instance_color, instance_id);
 
 pub struct RenderContext {
     pub clear_color: [f32; 3],
-    pub gameobjects: Vec<Resource>,
+    // added to every lit pixel on top of the lights
+    pub ambient_light: [f32; 3],
     pub camera: CameraState,
-    pub picked_object: Option<Resource>,
+    // the part of the window and the layers the main camera draws
+    pub viewport: Viewport,
+    pub layer_mask: u32,
+    // extra cameras, the index is used as a handle
+    pub cameras: Vec<RenderCamera>,
+    pub picked_object: Option<Entity>,
+
+    // skip objects whose bounds are outside the camera frustum
+    pub frustum_culling: bool,
+    // draw runs of objects sharing a model and material with one instanced draw call
+    pub instancing: bool,
+    pub stats: RenderStats,
+
+    window_width: u32,
+    window_height: u32,
 
     picking_attachments: Option<(
         glium::texture::UnsignedTexture2d,
         glium::framebuffer::DepthRenderBuffer,
     )>,
 
-    picking_pbo: glium::texture::pixel_buffer::PixelBuffer<u32>,
+    // the picking resources are None when running without a display
+    picking_pbo: Option<glium::texture::pixel_buffer::PixelBuffer<u32>>,
 
-    picking_program: glium::Program,
+    picking_program: Option<glium::Program>,
+    // the entities drawn into the picking texture, the ids in it are an index into this. the
+    // handles keep their generation so a slot reused before the read back isn't picked
+    picking_entities: Vec<Entity>,
 }
 
 #[allow(dead_code)]
 impl RenderContext {
-    pub fn new(win_width: i32, win_height: i32, display: &glium::Display) -> RenderContext {
+    pub fn new(win_width: i32, win_height: i32, display: Option<&glium::Display>) -> RenderContext {
         let picking_attachments: Option<(
             glium::texture::UnsignedTexture2d,
             glium::framebuffer::DepthRenderBuffer,
         )> = None;
 
-        let picking_pbo: glium::texture::pixel_buffer::PixelBuffer<u32> =
-            glium::texture::pixel_buffer::PixelBuffer::new_empty(display, 1);
+        let picking_pbo: Option<glium::texture::pixel_buffer::PixelBuffer<u32>> =
+            display.map(|display| glium::texture::pixel_buffer::PixelBuffer::new_empty(display, 1));
 
         let picking_program = shader::load(
-            &display,
+            display,
             &assets::get_asset("./res/picking.vs"),
             &assets::get_asset("./res/picking.fs"),
         );
@@ -41,32 +281,74 @@ impl RenderContext {
         let mut rc = RenderContext {
             camera: CameraState::new(win_width, win_height),
             clear_color: [0.0, 0.0, 0.0],
-            gameobjects: Vec::new(),
+            ambient_light: [0.4, 0.4, 0.4],
+            viewport: Viewport::full(),
+            layer_mask: ALL_LAYERS,
+            cameras: Vec::new(),
             picked_object: None,
 
+            frustum_culling: true,
+            instancing: true,
+            stats: RenderStats::default(),
+
+            window_width: win_width as u32,
+            window_height: win_height as u32,
+
             picking_attachments: picking_attachments,
             picking_pbo: picking_pbo,
             picking_program: picking_program,
+            picking_entities: Vec::new(),
         };
 
-        rc.init_picking_buffers(display, win_width, win_height);
+        if let Some(display) = display {
+            rc.init_picking_buffers(display, win_width, win_height);
+        }
 
         rc
     }
 
-    pub fn get_gameobject(&mut self, resources: &mut ResourceContext, name: String) -> Resource {
-        for i in 0..self.gameobjects.len() {
-            if resources.get_gameobject_ref(self.gameobjects[i]).name == name {
-                return self.gameobjects[i];
-            }
-        }
+    pub fn resized(&mut self, display: &glium::Display, width: i32, height: i32) {
+        self.window_width = width as u32;
+        self.window_height = height as u32;
+        self.sync_camera_sizes();
+        self.init_picking_buffers(display, width, height)
+    }
 
-        self.gameobjects[0]
+    // returns the handle of the camera
+    pub fn add_camera(&mut self, mut camera: RenderCamera) -> usize {
+        camera.sync_size(self.window_width, self.window_height);
+        self.cameras.push(camera);
+
+        self.cameras.len() - 1
     }
 
-    pub fn resized(&mut self, display: &glium::Display, width: i32, height: i32) {
-        self.camera.resize(width, height);
-        self.init_picking_buffers(display, width, height)
+    pub fn get_camera(&self, camera: usize) -> Option<&RenderCamera> {
+        self.cameras.get(camera)
+    }
+
+    pub fn get_camera_mut(&mut self, camera: usize) -> Option<&mut RenderCamera> {
+        self.cameras.get_mut(camera)
+    }
+
+    // the main camera ray through a window pixel, None when the pixel is outside its viewport
+    pub fn screen_ray(&self, x: f32, y: f32) -> Option<Ray> {
+        self.viewport
+            .to_local(x, y, self.window_width, self.window_height)
+            .map(|local| self.camera.screen_ray(local[0], local[1]))
+    }
+
+    // viewports can change at any time, so the camera sizes are checked before every frame
+    fn sync_camera_sizes(&mut self) {
+        let size = self
+            .viewport
+            .pixel_size(self.window_width, self.window_height);
+        if self.camera.get_screen_size() != size {
+            self.camera.resize(size.0, size.1);
+        }
+
+        for camera in &mut self.cameras {
+            camera.sync_size(self.window_width, self.window_height);
+        }
     }
 
     pub fn init_picking_buffers(&mut self, display: &glium::Display, width: i32, height: i32) {
@@ -90,8 +372,92 @@ impl RenderContext {
     }
 }
 
+// the camera state one pass of draw_scene needs
+struct SceneView {
+    persp_matrix: [[f32; 4]; 4],
+    view_matrix: [[f32; 4]; 4],
+    position: Vector3,
+    // None when culling is off
+    frustum: Option<Frustum>,
+    layer_mask: u32,
+    overlay: bool,
+    // a camera rendering into a texture skips the objects using that texture
+    skip_texture: Option<Resource>,
+    instancing: bool,
+}
+
+impl SceneView {
+    fn new(
+        camera: &mut CameraState,
+        layer_mask: u32,
+        overlay: bool,
+        culling: bool,
+        instancing: bool,
+    ) -> SceneView {
+        let persp_matrix = camera.get_perspective();
+        let view_matrix = camera.get_view();
+
+        SceneView {
+            persp_matrix: persp_matrix,
+            view_matrix: view_matrix,
+            position: camera.position,
+            frustum: if culling {
+                Some(Frustum::from_matrices(persp_matrix, view_matrix))
+            } else {
+                None
+            },
+            layer_mask: layer_mask,
+            overlay: overlay,
+            skip_texture: None,
+            instancing: instancing,
+        }
+    }
+}
+
+fn draw_parameters(
+    projection: &Projection,
+    viewport: glium::Rect,
+) -> glium::DrawParameters<'static> {
+    glium::DrawParameters {
+        depth: glium::Depth {
+            test: projection.depth_test(),
+            write: true,
+            ..Default::default()
+        },
+        backface_culling: glium::BackfaceCullingMode::CullCounterClockwise,
+        viewport: Some(viewport),
+        ..Default::default()
+    }
+}
+
+fn clear_viewport<S: glium::Surface>(
+    surface: &mut S,
+    rect: &glium::Rect,
+    clear: ClearMode,
+    projection: &Projection,
+) {
+    match clear {
+        ClearMode::Color(color) => surface.clear(
+            Some(rect),
+            Some((color[0], color[1], color[2], 1.0)),
+            false,
+            Some(projection.clear_depth()),
+            None,
+        ),
+        ClearMode::DepthOnly => surface.clear(
+            Some(rect),
+            None,
+            false,
+            Some(projection.clear_depth()),
+            None,
+        ),
+        ClearMode::Nothing => {}
+    }
+}
+
 pub fn render(
     context: &mut RenderContext,
+    entities: &EntityContext,
     resources: &mut ResourceContext,
     target: &mut glium::Frame,
     display: &glium::Display,
@@ -99,14 +465,91 @@ pub fn render(
 ) {
     use glium::Surface;
 
-    let picked_object = context.picking_pbo.read().map(|d| d[0]).unwrap_or(0);
+    let (window_width, window_height) = target.get_dimensions();
+    context.window_width = window_width;
+    context.window_height = window_height;
+    context.sync_camera_sizes();
 
-    if picked_object != 0 {
-        context.picked_object = Some(context.gameobjects[(picked_object - 1) as usize]);
-    } else {
-        context.picked_object = None;
+    let picking_pbo = context
+        .picking_pbo
+        .as_ref()
+        .expect("rendering needs a display");
+    let picking_program = context
+        .picking_program
+        .as_ref()
+        .expect("rendering needs a display");
+
+    let picked_id = picking_pbo.read().map(|d| d[0]).unwrap_or(0) as usize;
+
+    // the id was read back a frame late, so the entity may have been destroyed since
+    context.picked_object = match context.picking_entities.get(picked_id.wrapping_sub(1)) {
+        Some(&entity) if entities.is_alive(entity) => Some(entity),
+        _ => None,
+    };
+    context.picking_entities.clear();
+
+    let frustum_culling = context.frustum_culling;
+    let instancing = context.instancing;
+    let mut stats = RenderStats::default();
+
+    let lights = lighting::collect_lights(entities);
+    let ambient_light = context.ambient_light;
+
+    // cameras rendering into textures go first so the main view shows this frame's image
+    for render_camera in &mut context.cameras {
+        if !render_camera.enabled {
+            continue;
+        }
+
+        let render_target = match render_camera.target {
+            Some(ref render_target) => render_target,
+            None => continue,
+        };
+        let depth_buffer = match render_target.depth_buffer {
+            Some(ref depth_buffer) => depth_buffer,
+            None => continue,
+        };
+
+        let mut texture_target = glium::framebuffer::SimpleFrameBuffer::with_depth_buffer(
+            display,
+            resources.get_tex_ref(render_target.texture),
+            depth_buffer,
+        )
+        .unwrap();
+
+        let projection = render_camera.camera.get_projection();
+        let rect = render_camera
+            .viewport
+            .to_rect(render_target.width, render_target.height);
+        clear_viewport(&mut texture_target, &rect, render_camera.clear, &projection);
+
+        let mut view = SceneView::new(
+            &mut render_camera.camera,
+            render_camera.layer_mask,
+            false,
+            frustum_culling,
+            instancing,
+        );
+        view.skip_texture = Some(render_target.texture);
+
+        draw_scene(
+            &mut texture_target,
+            None,
+            picking_program,
+            display,
+            &view,
+            entities,
+            resources,
+            &lights,
+            ambient_light,
+            &mut stats,
+            &draw_parameters(&projection, rect),
+        );
     }
 
+    let projection = context.camera.get_projection();
+    let rect = context.viewport.to_rect(window_width, window_height);
+
     target.clear_color_and_depth(
         (
             context.clear_color[0],
@@ -114,21 +557,10 @@ pub fn render(
             context.clear_color[2],
             1.0,
         ),
-        1.0,
+        projection.clear_depth(),
     );
 
-    let params = glium::DrawParameters {
-        depth: glium::Depth {
-            test: glium::DepthTest::IfLess,
-            write: true,
-            ..Default::default()
-        },
-        backface_culling: glium::BackfaceCullingMode::CullCounterClockwise,
-        ..Default::default()
-    };
-
-    let pers_mat = context.camera.get_perspective();
-    let view_mat = context.camera.get_view();
+    let params = draw_parameters(&projection, rect);
 
     //clearing the picking texture
     if let Some((ref picking_texture, ref _depth_buffer)) = context.picking_attachments {
@@ -154,118 +586,100 @@ pub fn render(
     }
 
     let mut picking_target = picking_target.unwrap();
+    picking_target.clear(None, None, false, Some(projection.clear_depth()), None);
 
-    for i in 0..context.gameobjects.len() {
-        let gobj = resources.get_gameobject_ref(context.gameobjects[i]);
-
-        if gobj.overlay {
-            continue;
-        }
-
-        let mut material = gobj.material;
-        let program = resources.get_shader_ref(material.shader_prog);
-        let model = resources.get_model_ref(gobj.model);
-        let model_matrix = gobj.get_model_matrix();
-
-        let uniforms = uniform! {
-            persp_matrix: pers_mat,
-            view_matrix: view_mat,
-            model_matrix: model_matrix,
-            view_pos: context.camera.position.raw(),
-            light_dir: (-0.5, -1.0, 0.0f32),
-            ambient_light: 0.4 as f32,
-            diffuse: resources.get_tex_ref(material.diffuse_tex),
-            normal_map: resources.get_tex_ref(material.normal_tex),
-            color: material.color,
-            tiling: material.tiling,
-        };
-
-        // add 1 to id so we know that 0 is nothing
-        let picking_uniform = uniform! {
-            persp_matrix: pers_mat,
-            view_matrix: view_mat,
-            model_matrix: model_matrix,
-            id: (i + 1) as u32
-        };
+    let view = SceneView::new(
+        &mut context.camera,
+        context.layer_mask,
+        false,
+        context.frustum_culling,
+        context.instancing,
+    );
+    draw_scene(
+        target,
+        Some((&mut picking_target, &mut context.picking_entities)),
+        picking_program,
+        display,
+        &view,
+        entities,
+        resources,
+        &lights,
+        ambient_light,
+        &mut stats,
+        &params,
+    );
 
-        picking_target
-            .draw(
-                &model.vertex_buffer,
-                &model.index_buffer,
-                &context.picking_program,
-                &picking_uniform,
-                &params,
-            )
-            .unwrap();
-
-        target
-            .draw(
-                &model.vertex_buffer,
-                &model.index_buffer,
-                &program,
-                &uniforms,
-                &params,
-            )
-            .unwrap();
-    }
     // draw objects that are marked as overlay
-    target.clear_depth(1.0);
-    picking_target.clear_depth(1.0);
+    target.clear(
+        Some(&rect),
+        None,
+        false,
+        Some(projection.clear_depth()),
+        None,
+    );
+    picking_target.clear(
+        Some(&rect),
+        None,
+        false,
+        Some(projection.clear_depth()),
+        None,
+    );
 
-    for i in 0..context.gameobjects.len() {
-        let gobj = resources.get_gameobject_ref(context.gameobjects[i]);
+    let view = SceneView::new(
+        &mut context.camera,
+        context.layer_mask,
+        true,
+        context.frustum_culling,
+        context.instancing,
+    );
+    draw_scene(
+        target,
+        Some((&mut picking_target, &mut context.picking_entities)),
+        picking_program,
+        display,
+        &view,
+        entities,
+        resources,
+        &lights,
+        ambient_light,
+        &mut stats,
+        &params,
+    );
 
-        if !gobj.overlay {
+    // the extra window cameras are drawn on top, in the order they were added
+    for render_camera in &mut context.cameras {
+        if !render_camera.enabled || render_camera.target.is_some() {
             continue;
         }
 
-        let mut material = gobj.material;
-        let program = resources.get_shader_ref(material.shader_prog);
-        let model = resources.get_model_ref(gobj.model);
-        let model_matrix = gobj.get_model_matrix();
-
-        let uniforms = uniform! {
-            persp_matrix: pers_mat,
-            view_matrix: view_mat,
-            model_matrix: model_matrix,
-            view_pos: context.camera.position.raw(),
-            light_dir: (-0.5, -1.0, 0.0f32),
-            ambient_light: 0.4 as f32,
-            diffuse: resources.get_tex_ref(material.diffuse_tex),
-            normal_map: resources.get_tex_ref(material.normal_tex),
-            color: material.color,
-            tiling: material.tiling,
-        };
-
-        // add 1 to id so we know that 0 is nothing
-        let picking_uniform = uniform! {
-            persp_matrix: pers_mat,
-            view_matrix: view_mat,
-            model_matrix: model_matrix,
-            id: (i + 1) as u32
-        };
+        let projection = render_camera.camera.get_projection();
+        let rect = render_camera.viewport.to_rect(window_width, window_height);
+        clear_viewport(target, &rect, render_camera.clear, &projection);
 
-        picking_target
-            .draw(
-                &model.vertex_buffer,
-                &model.index_buffer,
-                &context.picking_program,
-                &picking_uniform,
-                &params,
-            )
-            .unwrap();
-
-        target
-            .draw(
-                &model.vertex_buffer,
-                &model.index_buffer,
-                &program,
-                &uniforms,
-                &params,
-            )
-            .unwrap();
+        let view = SceneView::new(
+            &mut render_camera.camera,
+            render_camera.layer_mask,
+            false,
+            frustum_culling,
+            instancing,
+        );
+        draw_scene(
+            target,
+            None,
+            picking_program,
+            display,
+            &view,
+            entities,
+            resources,
+            &lights,
+            ambient_light,
+            &mut stats,
+            &draw_parameters(&projection, rect),
+        );
     }
 
+    context.stats = stats;
+
     if let (Some(cursor), Some(&(ref picking_texture, _))) =
         (cursor_position, context.picking_attachments.as_ref())
     {
@@ -284,11 +698,494 @@ pub fn render(
                 .first_layer()
                 .into_image(None)
                 .unwrap()
-                .raw_read_to_pixel_buffer(&read_target, &context.picking_pbo);
+                .raw_read_to_pixel_buffer(&read_target, picking_pbo);
         } else {
-            context.picking_pbo.write(&[0]);
+            picking_pbo.write(&[0]);
         }
     } else {
-        context.picking_pbo.write(&[0]);
+        picking_pbo.write(&[0]);
+    }
+}
+
+// draw the overlay or non overlay objects the view can see, into the picking target as well when
+// one is given. the picked entities are added to its list. the objects are sorted through a
+// render queue first
+#[allow(clippy::too_many_arguments)]
+fn draw_scene<S: glium::Surface>(
+    target: &mut S,
+    picking: Option<(&mut glium::framebuffer::SimpleFrameBuffer, &mut Vec<Entity>)>,
+    picking_program: &glium::Program,
+    display: &glium::Display,
+    view: &SceneView,
+    entities: &EntityContext,
+    resources: &ResourceContext,
+    lights: &[SceneLight],
+    ambient_light: [f32; 3],
+    stats: &mut RenderStats,
+    params: &glium::DrawParameters,
+) {
+    let mut queue = RenderQueue::new();
+
+    // the scene tree narrows the frustum down to a few candidates, the exact box test below
+    // handles the rest
+    let candidates = match view.frustum {
+        Some(ref frustum) => {
+            let candidates = entities.scene_tree.query_frustum(frustum);
+
+            // the overlay pass looks at the same objects, they are only counted once
+            if !view.overlay {
+                stats.culled += (entities.scene_tree.len() - candidates.len()) as u32;
+            }
+
+            candidates
+        }
+        None => join(&entities.transforms, &entities.mesh_renderers)
+            .map(|(entity, _, _)| entity)
+            .collect(),
+    };
+
+    for entity in candidates {
+        let (transform, mesh) = match (
+            entities.transforms.get(entity),
+            entities.mesh_renderers.get(entity),
+        ) {
+            (Some(transform), Some(mesh)) => (transform, mesh),
+            _ => continue,
+        };
+
+        if mesh.overlay != view.overlay {
+            continue;
+        }
+        if !in_layer_mask(view.layer_mask, entities.get_layer(entity)) {
+            continue;
+        }
+
+        if let Some(ref frustum) = view.frustum {
+            let bounds = mesh.world_bounds(transform);
+
+            if !frustum.intersects_aabb(&bounds) {
+                stats.culled += 1;
+                continue;
+            }
+        }
+
+        let material = mesh.material;
+        if let Some(texture) = view.skip_texture {
+            // sampling the texture that is being rendered to is undefined
+            if material.diffuse_tex == texture || material.normal_tex == texture {
+                continue;
+            }
+        }
+
+        let depth = (transform.position - view.position).length();
+        queue.push(
+            entity,
+            mesh.model,
+            material,
+            transform.get_model_matrix(),
+            depth,
+        );
+    }
+
+    queue.sort();
+
+    let changes = queue.state_changes();
+    stats.shader_changes += changes.shaders;
+    stats.material_changes += changes.materials;
+    stats.mesh_changes += changes.meshes;
+
+    if queue.is_empty() {
+        return;
+    }
+
+    let (picking_target, picking_entities) = match picking {
+        Some((picking_target, picking_entities)) => (Some(picking_target), Some(picking_entities)),
+        None => (None, None),
+    };
+
+    // one instance buffer for the whole pass, each draw uses the slice of its run
+    let first_id = picking_entities.as_ref().map_or(0, |list| list.len());
+    let instances: Vec<InstanceData> = queue
+        .items()
+        .iter()
+        .enumerate()
+        .map(|(index, item)| InstanceData {
+            instance_model: item.model_matrix,
+            instance_color: [
+                item.material.color[0],
+                item.material.color[1],
+                item.material.color[2],
+                item.material.opacity,
+            ],
+            instance_id: (first_id + index + 1) as u32,
+        })
+        .collect();
+
+    if let Some(picking_entities) = picking_entities {
+        picking_entities.extend(queue.items().iter().map(|item| item.entity));
+    }
+    let instance_buffer = glium::VertexBuffer::dynamic(display, &instances).unwrap();
+
+    // transparent objects are blended over what is behind them and don't hide each other
+    let mut blend_params = params.clone();
+    blend_params.blend = glium::draw_parameters::Blend::alpha_blending();
+    blend_params.depth.write = false;
+
+    // every object gets the lights closest to it, not the ones of the run it is drawn with
+    let item_lights: Vec<Vec<usize>> = queue
+        .items()
+        .iter()
+        .map(|item| match entities.world_bounds(item.entity) {
+            Some(bounds) => lighting::select_lights(lights, &bounds, MAX_LIGHTS),
+            None => Vec::new(),
+        })
+        .collect();
+
+    let batches = if view.instancing {
+        queue.batches()
+    } else {
+        single_batches(queue.len())
+    };
+
+    for batch in batches {
+        let first = queue.items()[batch.start];
+        let (vertex_buffer, index_buffer) = match model_buffers(resources, first.model) {
+            Some(buffers) => buffers,
+            None => continue,
+        };
+
+        let program = resources.get_shader_ref(first.material.shader_prog);
+
+        // shaders without the instance attributes get the object through uniforms, one draw
+        // call each. that includes basic.vs on drivers without instanced arrays, see
+        // shader::load. the lights are uniforms too, so a run is split where they change
+        let instanced = program.get_attribute("instance_model").is_some();
+        let draws = if instanced {
+            render_queue::split_range(batch, |a, b| item_lights[a] == item_lights[b])
+        } else {
+            batch.map(|index| index..index + 1).collect()
+        };
+
+        let item_params = match first.pass {
+            RenderPass::Opaque => params,
+            RenderPass::Transparent => &blend_params,
+        };
+
+        for draw in draws {
+            let item = queue.items()[draw.start];
+            let material = item.material;
+
+            let draw_lights: Vec<&SceneLight> = item_lights[draw.start]
+                .iter()
+                .map(|&index| &lights[index])
+                .collect();
+
+            let material_uniforms = uniform! {
+                persp_matrix: view.persp_matrix,
+                view_matrix: view.view_matrix,
+                model_matrix: item.model_matrix,
+                view_pos: view.position.raw(),
+                ambient_light: ambient_light,
+                diffuse: resources.get_tex_ref(material.diffuse_tex),
+                normal_map: resources.get_tex_ref(material.normal_tex),
+                color: material.color,
+                tiling: material.tiling,
+                opacity: material.opacity,
+            };
+            let uniforms = LightUniforms {
+                lights: draw_lights,
+                rest: material_uniforms,
+            };
+
+            if instanced {
+                // the attributes are only compiled in when the driver can instance
+                let instance_slice = instance_buffer.slice(draw.clone()).unwrap();
+
+                target
+                    .draw(
+                        (vertex_buffer, instance_slice.per_instance().unwrap()),
+                        index_buffer,
+                        program,
+                        &uniforms,
+                        item_params,
+                    )
+                    .unwrap();
+            } else {
+                target
+                    .draw(vertex_buffer, index_buffer, program, &uniforms, item_params)
+                    .unwrap();
+            }
+
+            stats.drawn += draw.len() as u32;
+            stats.draw_calls += 1;
+        }
+    }
+
+    // the picking draws go after the normal ones so the picking program stays bound for the
+    // whole pass instead of being swapped in for every object. the ids come with the instances,
+    // or as a uniform when the driver can't instance
+    if let Some(picking_target) = picking_target {
+        let instanced = picking_program.get_attribute("instance_model").is_some();
+        let batches = if view.instancing && instanced {
+            queue.picking_batches()
+        } else {
+            single_batches(queue.len())
+        };
+
+        for batch in batches {
+            let (vertex_buffer, index_buffer) =
+                match model_buffers(resources, queue.items()[batch.start].model) {
+                    Some(buffers) => buffers,
+                    None => continue,
+                };
+
+            if instanced {
+                let picking_uniform = uniform! {
+                    persp_matrix: view.persp_matrix,
+                    view_matrix: view.view_matrix,
+                };
+                let instance_slice = instance_buffer.slice(batch).unwrap();
+
+                picking_target
+                    .draw(
+                        (vertex_buffer, instance_slice.per_instance().unwrap()),
+                        index_buffer,
+                        picking_program,
+                        &picking_uniform,
+                        params,
+                    )
+                    .unwrap();
+            } else {
+                let instance = instances[batch.start];
+                let picking_uniform = uniform! {
+                    persp_matrix: view.persp_matrix,
+                    view_matrix: view.view_matrix,
+                    model_matrix: instance.instance_model,
+                    object_id: instance.instance_id,
+                };
+
+                picking_target
+                    .draw(
+                        vertex_buffer,
+                        index_buffer,
+                        picking_program,
+                        &picking_uniform,
+                        params,
+                    )
+                    .unwrap();
+            }
+            stats.draw_calls += 1;
+        }
+    }
+}
+
+// the lights of a draw as the light_* uniform arrays of basic.fs, followed by the other uniforms
+struct LightUniforms<'l, R: Uniforms> {
+    lights: Vec<&'l SceneLight>,
+    rest: R,
+}
+
+impl<'l, R: Uniforms> Uniforms for LightUniforms<'l, R> {
+    fn visit_values<'a, F: FnMut(&str, UniformValue<'a>)>(&'a self, mut output: F) {
+        output(
+            "light_count",
+            UniformValue::SignedInt(self.lights.len() as i32),
+        );
+
+        for (index, light) in self.lights.iter().enumerate() {
+            let kind = match light.kind {
+                LightKind::Directional => 0,
+                LightKind::Point => 1,
+                LightKind::Spot => 2,
+            };
+
+            // the shader blends between the cone cosines, they can't be equal
+            let inner = light.inner_angle.cos();
+            let outer = light.outer_angle.cos().min(inner - 0.0001);
+
+            let name = |uniform: &str| format!("{}[{}]", uniform, index);
+            output(&name("light_kind"), UniformValue::SignedInt(kind));
+            output(
+                &name("light_position"),
+                UniformValue::Vec3(light.position.raw()),
+            );
+            output(
+                &name("light_direction"),
+                UniformValue::Vec3(light.direction.raw()),
+            );
+            output(&name("light_color"), UniformValue::Vec3(light.radiance()));
+            output(&name("light_range"), UniformValue::Float(light.range));
+            output(
+                &name("light_attenuation"),
+                UniformValue::Vec3(light.attenuation),
+            );
+            output(&name("light_cone"), UniformValue::Vec2([inner, outer]));
+        }
+
+        self.rest.visit_values(output);
+    }
+}
+
+// every item on its own, used when instancing is off
+fn single_batches(count: usize) -> Vec<Range<usize>> {
+    (0..count).map(|index| index..index + 1).collect()
+}
+
+// the gpu buffers of a model, None when running without a display
+fn model_buffers(
+    resources: &ResourceContext,
+    model: Resource,
+) -> Option<(&glium::VertexBuffer<ModelVertex>, &glium::IndexBuffer<u16>)> {
+    let model = resources.get_model_ref(model);
+
+    match (model.vertex_buffer.as_ref(), model.index_buffer.as_ref()) {
+        (Some(vertex_buffer), Some(index_buffer)) => Some((vertex_buffer, index_buffer)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // new loads the picking shaders from the asset pack, the tests only need the sizes
+    fn headless_context(width: u32, height: u32) -> RenderContext {
+        RenderContext {
+            clear_color: [0.0, 0.0, 0.0],
+            ambient_light: [0.0, 0.0, 0.0],
+            camera: CameraState::new(width as i32, height as i32),
+            viewport: Viewport::full(),
+            layer_mask: ALL_LAYERS,
+            cameras: Vec::new(),
+            picked_object: None,
+            frustum_culling: true,
+            instancing: true,
+            stats: RenderStats::default(),
+            window_width: width,
+            window_height: height,
+            picking_attachments: None,
+            picking_pbo: None,
+            picking_program: None,
+            picking_entities: Vec::new(),
+        }
+    }
+
+    fn rect(left: u32, bottom: u32, width: u32, height: u32) -> glium::Rect {
+        glium::Rect {
+            left: left,
+            bottom: bottom,
+            width: width,
+            height: height,
+        }
+    }
+
+    #[test]
+    fn viewport_rects_start_at_the_bottom_left() {
+        assert_eq!(Viewport::full().to_rect(800, 600), rect(0, 0, 800, 600));
+
+        // the top of the viewport is the top of the window, so its rect is at the top too
+        let top = Viewport::new(0.25, 0.0, 0.5, 0.25);
+        assert_eq!(top.to_rect(800, 600), rect(200, 450, 400, 150));
+
+        let bottom_right = Viewport::new(0.5, 0.5, 0.5, 0.5);
+        assert_eq!(bottom_right.to_rect(800, 600), rect(400, 0, 400, 300));
+    }
+
+    #[test]
+    fn window_pixels_are_made_local_to_the_viewport() {
+        let viewport = Viewport::new(0.5, 0.5, 0.5, 0.5);
+
+        // window pixels have their origin at the top left like the viewport
+        assert_eq!(viewport.to_local(400.0, 300.0, 800, 600), Some([0.0, 0.0]));
+        assert_eq!(
+            viewport.to_local(799.0, 599.0, 800, 600),
+            Some([399.0, 299.0])
+        );
+
+        assert_eq!(viewport.to_local(399.0, 300.0, 800, 600), None);
+        assert_eq!(viewport.to_local(400.0, 299.0, 800, 600), None);
+        assert_eq!(viewport.to_local(800.0, 300.0, 800, 600), None);
+        assert_eq!(viewport.to_local(400.0, 600.0, 800, 600), None);
+    }
+
+    #[test]
+    fn high_layers_are_only_in_the_full_mask() {
+        assert!(in_layer_mask(1 << 3, 3));
+        assert!(!in_layer_mask(1 << 3, 4));
+        assert!(in_layer_mask(ALL_LAYERS, 0));
+        assert!(in_layer_mask(ALL_LAYERS, 31));
+
+        for &layer in &[32, 33, 64, 1000, u32::max_value()] {
+            assert!(in_layer_mask(ALL_LAYERS, layer), "{}", layer);
+            assert!(!in_layer_mask(ALL_LAYERS & !1, layer), "{}", layer);
+            assert!(!in_layer_mask(0, layer), "{}", layer);
+        }
+    }
+
+    #[test]
+    fn target_cameras_follow_the_target_size() {
+        let mut resources = ResourceContext::new();
+        let mut render = headless_context(800, 600);
+        render.viewport = Viewport::new(0.0, 0.0, 0.5, 1.0);
+
+        let target = RenderTarget::new(None, &mut resources, 256, 128);
+        let target_camera =
+            render.add_camera(RenderCamera::new(CameraState::new(800, 600)).with_target(target));
+        let window_camera = render.add_camera(
+            RenderCamera::new(CameraState::new(800, 600))
+                .with_viewport(Viewport::new(0.5, 0.0, 0.5, 0.5)),
+        );
+
+        assert_eq!(
+            render
+                .get_camera(target_camera)
+                .unwrap()
+                .camera
+                .get_screen_size(),
+            (256, 128)
+        );
+        assert_eq!(
+            render
+                .get_camera(window_camera)
+                .unwrap()
+                .camera
+                .get_screen_size(),
+            (400, 300)
+        );
+
+        render.window_width = 1000;
+        render.window_height = 500;
+        render.sync_camera_sizes();
+
+        assert_eq!(render.camera.get_screen_size(), (500, 500));
+        // the window size doesn't change the aspect ratio of a render target camera
+        assert_eq!(
+            render
+                .get_camera(target_camera)
+                .unwrap()
+                .camera
+                .get_screen_size(),
+            (256, 128)
+        );
+        assert_eq!(
+            render
+                .get_camera(window_camera)
+                .unwrap()
+                .camera
+                .get_screen_size(),
+            (500, 250)
+        );
+
+        // a viewport of the target is a part of the target, not of the window
+        render.get_camera_mut(target_camera).unwrap().viewport = Viewport::new(0.0, 0.0, 0.5, 1.0);
+        render.sync_camera_sizes();
+        assert_eq!(
+            render
+                .get_camera(target_camera)
+                .unwrap()
+                .camera
+                .get_screen_size(),
+            (128, 128)
+        );
     }
 }
@@ -1,5 +1,4 @@
 extern crate glium;
-use gameobject::GameObject;
 use model::Model;
 use std::collections::HashMap;
 
@@ -13,10 +12,10 @@ struct GlyphIdx {
 }
 
 pub struct ResourceContext {
-    texture_resources: Vec<glium::texture::SrgbTexture2d>,
-    shader_resources: Vec<glium::Program>,
+    // textures and shaders are None when running without a display
+    texture_resources: Vec<Option<glium::texture::SrgbTexture2d>>,
+    shader_resources: Vec<Option<glium::Program>>,
     model_resources: Vec<Model>,
-    gameobject_resources: Vec<GameObject>,
 
     glyph_storage: HashMap<GlyphIdx, Resource>,
 }
@@ -28,7 +27,6 @@ impl ResourceContext {
             texture_resources: Vec::new(),
             shader_resources: Vec::new(),
             model_resources: Vec::new(),
-            gameobject_resources: Vec::new(),
             glyph_storage: HashMap::new(),
         }
     }
@@ -69,13 +67,13 @@ impl ResourceContext {
         return None;
     }
 
-    pub fn alloc_tex(&mut self, tex: glium::texture::SrgbTexture2d) -> Resource {
+    pub fn alloc_tex(&mut self, tex: Option<glium::texture::SrgbTexture2d>) -> Resource {
         self.texture_resources.push(tex);
 
         return self.texture_resources.len() - 1;
     }
 
-    pub fn alloc_shader(&mut self, shader: glium::Program) -> Resource {
+    pub fn alloc_shader(&mut self, shader: Option<glium::Program>) -> Resource {
         self.shader_resources.push(shader);
 
         return self.shader_resources.len() - 1;
@@ -87,41 +85,35 @@ impl ResourceContext {
         return self.model_resources.len() - 1;
     }
 
-    pub fn alloc_gameobject(&mut self, gameobject: GameObject) -> Resource {
-        self.gameobject_resources.push(gameobject);
-
-        return self.gameobject_resources.len() - 1;
-    }
-
     pub fn get_tex_ref_mut(&mut self, id: Resource) -> &mut glium::texture::SrgbTexture2d {
-        return &mut self.texture_resources[id];
+        return self.texture_resources[id]
+            .as_mut()
+            .expect("texture was loaded without a display");
     }
 
     pub fn get_shader_ref_mut(&mut self, id: Resource) -> &mut glium::Program {
-        return &mut self.shader_resources[id];
+        return self.shader_resources[id]
+            .as_mut()
+            .expect("shader was loaded without a display");
     }
 
     pub fn get_model_ref_mut(&mut self, id: Resource) -> &mut Model {
         return &mut self.model_resources[id];
     }
 
-    pub fn get_gameobject_ref_mut(&mut self, id: Resource) -> &mut GameObject {
-        return &mut self.gameobject_resources[id];
-    }
-
     pub fn get_tex_ref(&self, id: Resource) -> &glium::texture::SrgbTexture2d {
-        return &self.texture_resources[id];
+        return self.texture_resources[id]
+            .as_ref()
+            .expect("texture was loaded without a display");
     }
 
     pub fn get_shader_ref(&self, id: Resource) -> &glium::Program {
-        return &self.shader_resources[id];
+        return self.shader_resources[id]
+            .as_ref()
+            .expect("shader was loaded without a display");
     }
 
     pub fn get_model_ref(&self, id: Resource) -> &Model {
         return &self.model_resources[id];
     }
-
-    pub fn get_gameobject_ref(&self, id: Resource) -> &GameObject {
-        return &self.gameobject_resources[id];
-    }
 }
@@ -0,0 +1,377 @@
+extern crate glium;
+
+use app::{App, Engine};
+use colored::*;
+use ecs;
+use glium::glutin;
+use recording::{InputPlayback, InputRecording, RecordedEvent};
+use renderer;
+
+pub struct RunnerConfig {
+    pub title: String,
+    pub width: i32,
+    pub height: i32,
+    pub vsync: bool,
+
+    // save the input of the session to this file when the runner exits
+    pub record_input: Option<String>,
+    // play the input back from this file instead of the live input until it runs out
+    pub replay_input: Option<String>,
+}
+
+impl RunnerConfig {
+    pub fn new(title: &str, width: i32, height: i32) -> RunnerConfig {
+        RunnerConfig {
+            title: title.to_string(),
+            width: width,
+            height: height,
+            vsync: true,
+            record_input: None,
+            replay_input: None,
+        }
+    }
+}
+
+pub struct HeadlessConfig {
+    pub width: i32,
+    pub height: i32,
+    pub delta_time: f32,
+    pub frames: u64,
+
+    // when set every frame uses the recorded frame time and input, stopping once it runs out
+    pub replay_input: Option<InputRecording>,
+}
+
+impl HeadlessConfig {
+    // run a fixed number of frames at 60 fps
+    pub fn new(frames: u64) -> HeadlessConfig {
+        HeadlessConfig {
+            width: 1024,
+            height: 768,
+            delta_time: 1.0 / 60.0,
+            frames: frames,
+            replay_input: None,
+        }
+    }
+
+    pub fn with_replay(mut self, recording: InputRecording) -> HeadlessConfig {
+        self.replay_input = Some(recording);
+        self
+    }
+}
+
+// open a window and run the app until the window is closed or the app asks to quit
+pub fn run<A: App>(mut app: A, config: RunnerConfig) {
+    println!("Starting game");
+
+    // Create A window
+    let mut events_loop = glutin::EventsLoop::new();
+    let window = glutin::WindowBuilder::new()
+        .with_title(config.title.clone())
+        .with_dimensions(glutin::dpi::LogicalSize::new(
+            config.width as f64,
+            config.height as f64,
+        ));
+
+    let glutin_context = glutin::ContextBuilder::new().with_vsync(config.vsync);
+    let display = glium::Display::new(window, glutin_context, &events_loop).unwrap();
+
+    let mut engine = Engine::new(display, config.width, config.height);
+
+    app.init(&mut engine);
+
+    let mut recording = config.record_input.as_ref().map(|_| InputRecording::new());
+    let mut playback = config.replay_input.as_ref().map(|path| {
+        InputPlayback::new(InputRecording::load(path).expect("Failed to load input recording"))
+    });
+
+    // Game Loop
+    let mut closed = false;
+    while !closed && !engine.quit {
+        let replay_frame = match playback {
+            Some(ref mut playback) => playback.next_frame().cloned(),
+            None => None,
+        };
+        let replaying = replay_frame.is_some();
+
+        // a replayed frame runs with the recorded frame time so the simulation matches the recording
+        match replay_frame {
+            Some(ref frame) => engine.time.advance(frame.delta_time),
+            None => engine.time.tick(),
+        }
+
+        if let Some(ref mut recording) = recording {
+            recording.begin_frame(engine.time.unscaled_delta_time());
+        }
+
+        update_frame(&mut app, &mut engine);
+
+        let mut target = engine.display.as_ref().unwrap().draw();
+
+        renderer::render(
+            &mut engine.render,
+            &engine.entities,
+            &mut engine.resources,
+            &mut target,
+            engine.display.as_ref().unwrap(),
+            engine.cursor_position,
+        );
+
+        app.render_gui(&mut engine);
+        engine.editor.render_editor(
+            &mut engine.ui,
+            &engine.entities,
+            &engine.time,
+            &engine.render.stats,
+        );
+
+        engine.ui.draw_frame(
+            &mut engine.resources,
+            &mut target,
+            engine.display.as_ref().unwrap(),
+        );
+
+        target.finish().unwrap();
+
+        if let Some((locked, visible)) = engine.input.take_cursor_request() {
+            apply_cursor_request(engine.display.as_ref().unwrap(), locked, visible);
+        }
+
+        engine.input.start_event_proc();
+
+        if let Some(frame) = replay_frame {
+            for event in &frame.events {
+                if let Some(ref mut recording) = recording {
+                    recording.push(*event);
+                }
+                replay_event(&mut engine, event);
+            }
+        }
+
+        // pads are polled even during a replay so their events don't pile up in the backend
+        for event in engine.input.poll_gamepads() {
+            if replaying {
+                continue;
+            }
+
+            if let Some(ref mut recording) = recording {
+                recording.push(RecordedEvent::Gamepad(event));
+            }
+            engine.input.process_gamepad_event(&event);
+        }
+
+        events_loop.poll_events(|ev| {
+            // live input is ignored during a replay, window events like resizing still go through
+            if replaying && RecordedEvent::from_event(&ev).is_some() {
+                return;
+            }
+
+            if let Some(ref mut recording) = recording {
+                recording.record(&ev);
+            }
+
+            if dispatch_event(&mut app, &mut engine, &ev) {
+                closed = true;
+            }
+        });
+    }
+
+    app.shutdown(&mut engine);
+
+    if let (Some(recording), Some(path)) = (recording, config.record_input) {
+        match recording.save(&path) {
+            Ok(_) => println!("Saved input recording to {}", path),
+            Err(err) => println!(
+                "{}",
+                format!("Failed to save input recording to {}: {}", path, err).red()
+            ),
+        }
+    }
+}
+
+// run the game loop without a window or gl context for a fixed number of frames,
+// the engine is handed back afterwards so the scene can be inspected
+pub fn run_headless<A: App>(app: &mut A, config: HeadlessConfig) -> Engine {
+    run_headless_until(app, config, |_, _| false)
+}
+
+// same as run_headless, but stops early once should_stop returns true
+pub fn run_headless_until<A: App, F: FnMut(&A, &Engine) -> bool>(
+    app: &mut A,
+    config: HeadlessConfig,
+    mut should_stop: F,
+) -> Engine {
+    let mut engine = Engine::new_headless(config.width, config.height);
+
+    app.init(&mut engine);
+
+    let mut playback = config.replay_input.map(InputPlayback::new);
+
+    for _ in 0..config.frames {
+        if engine.quit || should_stop(app, &engine) {
+            break;
+        }
+
+        let replay_frame = match playback {
+            Some(ref mut playback) => match playback.next_frame() {
+                Some(frame) => Some(frame.clone()),
+                // the recording has run out
+                None => break,
+            },
+            None => None,
+        };
+
+        let replaying = replay_frame.is_some();
+
+        let delta_time = match replay_frame {
+            Some(ref frame) => frame.delta_time,
+            None => config.delta_time,
+        };
+        engine.time.advance(delta_time);
+
+        update_frame(app, &mut engine);
+
+        // lay out the ui so buttons and editor logic run, there is just nothing to draw it to
+        app.render_gui(&mut engine);
+        engine.editor.render_editor(
+            &mut engine.ui,
+            &engine.entities,
+            &engine.time,
+            &engine.render.stats,
+        );
+        engine.ui.end_frame();
+
+        engine.input.start_event_proc();
+
+        if let Some(frame) = replay_frame {
+            for event in &frame.events {
+                replay_event(&mut engine, event);
+            }
+        }
+
+        // headless engines have no gamepad backend unless the app sets one, like a fake backend in a test
+        for event in engine.input.poll_gamepads() {
+            if !replaying {
+                engine.input.process_gamepad_event(&event);
+            }
+        }
+    }
+
+    app.shutdown(&mut engine);
+
+    engine
+}
+
+// everything in a frame that does not touch the window, shared by both runners
+fn update_frame<A: App>(app: &mut A, engine: &mut Engine) {
+    engine
+        .input
+        .update_shortcuts(engine.time.unscaled_delta_time());
+
+    for _ in 0..engine.physics.fixed_steps(engine.time.delta_time()) {
+        app.fixed_update(engine);
+        ecs::physics_pre_step_system(&mut engine.entities, &mut engine.physics);
+        engine.physics.step();
+    }
+
+    app.update(engine);
+    engine.editor.update(
+        &mut engine.render,
+        &mut engine.entities,
+        &mut engine.input,
+        &mut engine.time,
+    );
+
+    // rebuild prefab instances so edits to the prefab files show up
+    if engine.input.shortcut_triggered("reload_prefabs") {
+        let remap = engine.prefabs.reload_instances(
+            engine.display.as_ref(),
+            &mut engine.resources,
+            &mut engine.physics,
+            &mut engine.entities,
+        );
+        engine.editor.remap_selection(&engine.entities, &remap);
+    }
+
+    ecs::physics_system(&mut engine.entities, &mut engine.physics);
+    ecs::camera_system(&engine.entities, &mut engine.render);
+    ecs::scene_tree_system(&mut engine.entities);
+    engine.entities.maintain(&mut engine.physics);
+}
+
+fn apply_cursor_request(display: &glium::Display, locked: bool, visible: bool) {
+    let window = display.gl_window();
+
+    if let Err(err) = window.grab_cursor(locked) {
+        println!("{}", format!("Failed to lock the cursor: {}", err).red());
+    }
+    window.hide_cursor(!visible);
+}
+
+fn replay_event(engine: &mut Engine, event: &RecordedEvent) {
+    event.apply(&mut engine.input);
+
+    match *event {
+        RecordedEvent::CursorMoved(x, y) => {
+            engine.cursor_position = Some((x as i32, y as i32));
+            engine.ui.mouse_x = x as f32;
+            engine.ui.mouse_y = y as f32;
+        }
+        RecordedEvent::MouseButton(button, pressed) => match button {
+            glutin::MouseButton::Left => engine.ui.left_mouse_down = pressed,
+            glutin::MouseButton::Right => engine.ui.right_mouse_down = pressed,
+            _ => (),
+        },
+        _ => (),
+    }
+}
+
+// hand an event to the app and the engine, returns true when the window should close
+fn dispatch_event<A: App>(app: &mut A, engine: &mut Engine, event: &glutin::Event) -> bool {
+    app.on_event(engine, event);
+
+    match *event {
+        glutin::Event::WindowEvent { ref event, .. } => process_window_event(engine, event),
+        glutin::Event::DeviceEvent { ref event, .. } => {
+            engine.input.process_device_events(event);
+            false
+        }
+        _ => false,
+    }
+}
+
+// feed a window event to the engine contexts, returns true when the window should close
+fn process_window_event(engine: &mut Engine, event: &glutin::WindowEvent) -> bool {
+    match *event {
+        glutin::WindowEvent::CloseRequested => return true,
+        glutin::WindowEvent::Resized(logical_size) => {
+            let width = logical_size.width as i32;
+            let height = logical_size.height as i32;
+
+            engine
+                .render
+                .resized(engine.display.as_ref().unwrap(), width, height);
+            engine.ui.screen_resize(width as f32, height as f32);
+        }
+        glutin::WindowEvent::CursorMoved { position, .. } => {
+            engine.input.process_events(event);
+
+            engine.cursor_position = Some((position.x as i32, position.y as i32));
+            engine.ui.mouse_x = position.x as f32;
+            engine.ui.mouse_y = position.y as f32;
+        }
+        glutin::WindowEvent::MouseInput { button, state, .. } => {
+            engine.input.process_events(event);
+
+            let pressed = state == glutin::ElementState::Pressed;
+            match button {
+                glutin::MouseButton::Left => engine.ui.left_mouse_down = pressed,
+                glutin::MouseButton::Right => engine.ui.right_mouse_down = pressed,
+                _ => (),
+            }
+        }
+        _ => engine.input.process_events(event),
+    }
+
+    false
+}
@@ -0,0 +1,552 @@
+// dynamic bounding volume tree over the world bounds of the entities, used by culling, picking and
+// other scene queries instead of scanning every entity. leaves store their box grown by a margin
+// so objects that move a little, like physics bodies settling, don't have to be reinserted every
+// frame. queries return candidates whose enlarged box matches, callers do the exact test
+use bounds::{Aabb, Frustum};
+use camera::Ray;
+use ecs::Entity;
+use std::collections::HashMap;
+use vector::Vector3;
+
+#[derive(Copy, Clone)]
+struct Node {
+    bounds: Aabb,
+    parent: Option<usize>,
+    // internal nodes always have two children, leaves have an entity
+    children: Option<[usize; 2]>,
+    entity: Option<Entity>,
+}
+
+pub struct SceneTree {
+    nodes: Vec<Node>,
+    free_nodes: Vec<usize>,
+    root: Option<usize>,
+    leaves: HashMap<Entity, usize>,
+
+    // how far the stored boxes are grown in every direction
+    pub margin: f32,
+}
+
+#[allow(dead_code)]
+impl SceneTree {
+    pub fn new() -> SceneTree {
+        SceneTree {
+            nodes: Vec::new(),
+            free_nodes: Vec::new(),
+            root: None,
+            leaves: HashMap::new(),
+            margin: 0.2,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.leaves.contains_key(&entity)
+    }
+
+    pub fn entities(&self) -> Vec<Entity> {
+        self.leaves.keys().cloned().collect()
+    }
+
+    // the enlarged box stored for the entity
+    pub fn get_bounds(&self, entity: Entity) -> Option<Aabb> {
+        self.leaves
+            .get(&entity)
+            .map(|leaf| self.nodes[*leaf].bounds)
+    }
+
+    pub fn insert(&mut self, entity: Entity, bounds: Aabb) {
+        self.remove(entity);
+
+        let leaf = self.alloc_node(Node {
+            bounds: bounds.expanded(self.margin),
+            parent: None,
+            children: None,
+            entity: Some(entity),
+        });
+
+        self.insert_leaf(leaf);
+        self.leaves.insert(entity, leaf);
+    }
+
+    pub fn remove(&mut self, entity: Entity) -> bool {
+        match self.leaves.remove(&entity) {
+            Some(leaf) => {
+                self.remove_leaf(leaf);
+                self.free_nodes.push(leaf);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // move an entity, it is only reinserted once it leaves its enlarged box. returns true when
+    // the tree changed
+    pub fn update(&mut self, entity: Entity, bounds: Aabb) -> bool {
+        let leaf = match self.leaves.get(&entity) {
+            Some(leaf) => *leaf,
+            None => {
+                self.insert(entity, bounds);
+                return true;
+            }
+        };
+
+        if self.nodes[leaf].bounds.contains(&bounds) {
+            return false;
+        }
+
+        self.remove_leaf(leaf);
+        self.nodes[leaf].bounds = bounds.expanded(self.margin);
+        self.insert_leaf(leaf);
+
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.free_nodes.clear();
+        self.leaves.clear();
+        self.root = None;
+    }
+
+    pub fn query_aabb(&self, bounds: &Aabb) -> Vec<Entity> {
+        self.query(|node_bounds| node_bounds.intersects(bounds))
+    }
+
+    pub fn query_sphere(&self, center: Vector3, radius: f32) -> Vec<Entity> {
+        self.query(|node_bounds| node_bounds.intersects_sphere(center, radius))
+    }
+
+    pub fn query_frustum(&self, frustum: &Frustum) -> Vec<Entity> {
+        self.query(|node_bounds| frustum.intersects_aabb(node_bounds))
+    }
+
+    // entities whose box the ray enters within max_distance, closest box first
+    pub fn query_ray(&self, ray: &Ray, max_distance: f32) -> Vec<(Entity, f32)> {
+        let mut hits = Vec::new();
+
+        self.walk(
+            |node_bounds| node_bounds.intersect_ray(ray, max_distance).is_some(),
+            |entity, node_bounds| {
+                if let Some(distance) = node_bounds.intersect_ray(ray, max_distance) {
+                    hits.push((entity, distance));
+                }
+            },
+        );
+
+        hits.sort_by(|a, b| a.1.total_cmp(&b.1));
+        hits
+    }
+
+    // the entity whose box is closest to the point, within max_distance
+    pub fn nearest(&self, point: Vector3, max_distance: f32) -> Option<Entity> {
+        let mut best: Option<(Entity, f32)> = None;
+
+        for entity in self.query_sphere(point, max_distance) {
+            let bounds = self.nodes[self.leaves[&entity]].bounds;
+            let closest = Vector3::new(
+                point.x.max(bounds.min.x).min(bounds.max.x),
+                point.y.max(bounds.min.y).min(bounds.max.y),
+                point.z.max(bounds.min.z).min(bounds.max.z),
+            );
+            let distance = (closest - point).length();
+
+            let closer = match best {
+                Some((_, best_distance)) => distance < best_distance,
+                None => true,
+            };
+            if closer {
+                best = Some((entity, distance));
+            }
+        }
+
+        best.map(|(entity, _)| entity)
+    }
+
+    fn query<F: Fn(&Aabb) -> bool>(&self, test: F) -> Vec<Entity> {
+        let mut found = Vec::new();
+        self.walk(&test, |entity, _| found.push(entity));
+        found
+    }
+
+    // visit every leaf whose box and parent boxes pass the test
+    fn walk<T: Fn(&Aabb) -> bool, V: FnMut(Entity, &Aabb)>(&self, test: T, mut visit: V) {
+        let mut stack = Vec::new();
+        if let Some(root) = self.root {
+            stack.push(root);
+        }
+
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !test(&node.bounds) {
+                continue;
+            }
+
+            match node.children {
+                Some(children) => stack.extend_from_slice(&children),
+                None => visit(node.entity.unwrap(), &node.bounds),
+            }
+        }
+    }
+
+    fn alloc_node(&mut self, node: Node) -> usize {
+        match self.free_nodes.pop() {
+            Some(index) => {
+                self.nodes[index] = node;
+                index
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    // walks down to the sibling that grows the tree's surface area the least and pairs the
+    // leaf with it under a new parent
+    fn insert_leaf(&mut self, leaf: usize) {
+        let mut index = match self.root {
+            Some(root) => root,
+            None => {
+                self.nodes[leaf].parent = None;
+                self.root = Some(leaf);
+                return;
+            }
+        };
+
+        let leaf_bounds = self.nodes[leaf].bounds;
+
+        while let Some([a, b]) = self.nodes[index].children {
+            let area = self.nodes[index].bounds.surface_area();
+            let combined_area = self.nodes[index].bounds.union(&leaf_bounds).surface_area();
+
+            // cost of making a new parent here, and what every level below has to pay for
+            // this node growing
+            let cost = 2.0 * combined_area;
+            let inherited = 2.0 * (combined_area - area);
+
+            let cost_a = self.descend_cost(a, &leaf_bounds) + inherited;
+            let cost_b = self.descend_cost(b, &leaf_bounds) + inherited;
+
+            if cost < cost_a && cost < cost_b {
+                break;
+            }
+
+            index = if cost_a < cost_b { a } else { b };
+        }
+
+        let sibling = index;
+        let old_parent = self.nodes[sibling].parent;
+        let new_parent = self.alloc_node(Node {
+            bounds: self.nodes[sibling].bounds.union(&leaf_bounds),
+            parent: old_parent,
+            children: Some([sibling, leaf]),
+            entity: None,
+        });
+
+        self.nodes[sibling].parent = Some(new_parent);
+        self.nodes[leaf].parent = Some(new_parent);
+
+        match old_parent {
+            Some(parent) => self.replace_child(parent, sibling, new_parent),
+            None => self.root = Some(new_parent),
+        }
+
+        self.refit(old_parent);
+    }
+
+    fn descend_cost(&self, index: usize, leaf_bounds: &Aabb) -> f32 {
+        let node = &self.nodes[index];
+        let combined_area = node.bounds.union(leaf_bounds).surface_area();
+
+        if node.children.is_none() {
+            combined_area
+        } else {
+            combined_area - node.bounds.surface_area()
+        }
+    }
+
+    // unlinks the leaf and frees its parent, the leaf node itself is kept
+    fn remove_leaf(&mut self, leaf: usize) {
+        if self.root == Some(leaf) {
+            self.root = None;
+            return;
+        }
+
+        let parent = self.nodes[leaf].parent.unwrap();
+        let [a, b] = self.nodes[parent].children.unwrap();
+        let sibling = if a == leaf { b } else { a };
+        let grandparent = self.nodes[parent].parent;
+
+        self.nodes[sibling].parent = grandparent;
+        match grandparent {
+            Some(grandparent) => self.replace_child(grandparent, parent, sibling),
+            None => self.root = Some(sibling),
+        }
+
+        self.free_nodes.push(parent);
+        self.nodes[leaf].parent = None;
+
+        self.refit(grandparent);
+    }
+
+    fn replace_child(&mut self, parent: usize, old_child: usize, new_child: usize) {
+        if let Some(ref mut children) = self.nodes[parent].children {
+            for child in children.iter_mut() {
+                if *child == old_child {
+                    *child = new_child;
+                }
+            }
+        }
+    }
+
+    // recompute the boxes from a node up to the root
+    fn refit(&mut self, mut index: Option<usize>) {
+        while let Some(current) = index {
+            if let Some([a, b]) = self.nodes[current].children {
+                self.nodes[current].bounds = self.nodes[a].bounds.union(&self.nodes[b].bounds);
+            }
+
+            index = self.nodes[current].parent;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use camera::CameraState;
+    use quaternion::Quaternion;
+
+    const WORLD_SIZE: f32 = 100.0;
+
+    // same generator as the bench so the scenes are reproducible
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn next(&mut self) -> f32 {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (self.0 >> 40) as f32 / (1u64 << 24) as f32
+        }
+
+        fn point(&mut self) -> Vector3 {
+            Vector3::new(
+                self.next() * WORLD_SIZE,
+                self.next() * WORLD_SIZE,
+                self.next() * WORLD_SIZE,
+            )
+        }
+    }
+
+    fn entity(index: usize) -> Entity {
+        Entity::new(index, 0)
+    }
+
+    fn unit_box(center: Vector3) -> Aabb {
+        Aabb::from_center(center, Vector3::new(0.5, 0.5, 0.5))
+    }
+
+    // a tree with every other entity removed again, so freed nodes get reused
+    fn random_tree(random: &mut Lcg) -> SceneTree {
+        let mut tree = SceneTree::new();
+        for index in 0..400 {
+            let size = Vector3::new(random.next(), random.next(), random.next()) * 3.0;
+            tree.insert(entity(index), Aabb::from_center(random.point(), size));
+        }
+        for index in (0..400).filter(|index| index % 2 == 0) {
+            assert!(tree.remove(entity(index)));
+        }
+        for index in 400..500 {
+            tree.insert(entity(index), unit_box(random.point()));
+        }
+
+        tree
+    }
+
+    // every entity whose stored box passes the test
+    fn brute_force<F: Fn(&Aabb) -> bool>(tree: &SceneTree, test: F) -> Vec<Entity> {
+        let mut found: Vec<Entity> = tree
+            .entities()
+            .into_iter()
+            .filter(|&entity| test(&tree.get_bounds(entity).unwrap()))
+            .collect();
+        found.sort();
+        found
+    }
+
+    fn box_distance(bounds: &Aabb, point: Vector3) -> f32 {
+        let closest = Vector3::new(
+            point.x.max(bounds.min.x).min(bounds.max.x),
+            point.y.max(bounds.min.y).min(bounds.max.y),
+            point.z.max(bounds.min.z).min(bounds.max.z),
+        );
+        (closest - point).length()
+    }
+
+    fn sorted(mut entities: Vec<Entity>) -> Vec<Entity> {
+        entities.sort();
+        entities
+    }
+
+    #[test]
+    fn insert_and_remove() {
+        let mut tree = SceneTree::new();
+        let bounds = unit_box(Vector3::new(1.0, 2.0, 3.0));
+
+        tree.insert(entity(0), bounds);
+        tree.insert(entity(1), bounds);
+        tree.insert(entity(0), bounds);
+
+        assert_eq!(tree.len(), 2);
+        assert!(tree.contains(entity(0)));
+        assert!(!tree.contains(entity(2)));
+        assert!(tree.get_bounds(entity(0)).unwrap().contains(&bounds));
+
+        assert!(tree.remove(entity(0)));
+        assert!(!tree.remove(entity(0)));
+        assert_eq!(tree.entities(), vec![entity(1)]);
+
+        assert!(tree.remove(entity(1)));
+        assert!(tree.is_empty());
+        assert!(tree.query_aabb(&bounds).is_empty());
+    }
+
+    #[test]
+    fn update_keeps_small_moves_inside_the_margin() {
+        let mut tree = SceneTree::new();
+        tree.margin = 0.5;
+
+        tree.insert(entity(0), unit_box(Vector3::new(0.0, 0.0, 0.0)));
+        let stored = tree.get_bounds(entity(0)).unwrap();
+
+        // inside the enlarged box, nothing changes
+        assert!(!tree.update(entity(0), unit_box(Vector3::new(0.4, 0.0, 0.0))));
+        assert_eq!(
+            tree.get_bounds(entity(0)).unwrap().min.raw(),
+            stored.min.raw()
+        );
+
+        // out of it, the box is grown around the new position
+        assert!(tree.update(entity(0), unit_box(Vector3::new(2.0, 0.0, 0.0))));
+        let moved = tree.get_bounds(entity(0)).unwrap();
+        assert_eq!(moved.min.raw(), [1.0, -1.0, -1.0]);
+        assert_eq!(moved.max.raw(), [3.0, 1.0, 1.0]);
+
+        // unknown entities are inserted
+        assert!(tree.update(entity(1), unit_box(Vector3::new(0.0, 0.0, 0.0))));
+        assert_eq!(tree.len(), 2);
+    }
+
+    #[test]
+    fn aabb_and_sphere_queries_match_brute_force() {
+        let mut random = Lcg(7);
+        let tree = random_tree(&mut random);
+
+        for _ in 0..50 {
+            let region = Aabb::from_center(random.point(), Vector3::new(10.0, 10.0, 10.0));
+            assert_eq!(
+                sorted(tree.query_aabb(&region)),
+                brute_force(&tree, |bounds| bounds.intersects(&region))
+            );
+
+            let center = random.point();
+            let radius = random.next() * 20.0;
+            assert_eq!(
+                sorted(tree.query_sphere(center, radius)),
+                brute_force(&tree, |bounds| bounds.intersects_sphere(center, radius))
+            );
+        }
+    }
+
+    #[test]
+    fn frustum_query_matches_brute_force() {
+        let mut random = Lcg(11);
+        let tree = random_tree(&mut random);
+        let mut camera = CameraState::new(800, 600);
+
+        for _ in 0..20 {
+            let position = random.point();
+            camera.set_transform(position, Quaternion::new(0.0, 0.0, 0.0, 1.0));
+            camera.look_at(random.point());
+            let frustum = Frustum::from_matrices(camera.get_perspective(), camera.get_view());
+
+            assert_eq!(
+                sorted(tree.query_frustum(&frustum)),
+                brute_force(&tree, |bounds| frustum.intersects_aabb(bounds))
+            );
+        }
+    }
+
+    #[test]
+    fn ray_query_matches_brute_force_and_is_sorted() {
+        let mut random = Lcg(13);
+        let tree = random_tree(&mut random);
+
+        for _ in 0..50 {
+            let ray = Ray {
+                origin: random.point(),
+                direction: (random.point() - random.point()).normalized(),
+            };
+
+            let hits = tree.query_ray(&ray, WORLD_SIZE);
+            assert!(hits.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+            assert_eq!(
+                sorted(hits.iter().map(|&(entity, _)| entity).collect()),
+                brute_force(&tree, |bounds| bounds
+                    .intersect_ray(&ray, WORLD_SIZE)
+                    .is_some())
+            );
+        }
+    }
+
+    #[test]
+    fn nearest_matches_brute_force() {
+        let mut random = Lcg(17);
+        let tree = random_tree(&mut random);
+
+        for _ in 0..50 {
+            let point = random.point();
+            let distances: Vec<(Entity, f32)> = tree
+                .entities()
+                .into_iter()
+                .map(|entity| {
+                    (
+                        entity,
+                        box_distance(&tree.get_bounds(entity).unwrap(), point),
+                    )
+                })
+                .collect();
+            let closest = distances
+                .iter()
+                .cloned()
+                .filter(|&(_, distance)| distance <= 10.0)
+                .fold(None, |best: Option<(Entity, f32)>, candidate| match best {
+                    Some(best) if best.1 <= candidate.1 => Some(best),
+                    _ => Some(candidate),
+                });
+
+            match (tree.nearest(point, 10.0), closest) {
+                (Some(found), Some((_, distance))) => {
+                    let found_distance = box_distance(&tree.get_bounds(found).unwrap(), point);
+                    assert!((found_distance - distance).abs() < 1e-4);
+                }
+                (None, None) => {}
+                (found, expected) => panic!(
+                    "nearest found {:?}, expected {:?}",
+                    found,
+                    expected.map(|(entity, _)| entity)
+                ),
+            }
+        }
+    }
+}
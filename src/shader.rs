@@ -1,14 +1,86 @@
 extern crate glium;
 
+use std::cell::Cell;
 use std::str;
 
-pub fn load(display: &glium::Display, vs: &[u8], fs: &[u8]) -> glium::Program {
+// only used to ask glium whether buffers can be read per instance
+#[derive(Copy, Clone)]
+struct InstancingProbe {
+    probe: f32,
+}
+
+implement_vertex!(InstancingProbe, probe);
+
+thread_local! {
+    // the display and its driver stay the same while running, so they are only asked once
+    static INSTANCING_SUPPORTED: Cell<Option<bool>> = Cell::new(None);
+}
+
+// returns None when there is no display to compile for, which is the case when running headless.
+// INSTANCING is defined in both stages when the driver supports instanced arrays
+pub fn load(display: Option<&glium::Display>, vs: &[u8], fs: &[u8]) -> Option<glium::Program> {
+    let display = match display {
+        Some(display) => display,
+        None => return None,
+    };
+
+    let instancing = instancing_supported(display);
+
     let program = glium::Program::from_source(
         display,
-        &str::from_utf8(vs).unwrap(),
-        &str::from_utf8(fs).unwrap(),
+        &with_defines(str::from_utf8(vs).unwrap(), instancing),
+        &with_defines(str::from_utf8(fs).unwrap(), instancing),
         None,
     ).unwrap();
 
-    return program;
+    return Some(program);
+}
+
+// instanced arrays are core since gl 3.3 and an extension on older drivers
+pub fn instancing_supported(display: &glium::Display) -> bool {
+    INSTANCING_SUPPORTED.with(|supported| {
+        if let Some(supported) = supported.get() {
+            return supported;
+        }
+
+        let probe = glium::VertexBuffer::new(display, &[InstancingProbe { probe: 0.0 }]).unwrap();
+        let result = probe.per_instance().is_ok();
+
+        supported.set(Some(result));
+        result
+    })
+}
+
+// the defines have to go after the #version line
+fn with_defines(source: &str, instancing: bool) -> String {
+    if !instancing {
+        return source.to_string();
+    }
+
+    match source.find('\n') {
+        Some(end) if source.starts_with("#version") => {
+            format!("{}#define INSTANCING\n{}", &source[..end + 1], &source[end + 1..])
+        }
+        _ => format!("#define INSTANCING\n{}", source),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn instancing_define_goes_after_the_version() {
+        let source = "#version 140\nvoid main() {}";
+
+        assert_eq!(
+            with_defines(source, true),
+            "#version 140\n#define INSTANCING\nvoid main() {}"
+        );
+        assert_eq!(with_defines(source, false), source);
+        assert_eq!(
+            with_defines("void main() {}", true),
+            "#define INSTANCING\nvoid main() {}"
+        );
+    }
 }
@@ -0,0 +1,339 @@
+// shortcuts are written as chords separated by spaces, a chord being modifiers and a key or mouse
+// button joined by '+':
+//
+//   Ctrl+S
+//   Ctrl+Shift+D
+//   Shift+MouseLeft
+//   Ctrl+K Ctrl+C
+//
+// modifiers have to match exactly, so Ctrl+S does not fire a shortcut bound to S
+extern crate glium;
+
+use action_map::Binding;
+use colored::*;
+use glium::glutin;
+use input::{key_from_name, mouse_button_from_name};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Chord {
+    pub modifiers: glutin::ModifiersState,
+    pub trigger: Binding,
+}
+
+#[allow(dead_code)]
+impl Chord {
+    pub fn new(modifiers: glutin::ModifiersState, trigger: Binding) -> Chord {
+        Chord {
+            modifiers: modifiers,
+            trigger: trigger,
+        }
+    }
+
+    pub fn parse(chord: &str) -> Option<Chord> {
+        let mut modifiers = glutin::ModifiersState::default();
+        let mut trigger = None;
+
+        for part in chord.split('+') {
+            // the trigger has to be the last part
+            if trigger.is_some() {
+                return None;
+            }
+
+            match part.to_lowercase().as_str() {
+                "ctrl" | "control" => modifiers.ctrl = true,
+                "shift" => modifiers.shift = true,
+                "alt" => modifiers.alt = true,
+                "logo" | "super" | "cmd" => modifiers.logo = true,
+                _ => trigger = Some(parse_trigger(part)?),
+            }
+        }
+
+        trigger.map(|trigger| Chord::new(modifiers, trigger))
+    }
+}
+
+// "MouseLeft", "Mouse4" or a VirtualKeyCode name
+fn parse_trigger(name: &str) -> Option<Binding> {
+    if name.starts_with("Mouse") {
+        let button = name["Mouse".len()..].to_lowercase();
+        return mouse_button_from_name(&button).map(Binding::Mouse);
+    }
+
+    key_from_name(name).map(Binding::Key)
+}
+
+pub struct Shortcut {
+    pub name: String,
+    pub sequence: Vec<Chord>,
+}
+
+pub struct ShortcutRegistry {
+    shortcuts: Vec<Shortcut>,
+
+    // chords pressed so far of a multi chord shortcut
+    pending: Vec<Chord>,
+    pending_time: f32,
+    // seconds to wait for the next chord of a sequence
+    pub sequence_timeout: f32,
+
+    triggered: Vec<String>,
+}
+
+#[allow(dead_code)]
+impl ShortcutRegistry {
+    pub fn new() -> ShortcutRegistry {
+        ShortcutRegistry {
+            shortcuts: Vec::new(),
+            pending: Vec::new(),
+            pending_time: 0.0,
+            sequence_timeout: 1.0,
+            triggered: Vec::new(),
+        }
+    }
+
+    // the shortcuts the editor and runner use
+    pub fn default_shortcuts() -> ShortcutRegistry {
+        let mut registry = ShortcutRegistry::new();
+
+        registry.register("reload_prefabs", "F5");
+        registry.register("toggle_pause", "Pause");
+        registry.register("delete_selected", "Delete");
+        registry.register("deselect", "Escape");
+        registry.register("frame_selected", "F");
+        registry.register("toggle_camera_mode", "Tab");
+        registry.register("toggle_projection", "Numpad5");
+        registry.register("view_top", "Numpad7");
+        registry.register("view_front", "Numpad1");
+        registry.register("view_side", "Numpad3");
+
+        registry
+    }
+
+    // returns false when the shortcut could not be parsed
+    pub fn register(&mut self, name: &str, shortcut: &str) -> bool {
+        let sequence: Option<Vec<Chord>> = shortcut.split_whitespace().map(Chord::parse).collect();
+
+        match sequence {
+            Some(ref sequence) if !sequence.is_empty() => {
+                self.register_chords(name, sequence.clone());
+                true
+            }
+            _ => {
+                println!(
+                    "{}",
+                    format!("Invalid shortcut '{}' for {}", shortcut, name).red()
+                );
+                false
+            }
+        }
+    }
+
+    pub fn register_chords(&mut self, name: &str, sequence: Vec<Chord>) {
+        self.shortcuts.push(Shortcut {
+            name: name.to_string(),
+            sequence: sequence,
+        });
+    }
+
+    pub fn unregister(&mut self, name: &str) {
+        self.shortcuts.retain(|shortcut| shortcut.name != name);
+    }
+
+    pub fn shortcuts(&self) -> &[Shortcut] {
+        &self.shortcuts
+    }
+
+    // true on the frame the shortcut fired
+    pub fn triggered(&self, name: &str) -> bool {
+        self.triggered.iter().any(|triggered| triggered == name)
+    }
+
+    pub fn triggered_shortcuts(&self) -> &[String] {
+        &self.triggered
+    }
+
+    // the start of a sequence that is waiting for its next chord
+    pub fn pending(&self) -> &[Chord] {
+        &self.pending
+    }
+
+    // feed the chords pressed this frame, returns the chords that were used by a shortcut
+    pub fn update(&mut self, chords: &[Chord], delta_time: f32) -> Vec<Chord> {
+        self.triggered.clear();
+
+        if !self.pending.is_empty() {
+            self.pending_time += delta_time;
+
+            if self.pending_time > self.sequence_timeout {
+                self.pending.clear();
+            }
+        }
+
+        let mut consumed = Vec::new();
+
+        for chord in chords {
+            let mut sequence = self.pending.clone();
+            sequence.push(*chord);
+
+            // a chord that doesn't continue the pending sequence may still start a new one
+            if !self.matches_any(&sequence) {
+                sequence = vec![*chord];
+
+                if !self.matches_any(&sequence) {
+                    self.pending.clear();
+                    continue;
+                }
+            }
+
+            consumed.push(*chord);
+
+            let complete = self
+                .shortcuts
+                .iter()
+                .find(|shortcut| shortcut.sequence == sequence)
+                .map(|shortcut| shortcut.name.clone());
+
+            match complete {
+                Some(name) => {
+                    self.triggered.push(name);
+                    self.pending.clear();
+                }
+                None => {
+                    self.pending = sequence;
+                    self.pending_time = 0.0;
+                }
+            }
+        }
+
+        consumed
+    }
+
+    // true when the sequence is a shortcut or the start of one
+    fn matches_any(&self, sequence: &[Chord]) -> bool {
+        self.shortcuts
+            .iter()
+            .any(|shortcut| shortcut.sequence.starts_with(sequence))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn modifiers(ctrl: bool, shift: bool, alt: bool) -> glutin::ModifiersState {
+        glutin::ModifiersState {
+            ctrl: ctrl,
+            shift: shift,
+            alt: alt,
+            logo: false,
+        }
+    }
+
+    fn key_chord(ctrl: bool, key: glutin::VirtualKeyCode) -> Chord {
+        Chord::new(modifiers(ctrl, false, false), Binding::Key(key))
+    }
+
+    #[test]
+    fn chords_parse() {
+        assert_eq!(
+            Chord::parse("Ctrl+Shift+D"),
+            Some(Chord::new(
+                modifiers(true, true, false),
+                Binding::Key(glutin::VirtualKeyCode::D)
+            ))
+        );
+        assert_eq!(
+            Chord::parse("alt+MouseLeft"),
+            Some(Chord::new(
+                modifiers(false, false, true),
+                Binding::Mouse(glutin::MouseButton::Left)
+            ))
+        );
+        assert_eq!(
+            Chord::parse("Mouse4"),
+            Some(Chord::new(
+                modifiers(false, false, false),
+                Binding::Mouse(glutin::MouseButton::Other(4))
+            ))
+        );
+        assert_eq!(
+            Chord::parse("F5"),
+            Some(key_chord(false, glutin::VirtualKeyCode::F5))
+        );
+    }
+
+    #[test]
+    fn bad_chords_are_rejected() {
+        assert_eq!(Chord::parse("Ctrl"), None);
+        assert_eq!(Chord::parse("S+Ctrl"), None);
+        assert_eq!(Chord::parse("Ctrl+NotAKey"), None);
+        assert_eq!(Chord::parse(""), None);
+
+        let mut registry = ShortcutRegistry::new();
+        assert!(!registry.register("broken", "Ctrl+K Ctrl"));
+        assert!(registry.shortcuts().is_empty());
+    }
+
+    #[test]
+    fn modifiers_match_exactly() {
+        let mut registry = ShortcutRegistry::new();
+        registry.register("save", "Ctrl+S");
+
+        registry.update(&[key_chord(false, glutin::VirtualKeyCode::S)], 0.0);
+        assert!(!registry.triggered("save"));
+
+        let consumed = registry.update(&[key_chord(true, glutin::VirtualKeyCode::S)], 0.0);
+        assert!(registry.triggered("save"));
+        assert_eq!(consumed, vec![key_chord(true, glutin::VirtualKeyCode::S)]);
+
+        // triggered only lasts for the frame
+        registry.update(&[], 0.0);
+        assert!(!registry.triggered("save"));
+    }
+
+    #[test]
+    fn sequences_fire_within_the_timeout() {
+        let mut registry = ShortcutRegistry::new();
+        registry.register("comment", "Ctrl+K Ctrl+C");
+
+        registry.update(&[key_chord(true, glutin::VirtualKeyCode::K)], 0.0);
+        assert_eq!(registry.pending().len(), 1);
+        assert!(!registry.triggered("comment"));
+
+        registry.update(&[], 0.5);
+        registry.update(&[key_chord(true, glutin::VirtualKeyCode::C)], 0.4);
+        assert!(registry.triggered("comment"));
+        assert!(registry.pending().is_empty());
+    }
+
+    #[test]
+    fn sequences_time_out() {
+        let mut registry = ShortcutRegistry::new();
+        registry.register("comment", "Ctrl+K Ctrl+C");
+        registry.sequence_timeout = 1.0;
+
+        registry.update(&[key_chord(true, glutin::VirtualKeyCode::K)], 0.0);
+        registry.update(&[], 0.6);
+        let consumed = registry.update(&[key_chord(true, glutin::VirtualKeyCode::C)], 0.6);
+
+        assert!(!registry.triggered("comment"));
+        assert!(consumed.is_empty());
+        assert!(registry.pending().is_empty());
+    }
+
+    #[test]
+    fn a_wrong_chord_restarts_the_sequence() {
+        let mut registry = ShortcutRegistry::new();
+        registry.register("comment", "Ctrl+K Ctrl+C");
+
+        registry.update(&[key_chord(true, glutin::VirtualKeyCode::K)], 0.0);
+        registry.update(&[key_chord(true, glutin::VirtualKeyCode::K)], 0.1);
+        assert_eq!(registry.pending().len(), 1);
+
+        registry.update(&[key_chord(true, glutin::VirtualKeyCode::X)], 0.1);
+        assert!(registry.pending().is_empty());
+
+        registry.update(&[key_chord(true, glutin::VirtualKeyCode::C)], 0.1);
+        assert!(!registry.triggered("comment"));
+    }
+}
@@ -2,7 +2,13 @@ extern crate glium;
 extern crate image;
 use colored::*;
 
-pub fn load(display: &glium::Display, buffer: &[u8]) -> glium::texture::SrgbTexture2d {
+// returns None when there is no display to upload to, which is the case when running headless
+pub fn load(display: Option<&glium::Display>, buffer: &[u8]) -> Option<glium::texture::SrgbTexture2d> {
+	let display = match display {
+		Some(display) => display,
+		None => return None,
+	};
+
 	let image = image::load_from_memory(buffer);
 
 	if !image.is_ok() {
@@ -16,5 +22,5 @@ pub fn load(display: &glium::Display, buffer: &[u8]) -> glium::texture::SrgbText
 	let image =
 		glium::texture::RawImage2d::from_raw_rgba_reversed(&image.into_raw(), image_dimensions);
 
-	glium::texture::SrgbTexture2d::new(display, image).unwrap()
+	Some(glium::texture::SrgbTexture2d::new(display, image).unwrap())
 }
@@ -0,0 +1,319 @@
+extern crate stopwatch;
+
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use stopwatch::Stopwatch;
+
+// number of frames used for the min/max frame time statistics
+const FRAME_HISTORY: usize = 120;
+
+// how quickly the smoothed frame time follows the real one
+const SMOOTHING: f32 = 0.1;
+
+// a source of time in seconds, swap it out for a ManualClock to get deterministic frames
+pub trait Clock {
+    fn now(&mut self) -> f64;
+}
+
+pub struct StopwatchClock {
+    sw: Stopwatch,
+}
+
+impl StopwatchClock {
+    pub fn new() -> StopwatchClock {
+        StopwatchClock {
+            sw: Stopwatch::start_new(),
+        }
+    }
+}
+
+impl Clock for StopwatchClock {
+    fn now(&mut self) -> f64 {
+        let elapsed = self.sw.elapsed();
+        elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 / 1_000_000_000.0
+    }
+}
+
+// a clock that only moves when told to. clones share the same time, so keep a clone around to
+// advance the one that was handed to Time
+#[derive(Clone)]
+pub struct ManualClock {
+    time: Rc<Cell<f64>>,
+}
+
+#[allow(dead_code)]
+impl ManualClock {
+    pub fn new() -> ManualClock {
+        ManualClock {
+            time: Rc::new(Cell::new(0.0)),
+        }
+    }
+
+    pub fn advance(&self, seconds: f64) {
+        self.time.set(self.time.get() + seconds);
+    }
+
+    pub fn time(&self) -> f64 {
+        self.time.get()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&mut self) -> f64 {
+        self.time.get()
+    }
+}
+
+pub struct Time {
+    clock: Box<dyn Clock>,
+    last_time: f64,
+
+    delta_time: f32,
+    unscaled_delta_time: f32,
+    time_scale: f32,
+    paused: bool,
+
+    total_time: f64,
+    unscaled_total_time: f64,
+    frame_count: u64,
+
+    smoothed_frame_time: f32,
+    frame_history: VecDeque<f32>,
+}
+
+#[allow(dead_code)]
+impl Time {
+    pub fn new() -> Time {
+        Time::with_clock(Box::new(StopwatchClock::new()))
+    }
+
+    pub fn with_clock(mut clock: Box<dyn Clock>) -> Time {
+        let last_time = clock.now();
+
+        Time {
+            clock: clock,
+            last_time: last_time,
+            delta_time: 0.0,
+            unscaled_delta_time: 0.0,
+            time_scale: 1.0,
+            paused: false,
+            total_time: 0.0,
+            unscaled_total_time: 0.0,
+            frame_count: 0,
+            smoothed_frame_time: 0.0,
+            frame_history: VecDeque::with_capacity(FRAME_HISTORY),
+        }
+    }
+
+    // advance to the next frame, call this once at the start of every frame
+    pub fn tick(&mut self) {
+        let now = self.clock.now();
+        self.advance((now - self.last_time).max(0.0) as f32);
+        self.last_time = now;
+    }
+
+    // advance by a known frame time, used when replaying or running a fixed number of frames
+    pub fn advance(&mut self, unscaled_delta_time: f32) {
+        self.unscaled_delta_time = unscaled_delta_time;
+
+        self.delta_time = if self.paused {
+            0.0
+        } else {
+            unscaled_delta_time * self.time_scale
+        };
+
+        self.total_time += self.delta_time as f64;
+        self.unscaled_total_time += unscaled_delta_time as f64;
+        self.frame_count += 1;
+
+        if self.frame_count == 1 {
+            self.smoothed_frame_time = unscaled_delta_time;
+        } else {
+            self.smoothed_frame_time +=
+                (unscaled_delta_time - self.smoothed_frame_time) * SMOOTHING;
+        }
+
+        if self.frame_history.len() == FRAME_HISTORY {
+            self.frame_history.pop_front();
+        }
+        self.frame_history.push_back(unscaled_delta_time);
+    }
+
+    // scaled by the time scale and zero while paused
+    pub fn delta_time(&self) -> f32 {
+        self.delta_time
+    }
+
+    pub fn unscaled_delta_time(&self) -> f32 {
+        self.unscaled_delta_time
+    }
+
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale
+    }
+
+    pub fn set_time_scale(&mut self, time_scale: f32) {
+        self.time_scale = time_scale.max(0.0);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn total_time(&self) -> f64 {
+        self.total_time
+    }
+
+    pub fn unscaled_total_time(&self) -> f64 {
+        self.unscaled_total_time
+    }
+
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    // smoothed frame time in seconds
+    pub fn frame_time(&self) -> f32 {
+        self.smoothed_frame_time
+    }
+
+    pub fn fps(&self) -> f32 {
+        if self.smoothed_frame_time > 0.0 {
+            1.0 / self.smoothed_frame_time
+        } else {
+            0.0
+        }
+    }
+
+    pub fn min_frame_time(&self) -> f32 {
+        if self.frame_history.is_empty() {
+            return 0.0;
+        }
+
+        self.frame_history
+            .iter()
+            .cloned()
+            .fold(std::f32::MAX, f32::min)
+    }
+
+    pub fn max_frame_time(&self) -> f32 {
+        self.frame_history.iter().cloned().fold(0.0, f32::max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manual_time() -> (Time, ManualClock) {
+        let clock = ManualClock::new();
+        let time = Time::with_clock(Box::new(clock.clone()));
+        (time, clock)
+    }
+
+    #[test]
+    fn tick_reads_the_shared_manual_clock() {
+        let (mut time, clock) = manual_time();
+
+        clock.advance(0.25);
+        time.tick();
+        assert_eq!(time.delta_time(), 0.25);
+        assert_eq!(time.unscaled_delta_time(), 0.25);
+
+        clock.advance(0.5);
+        time.tick();
+        assert_eq!(time.delta_time(), 0.5);
+        assert_eq!(time.total_time(), 0.75);
+        assert_eq!(time.frame_count(), 2);
+
+        // nothing happened on the clock
+        time.tick();
+        assert_eq!(time.delta_time(), 0.0);
+    }
+
+    #[test]
+    fn time_scale_scales_only_the_scaled_values() {
+        let (mut time, clock) = manual_time();
+        time.set_time_scale(0.5);
+
+        clock.advance(1.0);
+        time.tick();
+        assert_eq!(time.delta_time(), 0.5);
+        assert_eq!(time.unscaled_delta_time(), 1.0);
+        assert_eq!(time.total_time(), 0.5);
+        assert_eq!(time.unscaled_total_time(), 1.0);
+
+        time.set_time_scale(-2.0);
+        assert_eq!(time.time_scale(), 0.0);
+    }
+
+    #[test]
+    fn pause_stops_scaled_time() {
+        let (mut time, clock) = manual_time();
+
+        time.set_paused(true);
+        clock.advance(0.5);
+        time.tick();
+        assert_eq!(time.delta_time(), 0.0);
+        assert_eq!(time.unscaled_delta_time(), 0.5);
+        assert_eq!(time.total_time(), 0.0);
+
+        time.set_paused(false);
+        clock.advance(0.5);
+        time.tick();
+        assert_eq!(time.delta_time(), 0.5);
+        assert_eq!(time.total_time(), 0.5);
+    }
+
+    #[test]
+    fn frame_history_keeps_the_latest_frames() {
+        let (mut time, _clock) = manual_time();
+
+        time.advance(5.0);
+        for _ in 0..FRAME_HISTORY {
+            time.advance(0.01);
+        }
+        assert_eq!(time.max_frame_time(), 0.01);
+        assert_eq!(time.min_frame_time(), 0.01);
+
+        time.advance(0.001);
+        assert_eq!(time.min_frame_time(), 0.001);
+    }
+
+    #[test]
+    fn long_frames_are_not_clamped() {
+        let (mut time, clock) = manual_time();
+
+        clock.advance(1.5);
+        time.tick();
+        assert_eq!(time.delta_time(), 1.5);
+        assert_eq!(time.unscaled_delta_time(), 1.5);
+        assert_eq!(time.total_time(), 1.5);
+        assert_eq!(time.max_frame_time(), 1.5);
+        assert_eq!(time.frame_time(), 1.5);
+    }
+
+    #[test]
+    fn long_frames_are_scaled_and_paused() {
+        let (mut time, clock) = manual_time();
+
+        time.set_time_scale(0.5);
+        clock.advance(1.5);
+        time.tick();
+        assert_eq!(time.delta_time(), 0.75);
+        assert_eq!(time.unscaled_delta_time(), 1.5);
+
+        time.set_paused(true);
+        clock.advance(1.5);
+        time.tick();
+        assert_eq!(time.delta_time(), 0.0);
+        assert_eq!(time.unscaled_delta_time(), 1.5);
+        assert_eq!(time.total_time(), 0.75);
+        assert_eq!(time.unscaled_total_time(), 3.0);
+    }
+}
@@ -23,7 +23,7 @@ pub struct UIContext {
     pub mouse_y: f32,
 
     elements: Vec<UIElement>,
-    program: glium::Program,
+    program: Option<glium::Program>,
 }
 
 enum UIType {
@@ -65,7 +65,7 @@ pub struct UIStyle {
 
 #[allow(dead_code)]
 impl UIContext {
-    pub fn new(display: &glium::Display, width: f32, height: f32) -> UIContext {
+    pub fn new(display: Option<&glium::Display>, width: f32, height: f32) -> UIContext {
         UIContext {
             style: UIStyle {
                 quad_color: [1.0, 1.0, 1.0, 1.0],
@@ -76,7 +76,7 @@ impl UIContext {
             },
             elements: Vec::new(),
             program: shader::load(
-                &display,
+                display,
                 &assets::get_asset("./res/ui_basic.vs"),
                 &assets::get_asset("./res/ui_basic.fs"),
             ),
@@ -229,6 +229,11 @@ impl UIContext {
 
         self.elements.clear();
     }
+
+    // throw away this frame's elements without drawing them, used when running headless
+    pub fn end_frame(&mut self) {
+        self.elements.clear();
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -309,7 +314,7 @@ fn draw_text(
 
                 let tex_srgb = glium::texture::SrgbTexture2d::new(display, raw_image);
 
-                tex = resources.alloc_tex(tex_srgb.unwrap());
+                tex = resources.alloc_tex(Some(tex_srgb.unwrap()));
 
                 resources.store_glyph(
                     element.text[i],
@@ -465,7 +470,7 @@ fn draw_quad(
         .draw(
             &vertex_buffer,
             &index_buffer,
-            &context.program,
+            context.program.as_ref().unwrap(),
             &uniforms,
             &params,
         )
@@ -13,6 +13,40 @@ impl Vector3 {
     pub fn raw(&self) -> [f32; 3] {
         [self.x, self.y, self.z]
     }
+
+    pub fn length(&self) -> f32 {
+        self.dot(*self).sqrt()
+    }
+
+    pub fn normalized(&self) -> Vector3 {
+        let length = self.length();
+
+        if length > 0.0 {
+            *self * (1.0 / length)
+        } else {
+            *self
+        }
+    }
+
+    pub fn dot(&self, other: Vector3) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn cross(&self, other: Vector3) -> Vector3 {
+        Vector3 {
+            x: self.y * other.z - self.z * other.y,
+            y: self.z * other.x - self.x * other.z,
+            z: self.x * other.y - self.y * other.x,
+        }
+    }
+
+    pub fn lerp(&self, other: Vector3, amount: f32) -> Vector3 {
+        Vector3 {
+            x: self.x + (other.x - self.x) * amount,
+            y: self.y + (other.y - self.y) * amount,
+            z: self.z + (other.z - self.z) * amount,
+        }
+    }
 }
 
 // Operator Overloads below
@@ -45,6 +79,18 @@ impl Mul for Vector3 {
     }
 }
 
+impl Mul<f32> for Vector3 {
+    type Output = Vector3;
+
+    fn mul(self, scale: f32) -> Vector3 {
+        Vector3 {
+            x: self.x * scale,
+            y: self.y * scale,
+            z: self.z * scale,
+        }
+    }
+}
+
 impl Add for Vector3 {
     type Output = Vector3;
 
@@ -0,0 +1,53 @@
+// runs scenes through the headless runner, nothing here needs a window or a gl context
+extern crate game_engine;
+
+use game_engine::app::{App, Engine};
+use game_engine::assets;
+use game_engine::ecs::Transform;
+use game_engine::quaternion::Quaternion;
+use game_engine::runner::{self, HeadlessConfig};
+use game_engine::vector::Vector3;
+
+// the demo's physics sphere dropped from the height the demo spawns its spheres at
+struct FallingSphere;
+
+impl App for FallingSphere {
+    fn init(&mut self, engine: &mut Engine) {
+        let prefab = engine.prefabs.load("./res/prefabs/sphere.prefab");
+
+        engine.prefabs.instantiate(
+            engine.display.as_ref(),
+            &mut engine.resources,
+            &mut engine.physics,
+            &mut engine.entities,
+            &prefab,
+            Transform::new(
+                Vector3::new(0.0, 10.0, 0.0),
+                Quaternion::new(0.0, 0.0, 0.0, 1.0),
+            ),
+        );
+    }
+}
+
+fn sphere_height(engine: &Engine) -> f32 {
+    let sphere = engine.entities.find_by_name("sphere").unwrap();
+    engine.entities.transforms.get(sphere).unwrap().position.y
+}
+
+#[test]
+fn sphere_falls() {
+    // read the prefab and its model straight from res, building the pack would overwrite the
+    // one in the working directory
+    assets::set_read_from_disk(true);
+
+    let mut app = FallingSphere;
+    let engine = runner::run_headless_until(&mut app, HeadlessConfig::new(180), |_, engine| {
+        sphere_height(engine) < 9.0
+    });
+
+    assert!(sphere_height(&engine) < 10.0, "sphere did not fall");
+    assert!(
+        engine.time.frame_count() < 180,
+        "sphere took longer than 3 seconds to fall a unit"
+    );
+}